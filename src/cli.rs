@@ -1,16 +1,18 @@
-use clap::{crate_authors, crate_description, crate_version, App, Arg};
+use clap::{crate_authors, crate_description, crate_version, App, AppSettings, Arg, SubCommand};
 
 pub fn build_cli() -> App<'static, 'static> {
     App::new("Kukuri")
         .version(crate_version!())
         .author(crate_authors!())
         .about(crate_description!())
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(Arg::with_name("config")
             .help("Set a config file")
             .short("c")
             .long("config")
             .value_name("FILE")
             .takes_value(true)
+            .global(true)
         )
         .arg(Arg::with_name("dir")
             .help("Set output directory")
@@ -18,6 +20,7 @@ pub fn build_cli() -> App<'static, 'static> {
             .long("dir")
             .value_name("DIRECTORY")
             .takes_value(true)
+            .global(true)
         )
         .arg(Arg::with_name("l10n_dir")
             .help("Set l10n output directory")
@@ -25,10 +28,35 @@ pub fn build_cli() -> App<'static, 'static> {
             .long("l10n_dir")
             .value_name("DIRECTORY")
             .takes_value(true)
+            .global(true)
         )
         .arg(Arg::with_name("FILE")
             .help("Set input file(s)")
             .required(true)
             .min_values(1)
         )
+        .subcommand(SubCommand::with_name("glyphs")
+            .about("Export used characters per locale for font subsetting")
+            .arg(Arg::with_name("FILE")
+                .help("Set input script(s) and translated .po file(s)")
+                .required(true)
+                .min_values(1)
+            )
+        )
+        .subcommand(SubCommand::with_name("check-fonts")
+            .about("Report texts using characters missing in locale fonts")
+            .arg(Arg::with_name("FILE")
+                .help("Set input script(s) and translated .po file(s)")
+                .required(true)
+                .min_values(1)
+            )
+        )
+        .subcommand(SubCommand::with_name("decompile")
+            .about("Render scenes of input files back into KukuriScript")
+            .arg(Arg::with_name("FILE")
+                .help("Set input file(s), e.g. json output")
+                .required(true)
+                .min_values(1)
+            )
+        )
 }
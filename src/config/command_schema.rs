@@ -0,0 +1,571 @@
+use crate::core::command::CommandArg;
+use crate::core::diagnostic::Diagnostic;
+use crate::core::dialog::Scene;
+use crate::core::meta::MetaValue;
+use crate::utils;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+// Project level command schema
+//
+// [commands.bgm]
+// params = [
+//   { name = "file", type = "string" },
+//   { name = "volume", type = "float", default = 1.0 },
+// ]
+//
+// scene header keys are also validated, if any is defined.
+//
+// [header.chapter]
+// type = "int"
+// required = true
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CommandSchema {
+    pub commands: BTreeMap<String, CommandDef>,
+    pub header: BTreeMap<String, HeaderDef>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HeaderDef {
+    #[serde(rename = "type")]
+    pub value_type: HeaderType,
+    pub required: bool,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeaderType {
+    String,
+    Int,
+    Float,
+    Bool,
+    Array,
+    Table,
+    #[default]
+    Any,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CommandDef {
+    pub params: Vec<ParamDef>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ParamDef {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub param_type: ParamType,
+    // a parameter with default value is optional
+    pub default: Option<toml::Value>,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    String,
+    Int,
+    Float,
+    Bool,
+    Ident,
+    #[default]
+    Any,
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ParamType::String => "string",
+            ParamType::Int => "int",
+            ParamType::Float => "float",
+            ParamType::Bool => "bool",
+            ParamType::Ident => "ident",
+            ParamType::Any => "any",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl ParamType {
+    pub fn accepts(&self, arg: &CommandArg) -> bool {
+        // bare identifier is also a string
+        matches!(
+            (self, arg),
+            (ParamType::Any, _)
+                | (ParamType::String, CommandArg::Str(_))
+                | (ParamType::String, CommandArg::Ident(_))
+                | (ParamType::Int, CommandArg::Int(_))
+                | (ParamType::Float, CommandArg::Int(_))
+                | (ParamType::Float, CommandArg::Float(_))
+                | (ParamType::Bool, CommandArg::Bool(_))
+                | (ParamType::Ident, CommandArg::Ident(_))
+        )
+    }
+
+    fn accepts_toml(&self, v: &toml::Value) -> bool {
+        matches!(
+            (self, v),
+            (ParamType::Any, _)
+                | (ParamType::String, toml::Value::String(_))
+                | (ParamType::Ident, toml::Value::String(_))
+                | (ParamType::Int, toml::Value::Integer(_))
+                | (ParamType::Float, toml::Value::Integer(_))
+                | (ParamType::Float, toml::Value::Float(_))
+                | (ParamType::Bool, toml::Value::Boolean(_))
+        )
+    }
+}
+
+impl fmt::Display for HeaderType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            HeaderType::String => "string",
+            HeaderType::Int => "int",
+            HeaderType::Float => "float",
+            HeaderType::Bool => "bool",
+            HeaderType::Array => "array",
+            HeaderType::Table => "table",
+            HeaderType::Any => "any",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl HeaderType {
+    pub fn accepts(&self, v: &MetaValue) -> bool {
+        matches!(
+            (self, v),
+            (HeaderType::Any, _)
+                | (HeaderType::String, MetaValue::Str(_))
+                | (HeaderType::Int, MetaValue::Int(_))
+                | (HeaderType::Float, MetaValue::Int(_))
+                | (HeaderType::Float, MetaValue::Float(_))
+                | (HeaderType::Bool, MetaValue::Bool(_))
+                | (HeaderType::Array, MetaValue::Array(_))
+                | (HeaderType::Table, MetaValue::Table(_))
+        )
+    }
+}
+
+fn arg_type_name(arg: &CommandArg) -> &str {
+    match arg {
+        CommandArg::Str(_) => "string",
+        CommandArg::Int(_) => "int",
+        CommandArg::Float(_) => "float",
+        CommandArg::Bool(_) => "bool",
+        CommandArg::Ident(_) => "ident",
+        CommandArg::Named(_, _) => "named argument",
+    }
+}
+
+impl ParamDef {
+    pub fn is_optional(&self) -> bool {
+        self.default.is_some()
+    }
+}
+
+impl CommandSchema {
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        let schema: CommandSchema = toml::from_str(s)?;
+        Ok(schema)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let s = match utils::read_file(path.as_ref()) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!(
+                    "Failed to load command schema {}: {:?}",
+                    path.as_ref().display(),
+                    e
+                );
+                return None;
+            }
+        };
+
+        match CommandSchema::parse(&s) {
+            Ok(schema) => {
+                Diagnostic::print_all(path.as_ref().display().to_string(), &schema.check());
+                Some(schema)
+            }
+            Err(e) => {
+                eprintln!("Command schema toml parse error: {:?}", e);
+                None
+            }
+        }
+    }
+
+    // check the schema itself: default value types and parameter order
+    pub fn check(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for (name, def) in &self.commands {
+            let mut is_optional_found = false;
+            for p in &def.params {
+                if let Some(v) = &p.default {
+                    if !p.param_type.accepts_toml(v) {
+                        diagnostics.push(Diagnostic::error(
+                            0,
+                            format!(
+                                "default value of `{}.{}` is not {}",
+                                name, p.name, p.param_type
+                            ),
+                        ));
+                    }
+                    is_optional_found = true;
+                } else if is_optional_found {
+                    diagnostics.push(Diagnostic::error(
+                        0,
+                        format!(
+                            "required parameter `{}.{}` follows an optional parameter",
+                            name, p.name
+                        ),
+                    ));
+                }
+            }
+        }
+        diagnostics
+    }
+
+    // validate scene headers and every command in scenes
+    pub fn validate(&self, scenes: &[Scene]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for sc in scenes {
+            if !self.header.is_empty() {
+                diagnostics.extend(
+                    self.validate_header(sc)
+                        .into_iter()
+                        .map(|diag| diag.at(&sc.loc)),
+                );
+            }
+            sc.walk_dialogs(|d| {
+                for (id, args) in d.command_calls() {
+                    diagnostics.extend(
+                        self.validate_command(id, &args)
+                            .into_iter()
+                            .map(|diag| diag.at(&d.loc)),
+                    );
+                }
+            });
+        }
+        diagnostics
+    }
+
+    fn validate_header(&self, sc: &Scene) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for (key, value) in &sc.meta {
+            let def = match self.header.get(key) {
+                Some(def) => def,
+                None => {
+                    let mut message = format!("unknown header key `{}`", key);
+                    if let Some(s) = Self::similar_key(key, self.header.keys()) {
+                        message.push_str(&format!(", did you mean `{}`?", s));
+                    }
+                    diagnostics.push(Diagnostic::warning(0, message));
+                    continue;
+                }
+            };
+
+            if !def.value_type.accepts(value) {
+                diagnostics.push(Diagnostic::error(
+                    0,
+                    format!(
+                        "header `{}` expects {}, found {}",
+                        key,
+                        def.value_type,
+                        value.type_name()
+                    ),
+                ));
+            }
+        }
+
+        for (key, def) in &self.header {
+            if def.required && !sc.meta.contains_key(key) {
+                diagnostics.push(Diagnostic::error(
+                    0,
+                    format!("scene `{}` requires header `{}`", sc.title, key),
+                ));
+            }
+        }
+
+        diagnostics
+    }
+
+    fn validate_command(&self, id: &str, args: &[&CommandArg]) -> Vec<Diagnostic> {
+        let def = match self.commands.get(id) {
+            Some(def) => def,
+            None => {
+                let mut message = format!("unknown command `{}`", id);
+                if let Some(s) = Self::similar_key(id, self.commands.keys()) {
+                    message.push_str(&format!(", did you mean `{}`?", s));
+                }
+                return vec![Diagnostic::error(0, message)];
+            }
+        };
+
+        let mut diagnostics = Vec::new();
+        let mut is_given = vec![false; def.params.len()];
+        let mut positional_idx = 0;
+
+        for arg in args {
+            let (param_idx, value) = match arg {
+                CommandArg::Named(name, value) => {
+                    match def.params.iter().position(|p| &p.name == name) {
+                        Some(i) => (i, value.as_ref()),
+                        None => {
+                            diagnostics.push(Diagnostic::error(
+                                0,
+                                format!("command `{}` has no parameter `{}`", id, name),
+                            ));
+                            continue;
+                        }
+                    }
+                }
+                _ => {
+                    positional_idx += 1;
+                    (positional_idx - 1, *arg)
+                }
+            };
+
+            let param = match def.params.get(param_idx) {
+                Some(p) => p,
+                // reported after all arguments are counted
+                None => continue,
+            };
+
+            if is_given[param_idx] {
+                diagnostics.push(Diagnostic::error(
+                    0,
+                    format!("argument `{}` of `{}` is given twice", param.name, id),
+                ));
+            }
+            is_given[param_idx] = true;
+
+            if !param.param_type.accepts(value) {
+                diagnostics.push(Diagnostic::error(
+                    0,
+                    format!(
+                        "argument `{}` of `{}` expects {}, found {}",
+                        param.name,
+                        id,
+                        param.param_type,
+                        arg_type_name(value)
+                    ),
+                ));
+            }
+        }
+
+        if positional_idx > def.params.len() {
+            diagnostics.push(Diagnostic::error(
+                0,
+                format!(
+                    "command `{}` takes at most {} argument(s), but {} given",
+                    id,
+                    def.params.len(),
+                    positional_idx
+                ),
+            ));
+        }
+
+        for (p, is_given) in def.params.iter().zip(is_given) {
+            if !is_given && !p.is_optional() {
+                diagnostics.push(Diagnostic::error(
+                    0,
+                    format!("command `{}` requires argument `{}`", id, p.name),
+                ));
+            }
+        }
+
+        diagnostics
+    }
+
+    fn similar_key<'a, I: Iterator<Item = &'a String>>(id: &str, keys: I) -> Option<&'a str> {
+        keys.map(|k| (utils::edit_distance(id, k), k))
+            .filter(|&(d, k)| d <= 2 && d < k.chars().count())
+            .min()
+            .map(|(_, k)| k.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandSchema, ParamType};
+    use crate::core::command::{CommandArg, Float, InlineCommand};
+    use crate::core::diagnostic::{Diagnostic, SourceLoc};
+    use crate::core::dialog::{Dialog, DialogBody, DialogKind, Scene};
+    use crate::core::meta::MetaValue;
+
+    const SCHEMA_SRC: &str = r#"
+[commands.jump]
+params = [{ name = "scene", type = "ident" }]
+
+[commands.bgm]
+params = [
+  { name = "file", type = "string" },
+  { name = "volume", type = "float", default = 1.0 },
+]
+
+[commands.wait]
+params = [{ name = "seconds", type = "float" }]
+"#;
+
+    fn gen_command(id: &str, args: Vec<CommandArg>, line: usize) -> Dialog {
+        Dialog::from_dialog_data(
+            DialogKind::Command,
+            id,
+            args.into_iter().map(DialogBody::Arg).collect(),
+        )
+        .with_loc(SourceLoc {
+            path: String::from("test.kkr"),
+            line,
+        })
+    }
+
+    #[test]
+    fn test_parse() {
+        let schema = CommandSchema::parse(SCHEMA_SRC).unwrap();
+        assert_eq!(3, schema.commands.len());
+
+        let bgm = &schema.commands["bgm"];
+        assert_eq!(ParamType::String, bgm.params[0].param_type);
+        assert!(!bgm.params[0].is_optional());
+        assert_eq!(ParamType::Float, bgm.params[1].param_type);
+        assert!(bgm.params[1].is_optional());
+        assert!(schema.check().is_empty());
+    }
+
+    #[test]
+    fn test_check() {
+        let schema = CommandSchema::parse(
+            r#"
+[commands.bad]
+params = [
+  { name = "a", type = "int", default = "zero" },
+  { name = "b" },
+]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                Diagnostic::error(0, "default value of `bad.a` is not int"),
+                Diagnostic::error(
+                    0,
+                    "required parameter `bad.b` follows an optional parameter"
+                ),
+            ],
+            schema.check()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = CommandSchema::parse(SCHEMA_SRC).unwrap();
+        let mut sc = Scene::new();
+        sc.dialogs = vec![
+            gen_command("jump", vec![CommandArg::gen_ident("Opening")], 1),
+            gen_command("bgm", vec![CommandArg::gen_str("a.ogg")], 2),
+            gen_command(
+                "bgm",
+                vec![
+                    CommandArg::gen_ident("theme"),
+                    CommandArg::Named(String::from("volume"), Box::new(CommandArg::Int(1))),
+                ],
+                3,
+            ),
+            gen_command("wait", vec![CommandArg::Float(Float(0.5))], 4),
+            gen_command("shwo", Vec::new(), 5),
+            gen_command("jump", vec![CommandArg::gen_str("a b")], 6),
+            gen_command("wait", Vec::new(), 7),
+            gen_command(
+                "wait",
+                vec![CommandArg::Int(1), CommandArg::Int(2), CommandArg::Int(3)],
+                8,
+            ),
+            gen_command(
+                "bgm",
+                vec![
+                    CommandArg::gen_str("a.ogg"),
+                    CommandArg::Named(String::from("speed"), Box::new(CommandArg::Int(1))),
+                ],
+                9,
+            ),
+            gen_command("jamp", Vec::new(), 10),
+            Dialog::from_dialog_data(DialogKind::Dialog, "Test_11_A", Vec::new())
+                .with_inline_commands(vec![
+                    InlineCommand::from_command_data(0, "wait", vec![CommandArg::Int(1)]),
+                    InlineCommand::from_command_data(2, "wiat", vec![CommandArg::Int(1)]),
+                ])
+                .with_loc(SourceLoc {
+                    path: String::from("test.kkr"),
+                    line: 11,
+                }),
+        ];
+
+        let expected: Vec<Diagnostic> = vec![
+            (5, "unknown command `shwo`"),
+            (6, "argument `scene` of `jump` expects ident, found string"),
+            (7, "command `wait` requires argument `seconds`"),
+            (8, "command `wait` takes at most 1 argument(s), but 3 given"),
+            (9, "command `bgm` has no parameter `speed`"),
+            (10, "unknown command `jamp`, did you mean `jump`?"),
+            (11, "unknown command `wiat`, did you mean `wait`?"),
+        ]
+        .into_iter()
+        .map(|(line, message)| {
+            Diagnostic::error(0, message).at(&SourceLoc {
+                path: String::from("test.kkr"),
+                line,
+            })
+        })
+        .collect();
+
+        assert_eq!(expected, schema.validate(&[sc]));
+    }
+
+    #[test]
+    fn test_validate_header() {
+        let schema = CommandSchema::parse(
+            r#"
+[header.chapter]
+type = "int"
+required = true
+
+[header.bgm]
+type = "string"
+
+[header.custom]
+type = "table"
+"#,
+        )
+        .unwrap();
+
+        let mut sc = Scene::new();
+        sc.title = String::from("Test");
+        sc.loc = SourceLoc {
+            path: String::from("test.kkr"),
+            line: 1,
+        };
+        sc.meta.insert(String::from("bgm"), MetaValue::Int(1));
+        sc.meta
+            .insert(String::from("bmg"), MetaValue::Str(String::from("a.ogg")));
+        sc.meta
+            .insert(String::from("custom"), MetaValue::Table(Default::default()));
+
+        let loc = sc.loc.clone();
+        let expected: Vec<Diagnostic> = vec![
+            Diagnostic::error(0, "header `bgm` expects string, found int"),
+            Diagnostic::warning(0, "unknown header key `bmg`, did you mean `bgm`?"),
+            Diagnostic::error(0, "scene `Test` requires header `chapter`"),
+        ]
+        .into_iter()
+        .map(|diag| diag.at(&loc))
+        .collect();
+
+        assert_eq!(expected, schema.validate(&[sc]));
+    }
+}
@@ -1,7 +1,13 @@
+pub mod command_schema;
+pub mod spreadsheet;
+pub mod textbox;
+
 use crate::utils;
 use serde::{Deserialize, Serialize};
+use spreadsheet::SpreadsheetConfig;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
-use toml;
+use textbox::TextBoxConfig;
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd)]
 #[serde(default)]
@@ -13,8 +19,30 @@ pub struct Config {
     pub default_script_type: String,
     pub outputs: Vec<String>,
     pub l10n_outputs: Vec<String>,
+    // markup dialect of l10n texts
+    pub l10n_markup: String,
+    // command binding outputs for engine side
+    pub binding_outputs: Vec<String>,
     pub output_dir: PathBuf,
     pub l10n_output_dir: PathBuf,
+    // command schema toml file path
+    pub command_schema: Option<PathBuf>,
+    // KukuriScript macros toml file path
+    pub macros: Option<PathBuf>,
+    // if true => scene titles are qualified with input file path
+    // e.g. "<script_dir>/chapter1/intro.kkr" => "chapter1.intro.Opening"
+    pub namespace_from_path: bool,
+    pub script_dir: PathBuf,
+    // commands taking a scene title as the first argument
+    pub jump_commands: Vec<String>,
+    // talker id of Fountain action lines, None => Command "action"
+    pub fountain_narrator: Option<String>,
+    // font file path per locale for glyph coverage check
+    pub fonts: BTreeMap<String, PathBuf>,
+    // message window size for line wrapping
+    pub textbox: TextBoxConfig,
+    // column mapping of CSV/TSV scripts
+    pub spreadsheet: SpreadsheetConfig,
 }
 
 impl Config {
@@ -41,10 +69,24 @@ impl Config {
 
         conf.output_dir = Self::get_relative_dir(&path, &conf.output_dir);
         conf.l10n_output_dir = Self::get_relative_dir(&path, &conf.l10n_output_dir);
+        conf.script_dir = Self::get_relative_dir(&path, &conf.script_dir);
+        conf.command_schema = conf
+            .command_schema
+            .map(|p| Self::get_relative_dir(&path, &p));
+        conf.macros = conf.macros.map(|p| Self::get_relative_dir(&path, &p));
+        for p in conf.fonts.values_mut() {
+            *p = Self::get_relative_dir(&path, p);
+        }
 
         conf
     }
 
+    // font of a locale, "ja_JP" falls back to "ja"
+    pub fn font_for(&self, locale: &str) -> Option<&PathBuf> {
+        let lang = locale.split(['_', '-']).next().unwrap_or("");
+        self.fonts.get(locale).or_else(|| self.fonts.get(lang))
+    }
+
     fn get_relative_dir<P: AsRef<Path>, P2: AsRef<Path>>(
         base_path: &P,
         append_path: &P2,
@@ -69,8 +111,19 @@ impl Default for Config {
             default_script_type: String::from("kukuri"),
             outputs: vec![String::from("gd")],
             l10n_outputs: vec![String::from("po")],
+            l10n_markup: String::from("bbcode"),
+            binding_outputs: Vec::new(),
             output_dir: current_dir.clone(),
             l10n_output_dir: current_dir.clone(),
+            command_schema: None,
+            macros: None,
+            namespace_from_path: false,
+            script_dir: current_dir,
+            jump_commands: vec![String::from("jump")],
+            fountain_narrator: None,
+            fonts: BTreeMap::new(),
+            textbox: TextBoxConfig::default(),
+            spreadsheet: SpreadsheetConfig::default(),
         }
     }
 }
@@ -106,7 +159,9 @@ separate_output = false
 orig_locale = 'ja_JP'
 default_script_type = 'yarn'
 outputs = ['gd', 'json']
-l10n_outputs = ['po', 'fluent']";
+l10n_outputs = ['po', 'fluent']
+l10n_markup = 'tmp'
+command_schema = 'commands.toml'";
 
         let conf_str1 = "\
 orig_locale = 'fr_FR'
@@ -120,6 +175,8 @@ separate_output = false
             default_script_type: String::from("yarn"),
             outputs: vec![String::from("gd"), String::from("json")],
             l10n_outputs: vec![String::from("po"), String::from("fluent")],
+            l10n_markup: String::from("tmp"),
+            command_schema: Some(PathBuf::from("commands.toml")),
             ..Default::default()
         };
 
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+// Header names of CSV/TSV columns
+//
+// [spreadsheet]
+// scene = "Scene"
+// text = "Line (ja)"
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpreadsheetConfig {
+    // scene title, rows without it are in the previous row's scene
+    pub scene: String,
+    // dialog or option id, generated if empty
+    pub id: String,
+    // "option" for option rows, empty or "dialog" for dialog rows
+    #[serde(rename = "type")]
+    pub kind: String,
+    // talker id, ignored in option rows
+    pub talker: String,
+    pub text: String,
+    // id of the option (or the dialog before the choices) a row belongs to
+    pub parent: String,
+}
+
+impl Default for SpreadsheetConfig {
+    fn default() -> Self {
+        SpreadsheetConfig {
+            scene: String::from("scene"),
+            id: String::from("id"),
+            kind: String::from("type"),
+            talker: String::from("talker"),
+            text: String::from("text"),
+            parent: String::from("parent"),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Message window size for automatic line wrapping
+//
+// [textbox]
+// columns = 20
+// rows = 3
+//
+// [textbox.talkers.Narrator]
+// columns = 30
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextBoxConfig {
+    // full-width characters per line, 0 means no wrapping
+    pub columns: usize,
+    // lines per page, 0 means no page limit
+    pub rows: usize,
+    // if true => insert page breaks ("\f") instead of overflow warnings
+    pub page_break: bool,
+    // if true => East Asian Ambiguous characters (e.g. "…") are full-width
+    pub ambiguous_wide: bool,
+    // talker id: textbox size override
+    pub talkers: BTreeMap<String, TextBoxSize>,
+}
+
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextBoxSize {
+    pub columns: Option<usize>,
+    pub rows: Option<usize>,
+}
+
+impl TextBoxConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.columns > 0 || self.talkers.values().any(|s| s.columns.unwrap_or(0) > 0)
+    }
+
+    // return: (columns, rows) of the talker
+    pub fn size_for(&self, talker: &str) -> (usize, usize) {
+        match self.talkers.get(talker) {
+            Some(s) => (
+                s.columns.unwrap_or(self.columns),
+                s.rows.unwrap_or(self.rows),
+            ),
+            None => (self.columns, self.rows),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextBoxConfig;
+
+    #[test]
+    fn test_size_for() {
+        let conf: TextBoxConfig = toml::from_str(
+            "
+columns = 20
+rows = 3
+
+[talkers.N]
+columns = 30
+",
+        )
+        .unwrap();
+
+        assert!(conf.is_enabled());
+        assert_eq!((20, 3), conf.size_for("A"));
+        assert_eq!((30, 3), conf.size_for("N"));
+        assert!(!TextBoxConfig::default().is_enabled());
+    }
+}
@@ -0,0 +1,136 @@
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::Ordering;
+
+// f64 wrapper with total ordering,
+// so that command arguments can be compared like other dialog data.
+#[derive(Copy, Clone, Debug)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Float {}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Serialize for Float {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_f64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Float {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        f64::deserialize(deserializer).map(Float)
+    }
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize)]
+pub enum CommandArg {
+    // "quoted string" or non-identifier bare word
+    Str(String),
+    Int(i64),
+    Float(Float),
+    Bool(bool),
+    // bare identifier e.g. scene title
+    Ident(String),
+    // key=value
+    Named(String, Box<CommandArg>),
+}
+
+impl CommandArg {
+    pub fn gen_str<T: AsRef<str>>(s: T) -> Self {
+        Self::Str(String::from(s.as_ref()))
+    }
+
+    pub fn gen_ident<T: AsRef<str>>(s: T) -> Self {
+        Self::Ident(String::from(s.as_ref()))
+    }
+}
+
+impl Serialize for CommandArg {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            // flatten serialize output
+            Self::Str(ref s) | Self::Ident(ref s) => serializer.serialize_str(s),
+            Self::Int(n) => serializer.serialize_i64(n),
+            Self::Float(ref f) => f.serialize(serializer),
+            Self::Bool(b) => serializer.serialize_bool(b),
+            // named argument as single entry map: {"key": value}
+            Self::Named(ref k, ref v) => {
+                let mut sm = serializer.serialize_map(Some(1))?;
+                sm.serialize_entry(k, v)?;
+                sm.end()
+            }
+        }
+    }
+}
+
+// Command placed in the middle of dialog text. e.g. "Wait[wait 0.5]... what?"
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct InlineCommand {
+    // char offset in decoded dialog text
+    pub offset: usize,
+    pub id: String,
+    pub args: Vec<CommandArg>,
+}
+
+impl InlineCommand {
+    pub fn from_command_data<T: AsRef<str>>(offset: usize, id: T, args: Vec<CommandArg>) -> Self {
+        Self {
+            offset,
+            id: String::from(id.as_ref()),
+            args,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandArg, Float};
+
+    #[test]
+    fn test_serialize() {
+        let tests = [
+            (CommandArg::gen_str("Hello world"), r#""Hello world""#),
+            (CommandArg::gen_ident("DialogTest2"), r#""DialogTest2""#),
+            (CommandArg::Int(-3), "-3"),
+            (CommandArg::Float(Float(0.5)), "0.5"),
+            (CommandArg::Bool(true), "true"),
+            (
+                CommandArg::Named(
+                    String::from("volume"),
+                    Box::new(CommandArg::Float(Float(0.8))),
+                ),
+                r#"{"volume":0.8}"#,
+            ),
+        ];
+
+        for (src, expected) in &tests {
+            assert_eq!(*expected, serde_json::to_string(src).unwrap());
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Level::Warning => write!(f, "warning"),
+            Level::Error => write!(f, "error"),
+        }
+    }
+}
+
+// Diagnostic message with a source line number.
+// line 0 means "no specific line".
+// empty path means "the source currently parsed".
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub path: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn warning<T: AsRef<str>>(line: usize, message: T) -> Self {
+        Self {
+            level: Level::Warning,
+            path: String::new(),
+            line,
+            message: String::from(message.as_ref()),
+        }
+    }
+
+    pub fn error<T: AsRef<str>>(line: usize, message: T) -> Self {
+        Self {
+            level: Level::Error,
+            path: String::new(),
+            line,
+            message: String::from(message.as_ref()),
+        }
+    }
+
+    // point a diagnostic at a dialog location
+    pub fn at(mut self, loc: &SourceLoc) -> Self {
+        self.path = loc.path.clone();
+        self.line = loc.line;
+        self
+    }
+
+    // print diagnostics into stderr,
+    // source name is used when a diagnostic has no path.
+    pub fn print_all<T: AsRef<str>>(source: T, diagnostics: &[Diagnostic]) {
+        for d in diagnostics {
+            let path = if d.path.is_empty() {
+                source.as_ref()
+            } else {
+                &d.path
+            };
+            eprintln!("{}:{}", path, d);
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.line, self.level, self.message)
+    }
+}
+
+// Location of a parsed item in source file.
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct SourceLoc {
+    pub path: String,
+    pub line: usize,
+}
+
+impl SourceLoc {
+    pub fn from_line(line: usize) -> Self {
+        Self {
+            path: String::new(),
+            line,
+        }
+    }
+}
+
+impl fmt::Display for SourceLoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.path, self.line)
+    }
+}
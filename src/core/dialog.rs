@@ -1,3 +1,7 @@
+use super::command::{CommandArg, InlineCommand};
+use super::diagnostic::SourceLoc;
+use super::markup::{Dialect, RichText};
+use super::meta::SceneMeta;
 use serde::ser::{SerializeStruct, Serializer};
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
@@ -13,15 +17,37 @@ pub enum DialogKind {
 
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize)]
 pub enum DialogBody {
-    // Text(DialogBody || CommandArg)
+    // Text(DialogBody || TalkerId)
     Text(String),
     Choice(ChoiceData),
+    // typed command argument
+    Arg(CommandArg),
+    // dialog text with markup
+    Rich(RichText),
 }
 
 impl DialogBody {
     pub fn gen_text<T: AsRef<str>>(s: T) -> Self {
         Self::Text(String::from(s.as_ref()))
     }
+
+    // text without markup is kept as Text
+    pub fn from_rich_text(rt: RichText) -> Self {
+        if rt.is_plain() {
+            Self::Text(rt.plain_text())
+        } else {
+            Self::Rich(rt)
+        }
+    }
+
+    // Text or Rich as span tree
+    pub fn rich_text(&self) -> Option<RichText> {
+        match self {
+            Self::Text(s) => Some(RichText::from_plain(s)),
+            Self::Rich(rt) => Some(rt.clone()),
+            _ => None,
+        }
+    }
 }
 
 impl Serialize for DialogBody {
@@ -33,6 +59,8 @@ impl Serialize for DialogBody {
             // flatten serialize output
             Self::Text(ref s) => serializer.serialize_str(s),
             Self::Choice(ref cd) => serializer.serialize_newtype_struct("ChoiceData", cd),
+            Self::Arg(ref arg) => arg.serialize(serializer),
+            Self::Rich(ref rt) => rt.serialize(serializer),
         }
     }
 }
@@ -45,15 +73,23 @@ pub struct Dialog {
     // if DialogKind::Choices => Choices id e.g. "SceneTitle_idx_C1"
     pub id: String,
     pub args: Vec<DialogBody>,
+    // commands placed in dialog text, e.g. "Wait[wait 0.5]... what?"
+    #[serde(default)]
+    pub inline_commands: Vec<InlineCommand>,
+    // source location, not serialized
+    #[serde(skip)]
+    pub loc: SourceLoc,
 }
 
 impl Dialog {
-    #[allow(dead_code)]
+    #[cfg(test)]
     pub fn new() -> Self {
         Dialog {
             kind: DialogKind::Dialog,
             id: String::new(),
             args: Vec::new(),
+            inline_commands: Vec::new(),
+            loc: SourceLoc::default(),
         }
     }
 
@@ -62,6 +98,70 @@ impl Dialog {
             kind,
             id: String::from(id.as_ref()),
             args,
+            inline_commands: Vec::new(),
+            loc: SourceLoc::default(),
+        }
+    }
+
+    pub fn with_loc(mut self, loc: SourceLoc) -> Self {
+        self.loc = loc;
+        self
+    }
+
+    pub fn with_inline_commands(mut self, inline_commands: Vec<InlineCommand>) -> Self {
+        self.inline_commands = inline_commands;
+        self
+    }
+
+    // render dialog text markup into an engine dialect
+    pub fn render_markup(&mut self, dialect: Dialect) {
+        if self.kind != DialogKind::Dialog {
+            return;
+        }
+        if let Some(body) = self.args.first_mut() {
+            if let Some(rt) = body.rich_text() {
+                *body = DialogBody::Text(rt.render(dialect));
+            }
+        }
+    }
+
+    // all command calls in this dialog: (id, args)
+    // a command dialog itself and inline commands in its text
+    pub fn command_calls(&self) -> Vec<(&str, Vec<&CommandArg>)> {
+        let mut calls = Vec::new();
+        if self.kind == DialogKind::Command {
+            let args = self
+                .args
+                .iter()
+                .filter_map(|body| match body {
+                    DialogBody::Arg(arg) => Some(arg),
+                    _ => None,
+                })
+                .collect();
+            calls.push((self.id.as_str(), args));
+        }
+        for ic in &self.inline_commands {
+            calls.push((ic.id.as_str(), ic.args.iter().collect()));
+        }
+        calls
+    }
+
+    // visit this dialog and all dialogs in its choices
+    pub fn walk<F: FnMut(&Dialog)>(&self, f: &mut F) {
+        f(self);
+        for arg in &self.args {
+            if let DialogBody::Choice(cd) = arg {
+                cd.dialogs.iter().for_each(|d| d.walk(f));
+            }
+        }
+    }
+
+    pub fn walk_mut<F: FnMut(&mut Dialog)>(&mut self, f: &mut F) {
+        f(self);
+        for arg in &mut self.args {
+            if let DialogBody::Choice(cd) = arg {
+                cd.dialogs.iter_mut().for_each(|d| d.walk_mut(f));
+            }
         }
     }
 }
@@ -74,7 +174,12 @@ impl Serialize for Dialog {
         let is_exclude_orig_text =
             std::env::var("KUKURI_IS_EXCLUDE_ORIG_TEXT").unwrap_or(String::from("FALSE")) == "TRUE"
                 && self.kind == DialogKind::Dialog;
-        let mut ss = serializer.serialize_struct("Dialog", 3)?;
+        let s_len = if self.inline_commands.is_empty() {
+            3
+        } else {
+            4
+        };
+        let mut ss = serializer.serialize_struct("Dialog", s_len)?;
         ss.serialize_field("id", &self.id)?;
         ss.serialize_field("kind", &self.kind)?;
 
@@ -85,11 +190,15 @@ impl Serialize for Dialog {
         };
         ss.serialize_field("args", &args)?;
 
+        if !self.inline_commands.is_empty() {
+            ss.serialize_field("inline_commands", &self.inline_commands)?;
+        }
+
         ss.end()
     }
 }
 
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize)]
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Deserialize)]
 pub struct ChoiceData {
     // Choice id e.g. "SceneTitle_1_C1L2"
     pub id: String,
@@ -97,20 +206,13 @@ pub struct ChoiceData {
     pub label: String,
     // Choice inner dialogs
     pub dialogs: Vec<Dialog>,
-}
-
-impl Default for ChoiceData {
-    fn default() -> Self {
-        Self {
-            id: String::new(),
-            label: String::new(),
-            dialogs: Vec::new(),
-        }
-    }
+    // source location, not serialized
+    #[serde(skip)]
+    pub loc: SourceLoc,
 }
 
 impl ChoiceData {
-    #[allow(dead_code)]
+    #[cfg(test)]
     pub fn new() -> Self {
         ChoiceData::default()
     }
@@ -122,6 +224,11 @@ impl ChoiceData {
             ..Default::default()
         }
     }
+
+    pub fn with_loc(mut self, loc: SourceLoc) -> Self {
+        self.loc = loc;
+        self
+    }
 }
 
 impl Serialize for ChoiceData {
@@ -144,11 +251,17 @@ impl Serialize for ChoiceData {
     }
 }
 
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Scene {
     // SceneTitle
     pub title: String,
     pub dialogs: Vec<Dialog>,
+    // other header keys e.g. tags, bgm
+    #[serde(default)]
+    pub meta: SceneMeta,
+    // header location, not serialized
+    #[serde(skip)]
+    pub loc: SourceLoc,
 }
 
 impl Scene {
@@ -162,6 +275,15 @@ impl Scene {
         *self = Self::new();
     }
 
+    // visit all dialogs in this scene, including dialogs in choices
+    pub fn walk_dialogs<F: FnMut(&Dialog)>(&self, mut f: F) {
+        self.dialogs.iter().for_each(|d| d.walk(&mut f));
+    }
+
+    pub fn walk_dialogs_mut<F: FnMut(&mut Dialog)>(&mut self, mut f: F) {
+        self.dialogs.iter_mut().for_each(|d| d.walk_mut(&mut f));
+    }
+
     // pub fn from_title<T: AsRef<str>>(title: T) -> Self {
     //     Scene {
     //         title: String::from(title.as_ref()),
@@ -203,7 +325,7 @@ impl Scene {
         let li = v[2];
         let choices = &mut dialogs[di];
         if let DialogBody::Choice(ref mut cd) = choices.args[li] {
-            return Self::retrieve_inner_dialogs_as_mut(&mut cd.dialogs, inner_scene_idxs);
+            Self::retrieve_inner_dialogs_as_mut(&mut cd.dialogs, inner_scene_idxs)
         } else {
             panic!(
                 "retrieve_inner_dialogs_as_mut: dialog_body {} is not choice!",
@@ -226,10 +348,12 @@ impl Scene {
             .iter_mut()
             .filter(|d| d.kind == DialogKind::Choices)
             .nth(ci)
-            .expect(&format!(
-                "inner_choices_as_mut: Unable to find a choices dialog {}, {:#?}",
-                ci, v
-            ))
+            .unwrap_or_else(|| {
+                panic!(
+                    "inner_choices_as_mut: Unable to find a choices dialog {}, {:#?}",
+                    ci, v
+                )
+            })
     }
 
     pub fn truncate_scene_idxs(scene_idxs: &mut Vec<usize>) -> &mut Vec<usize> {
@@ -243,21 +367,27 @@ impl Scene {
     }
 }
 
-impl Default for Scene {
-    fn default() -> Self {
-        Scene {
-            title: String::new(),
-            dialogs: Vec::new(),
-        }
-    }
-}
-
 // scene_title: scene_dialogs
 pub type Scenes = HashMap<String, Vec<Dialog>>;
 
+// scene_title: scene_meta, only scenes with metadata
+pub type SceneMetas = HashMap<String, SceneMeta>;
+
+// copy scenes with dialog text rendered into an engine dialect
+pub fn render_scenes(scenes: &Scenes, dialect: Dialect) -> Scenes {
+    let mut scenes = scenes.clone();
+    for dialogs in scenes.values_mut() {
+        dialogs
+            .iter_mut()
+            .for_each(|d| d.walk_mut(&mut |d| d.render_markup(dialect)));
+    }
+    scenes
+}
+
 #[cfg(test)]
 mod tests {
     use super::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+    use crate::core::diagnostic::SourceLoc;
 
     fn gen_test_scene() -> Scene {
         let s = String::new();
@@ -280,6 +410,8 @@ mod tests {
                     ..Default::default()
                 }),
             ],
+            inline_commands: Vec::new(),
+            loc: SourceLoc::default(),
         };
 
         let choices = Dialog {
@@ -289,14 +421,31 @@ mod tests {
                 dialogs: vec![Dialog::new(), Dialog::new(), Dialog::new(), inner_choices],
                 ..Default::default()
             })],
+            inline_commands: Vec::new(),
+            loc: SourceLoc::default(),
         };
 
         Scene {
             title: String::from(""),
             dialogs: vec![Dialog::new(), Dialog::new(), choices],
+            ..Default::default()
         }
     }
 
+    #[test]
+    fn test_walk_dialogs() {
+        let mut scene = gen_test_scene();
+        let mut cnt = 0;
+        scene.walk_dialogs(|_| cnt += 1);
+        // 3 root dialogs + 4 dialogs in choice + 2 dialogs in inner choice
+        assert_eq!(9, cnt);
+
+        scene.walk_dialogs_mut(|d| d.loc.line = 5);
+        let mut lines = Vec::new();
+        scene.walk_dialogs(|d| lines.push(d.loc.line));
+        assert_eq!(vec![5; 9], lines);
+    }
+
     #[test]
     fn test_inner_dialogs_as_mut() {
         let mut inner_dialogs = vec![
@@ -368,6 +517,8 @@ mod tests {
                     ..Default::default()
                 }),
             ],
+            inline_commands: Vec::new(),
+            loc: SourceLoc::default(),
         };
 
         let mut choices = Dialog {
@@ -382,6 +533,8 @@ mod tests {
                 ],
                 ..Default::default()
             })],
+            inline_commands: Vec::new(),
+            loc: SourceLoc::default(),
         };
 
         let mut scene = gen_test_scene();
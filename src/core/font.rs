@@ -0,0 +1,218 @@
+use super::diagnostic::{Diagnostic, SourceLoc};
+use super::dialog::{DialogBody, DialogKind, Scene};
+use super::markup::Dialect;
+use super::talker::Talker;
+use crate::export::glyphs::GlyphSet;
+use crate::import::po::PoFile;
+use crate::utils;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+// Code points supported by a font file, read from its cmap table
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FontCoverage {
+    chars: BTreeSet<char>,
+}
+
+impl FontCoverage {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let data = utils::read_binary_file(path.as_ref()).map_err(|e| format!("{:?}", e))?;
+        Self::parse(&data)
+    }
+
+    // ttf, otf or the first font of ttc
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let face = ttf_parser::Face::parse(data, 0).map_err(|e| e.to_string())?;
+        let mut chars = BTreeSet::new();
+        if let Some(cmap) = face.tables().cmap {
+            for st in cmap.subtables {
+                if !st.is_unicode() {
+                    continue;
+                }
+                st.codepoints(|cp| {
+                    // code points mapped to .notdef are unsupported
+                    let has_glyph = st.glyph_index(cp).is_some_and(|g| g.0 != 0);
+                    if let Some(c) = char::from_u32(cp).filter(|_| has_glyph) {
+                        chars.insert(c);
+                    }
+                });
+            }
+        }
+        Ok(Self { chars })
+    }
+
+    #[cfg(test)]
+    pub fn from_chars<T: AsRef<str>>(s: T) -> Self {
+        Self {
+            chars: s.as_ref().chars().collect(),
+        }
+    }
+
+    pub fn missing(&self, gs: &GlyphSet) -> Vec<char> {
+        gs.chars().filter(|c| !self.chars.contains(c)).collect()
+    }
+
+    // dialog texts and choice labels
+    pub fn check_scenes(&self, scenes: &[Scene]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for sc in scenes {
+            sc.walk_dialogs(|d| match d.kind {
+                DialogKind::Dialog => {
+                    if let Some(rt) = d.args.first().and_then(|b| b.rich_text()) {
+                        let mut gs = GlyphSet::default();
+                        gs.add_rich_text(&rt);
+                        diagnostics.extend(self.check("dialog", &d.id, &gs, &d.loc));
+                    }
+                }
+                DialogKind::Choices => {
+                    for arg in &d.args {
+                        if let DialogBody::Choice(cd) = arg {
+                            let mut gs = GlyphSet::default();
+                            gs.add_str(&cd.label);
+                            diagnostics.extend(self.check("choice", &cd.id, &gs, &cd.loc));
+                        }
+                    }
+                }
+                DialogKind::Command => {}
+            });
+        }
+        diagnostics
+    }
+
+    pub fn check_talkers(&self, talkers: &[Talker]) -> Vec<Diagnostic> {
+        talkers
+            .iter()
+            .filter_map(|t| {
+                let mut gs = GlyphSet::default();
+                gs.add_str(t.name());
+                self.check("talker", t.id(), &gs, t.loc())
+            })
+            .collect()
+    }
+
+    // translated texts written in l10n markup dialect
+    pub fn check_po(&self, po: &PoFile, dialect: Dialect) -> Vec<Diagnostic> {
+        po.entries
+            .iter()
+            .filter_map(|e| {
+                let mut gs = GlyphSet::default();
+                gs.add_po_entry(e, dialect);
+                self.check("translation", &e.msgid, &gs, &SourceLoc::from_line(e.line))
+            })
+            .collect()
+    }
+
+    fn check(&self, kind: &str, id: &str, gs: &GlyphSet, loc: &SourceLoc) -> Option<Diagnostic> {
+        let missing = self.missing(gs);
+        if missing.is_empty() {
+            return None;
+        }
+
+        let chars: Vec<String> = missing
+            .iter()
+            .map(|c| format!("U+{:04X} '{}'", *c as u32, c))
+            .collect();
+        let diag = Diagnostic::warning(
+            0,
+            format!(
+                "{} `{}` has characters missing in font: {}",
+                kind,
+                id,
+                chars.join(", ")
+            ),
+        );
+        Some(diag.at(loc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FontCoverage;
+    use crate::core::diagnostic::{Diagnostic, SourceLoc};
+    use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+    use crate::core::markup::Dialect;
+    use crate::core::talker::Talker;
+    use crate::import::po::{PoEntry, PoFile};
+
+    #[test]
+    fn test_check_scenes() {
+        let mut sc = Scene::new();
+        sc.dialogs = vec![
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "Test_1_A",
+                vec![
+                    DialogBody::gen_text("あい\nう♥"),
+                    DialogBody::gen_text("TALKER_A"),
+                ],
+            )
+            .with_loc(SourceLoc::from_line(3)),
+            Dialog::from_dialog_data(
+                DialogKind::Choices,
+                "Test_2_C1",
+                vec![DialogBody::Choice(
+                    ChoiceData::from_texts("Test_2_C1L1", "はい").with_loc(SourceLoc::from_line(5)),
+                )],
+            ),
+        ];
+        let talkers = vec![
+            Talker::from_strs("TALKER_A", "あい").with_loc(SourceLoc::from_line(1)),
+            Talker::from_strs("TALKER_B", "Bob").with_loc(SourceLoc::from_line(2)),
+        ];
+
+        let fc = FontCoverage::from_chars("あいう");
+        assert_eq!(
+            vec![
+                Diagnostic::warning(
+                    3,
+                    "dialog `Test_1_A` has characters missing in font: U+2665 '♥'"
+                ),
+                Diagnostic::warning(
+                    5,
+                    "choice `Test_2_C1L1` has characters missing in font: U+306F 'は'"
+                ),
+            ],
+            fc.check_scenes(&[sc])
+        );
+        assert_eq!(
+            vec![Diagnostic::warning(
+                2,
+                "talker `TALKER_B` has characters missing in font: U+0042 'B', U+0062 'b', U+006F 'o'"
+            )],
+            fc.check_talkers(&talkers)
+        );
+    }
+
+    #[test]
+    fn test_check_po() {
+        let po = PoFile {
+            locale: String::from("en"),
+            entries: vec![
+                PoEntry {
+                    msgid: String::from("Test_1_A"),
+                    msgstr: String::from("[b]Hi[/b]{0}"),
+                    line: 4,
+                },
+                PoEntry {
+                    msgid: String::from("Test_2_B"),
+                    msgstr: String::from("Hé"),
+                    line: 8,
+                },
+            ],
+        };
+
+        let fc = FontCoverage::from_chars("Hi");
+        assert_eq!(
+            vec![Diagnostic::warning(
+                8,
+                "translation `Test_2_B` has characters missing in font: U+00E9 'é'"
+            )],
+            fc.check_po(&po, Dialect::BBCode)
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_font() {
+        assert!(FontCoverage::parse(b"not a font").is_err());
+    }
+}
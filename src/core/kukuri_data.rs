@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-use super::dialog::Scene;
+use super::diagnostic::SourceLoc;
+use super::dialog::{DialogBody, Scene};
 use super::talker::Talker;
 
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
 pub enum KukuriData {
     Scenes(Vec<Scene>),
     Talkers(Vec<Talker>),
+    // scenes and talkers defined in one script e.g. Ren'Py
+    Script(Vec<Scene>, Vec<Talker>),
 }
 
 impl KukuriData {
@@ -21,4 +24,47 @@ impl KukuriData {
     pub fn from_talkers(talkers: Vec<Talker>) -> Self {
         Self::Talkers(talkers)
     }
+
+    pub fn from_script(scenes: Vec<Scene>, talkers: Vec<Talker>) -> Self {
+        Self::Script(scenes, talkers)
+    }
+
+    // fill source path of scene, dialog, choice and talker locations,
+    // if not set by importer
+    pub fn set_source_path<T: AsRef<str>>(&mut self, path: T) {
+        self.walk_locs_mut(|loc| {
+            if loc.path.is_empty() {
+                loc.path = String::from(path.as_ref());
+            }
+        });
+    }
+
+    // clear all locations to compare parsed data with hand-made data
+    #[cfg(test)]
+    pub fn without_locs(mut self) -> Self {
+        self.walk_locs_mut(|loc| *loc = SourceLoc::default());
+        self
+    }
+
+    // visit scene, dialog, choice and talker locations
+    fn walk_locs_mut<F: FnMut(&mut SourceLoc)>(&mut self, mut f: F) {
+        let (scenes, talkers): (&mut [Scene], &mut [Talker]) = match self {
+            Self::Scenes(scenes) => (scenes, &mut []),
+            Self::Talkers(talkers) => (&mut [], talkers),
+            Self::Script(scenes, talkers) => (scenes, talkers),
+        };
+
+        for sc in scenes {
+            f(&mut sc.loc);
+            sc.walk_dialogs_mut(|d| {
+                f(&mut d.loc);
+                for arg in &mut d.args {
+                    if let DialogBody::Choice(cd) = arg {
+                        f(&mut cd.loc);
+                    }
+                }
+            });
+        }
+        talkers.iter_mut().for_each(|t| f(t.loc_mut()));
+    }
 }
@@ -0,0 +1,789 @@
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Text style of a markup tag
+//
+// <b> <i> <u> <s> <color=#ff0000> <size=24> <style=shout>
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize)]
+pub enum Style {
+    Bold,
+    Italic,
+    Underline,
+    Strike,
+    Color(String),
+    Size(String),
+    // project defined style name
+    Custom(String),
+}
+
+impl Style {
+    // parse tag body e.g. "b", "color=#ff0000"
+    pub fn parse(s: &str) -> Option<Self> {
+        let (name, value) = match s.split_once('=') {
+            Some((n, v)) => (n.trim(), Some(v.trim().trim_matches('"'))),
+            None => (s.trim(), None),
+        };
+
+        match (name, value) {
+            ("color", Some(v)) => Self::from_name_value(name, Some(&Self::color_value(v))),
+            _ => Self::from_name_value(name, value),
+        }
+    }
+
+    // a value is written into tags of every dialect as it is,
+    // e.g. "[shout]" of BBCode and "<style=\"shout\">" of TextMeshPro
+    fn is_valid_value(v: &str) -> bool {
+        !v.is_empty()
+            && !v
+                .chars()
+                .any(|c| c.is_whitespace() || "[]<>{}=\"\\/".contains(c))
+    }
+
+    // "#" starts a comment in scripts,
+    // so hex color is also written as "ff4040" or "\#ff4040"
+    fn color_value(v: &str) -> String {
+        let v = v.trim_start_matches('\\');
+        let is_hex = v.chars().all(|c| c.is_ascii_hexdigit()) && [3, 4, 6, 8].contains(&v.len());
+        if is_hex {
+            format!("#{}", v)
+        } else {
+            String::from(v)
+        }
+    }
+
+    // tag name used by closing tag
+    pub fn name(&self) -> &str {
+        match self {
+            Style::Bold => "b",
+            Style::Italic => "i",
+            Style::Underline => "u",
+            Style::Strike => "s",
+            Style::Color(_) => "color",
+            Style::Size(_) => "size",
+            Style::Custom(_) => "style",
+        }
+    }
+
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            Style::Color(v) | Style::Size(v) | Style::Custom(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // inverse of name() and value(), e.g. a "tag" of json output
+    pub fn from_name_value(name: &str, value: Option<&str>) -> Option<Self> {
+        match (name, value) {
+            ("b", None) => Some(Style::Bold),
+            ("i", None) => Some(Style::Italic),
+            ("u", None) => Some(Style::Underline),
+            ("s", None) => Some(Style::Strike),
+            (_, Some(v)) if !Self::is_valid_value(v) => None,
+            ("color", Some(v)) => Some(Style::Color(String::from(v))),
+            ("size", Some(v)) => Some(Style::Size(String::from(v))),
+            ("style", Some(v)) => Some(Style::Custom(String::from(v))),
+            _ => None,
+        }
+    }
+
+    fn open_tag(&self, dialect: Dialect) -> String {
+        match (dialect, self) {
+            (Dialect::Plain, _) => String::new(),
+            (Dialect::BBCode, Style::Size(v)) => format!("[font_size={}]", v),
+            // custom style is a RichTextEffect tag
+            (Dialect::BBCode, Style::Custom(v)) => format!("[{}]", v),
+            (Dialect::BBCode, _) => match self.value() {
+                Some(v) => format!("[{}={}]", self.name(), v),
+                None => format!("[{}]", self.name()),
+            },
+            (Dialect::TextMeshPro, Style::Custom(v)) => format!("<style=\"{}\">", v),
+            (Dialect::TextMeshPro, _) | (Dialect::Kukuri, _) => match self.value() {
+                Some(v) => format!("<{}={}>", self.name(), v),
+                None => format!("<{}>", self.name()),
+            },
+        }
+    }
+
+    fn close_tag(&self, dialect: Dialect) -> String {
+        match (dialect, self) {
+            (Dialect::Plain, _) => String::new(),
+            (Dialect::BBCode, Style::Size(_)) => String::from("[/font_size]"),
+            (Dialect::BBCode, Style::Custom(v)) => format!("[/{}]", v),
+            (Dialect::BBCode, _) => format!("[/{}]", self.name()),
+            (Dialect::TextMeshPro, _) | (Dialect::Kukuri, _) => format!("</{}>", self.name()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize)]
+pub enum Span {
+    Text(String),
+    Styled(Style, Vec<Span>),
+    // Ruby(base, reading) e.g. {漢字|かんじ}
+    // reading is not a part of plain text
+    Ruby(String, String),
+}
+
+impl Serialize for Span {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            // flatten serialize output
+            Self::Text(ref s) => serializer.serialize_str(s),
+            // {"tag": "color", "value": "#ff0000", "children": [...]}
+            Self::Styled(ref style, ref children) => {
+                let len = if style.value().is_some() { 3 } else { 2 };
+                let mut sm = serializer.serialize_map(Some(len))?;
+                sm.serialize_entry("tag", style.name())?;
+                if let Some(v) = style.value() {
+                    sm.serialize_entry("value", v)?;
+                }
+                sm.serialize_entry("children", children)?;
+                sm.end()
+            }
+            // {"tag": "ruby", "value": "かんじ", "children": ["漢字"]}
+            Self::Ruby(ref base, ref reading) => {
+                let mut sm = serializer.serialize_map(Some(3))?;
+                sm.serialize_entry("tag", "ruby")?;
+                sm.serialize_entry("value", reading)?;
+                sm.serialize_entry("children", &[base])?;
+                sm.end()
+            }
+        }
+    }
+}
+
+// Engine markup dialect of rendered text
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Dialect {
+    // no markup, for logs
+    Plain,
+    // Godot RichTextLabel
+    BBCode,
+    // Unity TextMeshPro
+    TextMeshPro,
+    // KukuriScript source syntax
+    Kukuri,
+}
+
+impl Dialect {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "plain" => Some(Dialect::Plain),
+            "bbcode" => Some(Dialect::BBCode),
+            "tmp" => Some(Dialect::TextMeshPro),
+            "kukuri" => Some(Dialect::Kukuri),
+            _ => None,
+        }
+    }
+
+    // visible chars of a rendered text, e.g. a translated text.
+    // tags are removed, and ruby readings are kept.
+    pub fn visible_text(&self, s: &str) -> String {
+        let mut out_s = String::new();
+        let mut rest = s;
+
+        while let Some(c) = rest.chars().next() {
+            let after = &rest[c.len_utf8()..];
+            if *self == Dialect::Kukuri && c == '\\' {
+                let escaped = after.chars().next();
+                escaped.iter().for_each(|&c| out_s.push(c));
+                rest = &after[escaped.map_or(0, |c| c.len_utf8())..];
+                continue;
+            }
+
+            let close = match (self, c) {
+                (Dialect::BBCode | Dialect::Kukuri, '[') => Some(']'),
+                (Dialect::TextMeshPro | Dialect::Kukuri, '<') => Some('>'),
+                (Dialect::Kukuri, '{') => Some('}'),
+                _ => None,
+            };
+            let (body, after_tag) = match close.and_then(|close| after.split_once(close)) {
+                Some(tag) => tag,
+                None => {
+                    out_s.push(c);
+                    rest = after;
+                    continue;
+                }
+            };
+            rest = after_tag;
+
+            match (self, body) {
+                (Dialect::BBCode, "lb") => out_s.push('['),
+                (Dialect::BBCode, "rb") => out_s.push(']'),
+                (Dialect::BBCode, b) if b.starts_with("ruby=") => out_s.push_str(&b[5..]),
+                (Dialect::TextMeshPro, "noparse") => {
+                    let (literal, r) = rest.split_once("</noparse>").unwrap_or((rest, ""));
+                    out_s.push_str(literal);
+                    rest = r;
+                }
+                // {base|reading}
+                (Dialect::Kukuri, b) if c == '{' => out_s.push_str(&b.replace('|', "")),
+                _ => {}
+            }
+        }
+
+        out_s
+    }
+
+    // escape a text char which would be read as markup
+    fn escape_char(&self, c: char, out_s: &mut String) {
+        match (self, c) {
+            (Dialect::BBCode, '[') => out_s.push_str("[lb]"),
+            (Dialect::TextMeshPro, '<') => out_s.push_str("<noparse><</noparse>"),
+            (Dialect::Kukuri, '\\' | '<' | '[' | '{' | '#') => {
+                out_s.push('\\');
+                out_s.push(c);
+            }
+            (Dialect::Kukuri, '\n') => out_s.push_str("\\n"),
+            (Dialect::Kukuri, '\t') => out_s.push_str("\\t"),
+            _ => out_s.push(c),
+        }
+    }
+}
+
+// Dialog text as a span tree
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RichText {
+    pub spans: Vec<Span>,
+}
+
+impl RichText {
+    pub fn from_plain<T: AsRef<str>>(s: T) -> Self {
+        let spans = if s.as_ref().is_empty() {
+            Vec::new()
+        } else {
+            vec![Span::Text(String::from(s.as_ref()))]
+        };
+        Self { spans }
+    }
+
+    // true if this text has no styled span
+    pub fn is_plain(&self) -> bool {
+        self.spans.iter().all(|s| matches!(s, Span::Text(_)))
+    }
+
+    pub fn plain_text(&self) -> String {
+        self.render(Dialect::Plain)
+    }
+
+    pub fn render(&self, dialect: Dialect) -> String {
+        self.render_with_inserts(dialect, &[])
+    }
+
+    // render text, and insert raw strings at char offsets of plain text.
+    // inserts must be sorted by offset.
+    pub fn render_with_inserts(&self, dialect: Dialect, inserts: &[(usize, String)]) -> String {
+        let mut r = Renderer {
+            dialect,
+            inserts,
+            insert_idx: 0,
+            offset: 0,
+            out_s: String::new(),
+        };
+        r.render_spans(&self.spans);
+        // inserts at the end of text
+        r.flush_inserts(usize::MAX);
+        r.out_s
+    }
+
+    // plain text chars: [(char, is_breakable_before)...]
+    // a ruby base is never broken
+    pub fn plain_units(&self) -> Vec<(char, bool)> {
+        fn collect(spans: &[Span], units: &mut Vec<(char, bool)>) {
+            for span in spans {
+                match span {
+                    Span::Text(s) => units.extend(s.chars().map(|c| (c, true))),
+                    Span::Styled(_, children) => collect(children, units),
+                    Span::Ruby(base, _) => {
+                        units.extend(base.chars().enumerate().map(|(i, c)| (c, i == 0)))
+                    }
+                }
+            }
+        }
+
+        let mut units = Vec::new();
+        collect(&self.spans, &mut units);
+        units
+    }
+
+    // insert or replace chars at char offsets of plain text
+    pub fn apply_edits(&mut self, edits: &BTreeMap<usize, TextEdit>) {
+        let mut edits = edits.clone();
+        let mut offset = 0;
+        let spans = std::mem::take(&mut self.spans);
+        self.spans = Self::apply_edits_to_spans(spans, &mut edits, &mut offset);
+    }
+
+    fn apply_edits_to_spans(
+        spans: Vec<Span>,
+        edits: &mut BTreeMap<usize, TextEdit>,
+        offset: &mut usize,
+    ) -> Vec<Span> {
+        fn push_text(spans: &mut Vec<Span>, s: String) {
+            match spans.last_mut() {
+                Some(Span::Text(t)) => t.push_str(&s),
+                _ => spans.push(Span::Text(s)),
+            }
+        }
+
+        let mut out_spans = Vec::new();
+        for span in spans {
+            match span {
+                Span::Text(s) => {
+                    let mut t = String::new();
+                    for c in s.chars() {
+                        match edits.remove(offset) {
+                            Some(TextEdit::Insert(e)) => {
+                                t.push(e);
+                                t.push(c);
+                            }
+                            Some(TextEdit::Replace(e)) => t.push(e),
+                            None => t.push(c),
+                        }
+                        *offset += 1;
+                    }
+                    push_text(&mut out_spans, t);
+                }
+                span => {
+                    // insert before a styled span or a ruby, not into it
+                    if let Some(&TextEdit::Insert(e)) = edits.get(offset) {
+                        edits.remove(offset);
+                        push_text(&mut out_spans, e.to_string());
+                    }
+                    match span {
+                        Span::Styled(style, children) => {
+                            let children = Self::apply_edits_to_spans(children, edits, offset);
+                            out_spans.push(Span::Styled(style, children));
+                        }
+                        Span::Ruby(base, reading) => {
+                            *offset += base.chars().count();
+                            out_spans.push(Span::Ruby(base, reading));
+                        }
+                        Span::Text(_) => unreachable!(),
+                    }
+                }
+            }
+        }
+        out_spans
+    }
+}
+
+// Edit of a plain text char
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum TextEdit {
+    // insert a char before
+    Insert(char),
+    // replace the char
+    Replace(char),
+}
+
+struct Renderer<'a> {
+    dialect: Dialect,
+    inserts: &'a [(usize, String)],
+    insert_idx: usize,
+    // char count of plain text
+    offset: usize,
+    out_s: String,
+}
+
+impl Renderer<'_> {
+    fn render_spans(&mut self, spans: &[Span]) {
+        for span in spans {
+            match span {
+                Span::Text(s) => {
+                    for c in s.chars() {
+                        self.flush_inserts(self.offset);
+                        self.dialect.escape_char(c, &mut self.out_s);
+                        self.offset += 1;
+                    }
+                }
+                Span::Styled(style, children) => {
+                    self.flush_inserts(self.offset);
+                    self.out_s.push_str(&style.open_tag(self.dialect));
+                    self.render_spans(children);
+                    self.out_s.push_str(&style.close_tag(self.dialect));
+                }
+                Span::Ruby(base, reading) => {
+                    // never break a ruby base
+                    self.flush_inserts(self.offset);
+                    self.render_ruby(base, reading);
+                    self.offset += base.chars().count();
+                }
+            }
+        }
+    }
+
+    fn render_ruby(&mut self, base: &str, reading: &str) {
+        let mut escaped_base = String::new();
+        base.chars()
+            .for_each(|c| self.dialect.escape_char(c, &mut escaped_base));
+
+        let s = match self.dialect {
+            // TextMeshPro has no ruby, use json output for ruby data
+            Dialect::Plain | Dialect::TextMeshPro => escaped_base,
+            // RichTextEffect tag
+            Dialect::BBCode => format!("[ruby={}]{}[/ruby]", reading, escaped_base),
+            Dialect::Kukuri => format!("{{{}|{}}}", escaped_base, reading),
+        };
+        self.out_s.push_str(&s);
+    }
+
+    fn flush_inserts(&mut self, offset: usize) {
+        while let Some((o, s)) = self.inserts.get(self.insert_idx) {
+            if *o > offset {
+                break;
+            }
+            self.out_s.push_str(s);
+            self.insert_idx += 1;
+        }
+    }
+}
+
+// Builds a span tree while parsing
+#[derive(Clone, Debug, Default)]
+pub struct RichTextBuilder {
+    root: Vec<Span>,
+    // opened styles and their children
+    stack: Vec<(Style, Vec<Span>)>,
+    // char count of plain text
+    char_count: usize,
+}
+
+impl RichTextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn char_count(&self) -> usize {
+        self.char_count
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        self.char_count += s.chars().count();
+
+        let spans = self.current_spans();
+        match spans.last_mut() {
+            Some(Span::Text(t)) => t.push_str(s),
+            _ => spans.push(Span::Text(String::from(s))),
+        }
+    }
+
+    pub fn push_ruby(&mut self, base: &str, reading: &str) {
+        self.char_count += base.chars().count();
+        self.current_spans()
+            .push(Span::Ruby(String::from(base), String::from(reading)));
+    }
+
+    pub fn open(&mut self, style: Style) {
+        self.stack.push((style, Vec::new()));
+    }
+
+    // close a style by tag name, empty name closes the last opened style.
+    // Err(message) if tags are unbalanced.
+    pub fn close(&mut self, name: &str) -> Result<(), String> {
+        let pos = if name.is_empty() {
+            self.stack.len().checked_sub(1)
+        } else {
+            self.stack.iter().rposition(|(s, _)| s.name() == name)
+        };
+
+        let pos = match pos {
+            Some(i) => i,
+            None => {
+                let tag = format!("</{}>", name);
+                return Err(format!("closing tag \"{}\" has no opening tag", tag));
+            }
+        };
+
+        let is_mismatched = pos + 1 != self.stack.len();
+        let inner_name = self.stack.last().map(|(s, _)| String::from(s.name()));
+        while self.stack.len() > pos {
+            self.pop();
+        }
+
+        match inner_name {
+            Some(inner) if is_mismatched => Err(format!(
+                "tag \"<{}>\" is closed by \"</{}>\", close inner tags first",
+                inner, name
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // return: (text, names of unclosed tags)
+    pub fn finish(mut self) -> (RichText, Vec<String>) {
+        let unclosed = self
+            .stack
+            .iter()
+            .map(|(s, _)| String::from(s.name()))
+            .collect();
+        while !self.stack.is_empty() {
+            self.pop();
+        }
+        (RichText { spans: self.root }, unclosed)
+    }
+
+    fn pop(&mut self) {
+        if let Some((style, children)) = self.stack.pop() {
+            self.current_spans().push(Span::Styled(style, children));
+        }
+    }
+
+    fn current_spans(&mut self) -> &mut Vec<Span> {
+        match self.stack.last_mut() {
+            Some((_, children)) => children,
+            None => &mut self.root,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dialect, RichText, RichTextBuilder, Span, Style, TextEdit};
+    use std::collections::BTreeMap;
+
+    // "Hi <b>bold <color=#f00>red</color></b>[x]"
+    fn gen_rich_text() -> RichText {
+        RichText {
+            spans: vec![
+                Span::Text(String::from("Hi ")),
+                Span::Styled(
+                    Style::Bold,
+                    vec![
+                        Span::Text(String::from("bold ")),
+                        Span::Styled(
+                            Style::Color(String::from("#f00")),
+                            vec![Span::Text(String::from("red"))],
+                        ),
+                    ],
+                ),
+                Span::Text(String::from("[x]")),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_style_parse() {
+        let tests = [
+            ("b", Some(Style::Bold)),
+            ("color=#ff0000", Some(Style::Color(String::from("#ff0000")))),
+            ("color=ff0000", Some(Style::Color(String::from("#ff0000")))),
+            ("color=\\#f00", Some(Style::Color(String::from("#f00")))),
+            ("color=red", Some(Style::Color(String::from("red")))),
+            ("size = 24", Some(Style::Size(String::from("24")))),
+            (
+                "style=\"shout\"",
+                Some(Style::Custom(String::from("shout"))),
+            ),
+            ("b=1", None),
+            ("color", None),
+            ("blink", None),
+            ("style=", None),
+            ("style=a]b", None),
+            ("style=a=b", None),
+            ("style=\"a b\"", None),
+            ("size=1/2", None),
+        ];
+
+        for (src, expected) in &tests {
+            assert_eq!(*expected, Style::parse(src));
+            if let Some(style) = expected {
+                let parts = Style::from_name_value(style.name(), style.value());
+                assert_eq!(*expected, parts);
+            }
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let rt = gen_rich_text();
+        let tests = [
+            (Dialect::Plain, "Hi bold red[x]"),
+            (
+                Dialect::BBCode,
+                "Hi [b]bold [color=#f00]red[/color][/b][lb]x]",
+            ),
+            (
+                Dialect::TextMeshPro,
+                "Hi <b>bold <color=#f00>red</color></b>[x]",
+            ),
+            (
+                Dialect::Kukuri,
+                "Hi <b>bold <color=#f00>red</color></b>\\[x]",
+            ),
+        ];
+
+        for (dialect, expected) in &tests {
+            assert_eq!(*expected, rt.render(*dialect));
+        }
+
+        let custom = RichText {
+            spans: vec![Span::Styled(
+                Style::Custom(String::from("shout")),
+                vec![Span::Text(String::from("<!>"))],
+            )],
+        };
+        assert_eq!("[shout]<!>[/shout]", custom.render(Dialect::BBCode));
+        assert_eq!(
+            "<style=\"shout\"><noparse><</noparse>!></style>",
+            custom.render(Dialect::TextMeshPro)
+        );
+    }
+
+    #[test]
+    fn test_render_ruby() {
+        let rt = RichText {
+            spans: vec![
+                Span::Ruby(String::from("漢字"), String::from("かんじ")),
+                Span::Text(String::from("です")),
+            ],
+        };
+        let tests = [
+            (Dialect::Plain, "漢字です"),
+            (Dialect::BBCode, "[ruby=かんじ]漢字[/ruby]です"),
+            (Dialect::TextMeshPro, "漢字です"),
+            (Dialect::Kukuri, "{漢字|かんじ}です"),
+        ];
+
+        for (dialect, expected) in &tests {
+            assert_eq!(*expected, rt.render(*dialect));
+        }
+
+        // reading is not counted, and ruby base is never broken
+        let inserts = [(1, String::from("{0}")), (2, String::from("{1}"))];
+        assert_eq!(
+            "[ruby=かんじ]漢字[/ruby]{0}{1}です",
+            rt.render_with_inserts(Dialect::BBCode, &inserts)
+        );
+        assert_eq!(
+            r#"[{"tag":"ruby","value":"かんじ","children":["漢字"]},"です"]"#,
+            serde_json::to_string(&rt).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_plain_units() {
+        let rt = RichText {
+            spans: vec![
+                Span::Styled(Style::Bold, vec![Span::Text(String::from("a"))]),
+                Span::Ruby(String::from("漢字"), String::from("かんじ")),
+            ],
+        };
+        assert_eq!(
+            vec![('a', true), ('漢', true), ('字', false)],
+            rt.plain_units()
+        );
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let mut rt = gen_rich_text();
+        let edits: BTreeMap<usize, TextEdit> = vec![
+            (3, TextEdit::Insert('\n')),
+            (5, TextEdit::Replace('_')),
+            (8, TextEdit::Insert('\u{c}')),
+        ]
+        .into_iter()
+        .collect();
+        rt.apply_edits(&edits);
+
+        assert_eq!(
+            "Hi \n<b>bo_d \u{c}<color=#f00>red</color></b>[x]",
+            rt.render(Dialect::TextMeshPro)
+        );
+    }
+
+    #[test]
+    fn test_visible_text() {
+        let rt = RichText {
+            spans: vec![
+                Span::Styled(Style::Bold, vec![Span::Text(String::from("<[x]"))]),
+                Span::Ruby(String::from("漢字"), String::from("かんじ")),
+            ],
+        };
+
+        let tests = [
+            (Dialect::BBCode, "<[x]かんじ漢字"),
+            // TextMeshPro has no ruby
+            (Dialect::TextMeshPro, "<[x]漢字"),
+            (Dialect::Kukuri, "<[x]漢字かんじ"),
+        ];
+
+        for (dialect, expected) in &tests {
+            let rendered = rt.render(*dialect);
+            assert_eq!(*expected, dialect.visible_text(&rendered));
+        }
+        assert_eq!("a<b", Dialect::Plain.visible_text("a<b"));
+    }
+
+    #[test]
+    fn test_render_with_inserts() {
+        let inserts = [
+            (0, String::from("{0}")),
+            (3, String::from("{1}")),
+            (14, String::from("{2}")),
+        ];
+        assert_eq!(
+            "{0}Hi {1}[b]bold [color=#f00]red[/color][/b][lb]x]{2}",
+            gen_rich_text().render_with_inserts(Dialect::BBCode, &inserts)
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut b = RichTextBuilder::new();
+        b.push_str("Hi ");
+        b.open(Style::Bold);
+        b.push_str("bold ");
+        b.open(Style::Color(String::from("#f00")));
+        b.push_str("red");
+        assert_eq!(Ok(()), b.close(""));
+        assert_eq!(Ok(()), b.close("b"));
+        b.push_str("[x]");
+        assert_eq!(14, b.char_count());
+        assert_eq!((gen_rich_text(), Vec::new()), b.finish());
+    }
+
+    #[test]
+    fn test_builder_unbalanced() {
+        let mut b = RichTextBuilder::new();
+        assert!(b.close("b").is_err());
+        b.open(Style::Bold);
+        b.open(Style::Italic);
+        b.push_str("a");
+        assert!(b.close("b").is_err());
+        b.open(Style::Underline);
+        let (rt, unclosed) = b.finish();
+
+        assert_eq!(vec![String::from("u")], unclosed);
+        assert_eq!(
+            vec![
+                Span::Styled(
+                    Style::Bold,
+                    vec![Span::Styled(
+                        Style::Italic,
+                        vec![Span::Text(String::from("a"))]
+                    )]
+                ),
+                Span::Styled(Style::Underline, Vec::new()),
+            ],
+            rt.spans
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            r##"["Hi ",{"tag":"b","children":["bold ",{"tag":"color","value":"#f00","children":["red"]}]},"[x]"]"##,
+            serde_json::to_string(&gen_rich_text()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use super::command::Float;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// scene header key: value, except title
+pub type SceneMeta = BTreeMap<String, MetaValue>;
+
+// Scene header value, converted from toml.
+// toml datetime, inf and nan are kept as string.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(untagged, from = "toml::Value")]
+pub enum MetaValue {
+    Str(String),
+    Int(i64),
+    Float(Float),
+    Bool(bool),
+    Array(Vec<MetaValue>),
+    Table(BTreeMap<String, MetaValue>),
+}
+
+impl MetaValue {
+    pub fn type_name(&self) -> &str {
+        match self {
+            MetaValue::Str(_) => "string",
+            MetaValue::Int(_) => "int",
+            MetaValue::Float(_) => "float",
+            MetaValue::Bool(_) => "bool",
+            MetaValue::Array(_) => "array",
+            MetaValue::Table(_) => "table",
+        }
+    }
+}
+
+impl From<toml::Value> for MetaValue {
+    fn from(v: toml::Value) -> Self {
+        match v {
+            toml::Value::String(s) => MetaValue::Str(s),
+            toml::Value::Integer(i) => MetaValue::Int(i),
+            toml::Value::Float(f) if f.is_finite() => MetaValue::Float(Float(f)),
+            // "inf" and "nan" can not be exported to json
+            v @ toml::Value::Float(_) => MetaValue::Str(v.to_string()),
+            toml::Value::Boolean(b) => MetaValue::Bool(b),
+            toml::Value::Datetime(dt) => MetaValue::Str(dt.to_string()),
+            toml::Value::Array(a) => MetaValue::Array(a.into_iter().map(MetaValue::from).collect()),
+            toml::Value::Table(t) => MetaValue::Table(
+                t.into_iter()
+                    .map(|(k, v)| (k, MetaValue::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetaValue, SceneMeta};
+    use crate::core::command::Float;
+
+    #[test]
+    fn test_from_toml() {
+        let meta: SceneMeta = toml::from_str(
+            r#"
+chapter = 2
+tags = ["intro", "night"]
+released = 2020-11-01
+far = inf
+[custom]
+volume = 0.5
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(MetaValue::Int(2), meta["chapter"]);
+        assert_eq!(
+            MetaValue::Array(vec![
+                MetaValue::Str(String::from("intro")),
+                MetaValue::Str(String::from("night")),
+            ]),
+            meta["tags"]
+        );
+        assert_eq!(MetaValue::Str(String::from("2020-11-01")), meta["released"]);
+        assert_eq!(MetaValue::Str(String::from("inf")), meta["far"]);
+        assert_eq!(
+            r#"{"chapter":2,"custom":{"volume":0.5},"far":"inf","released":"2020-11-01","tags":["intro","night"]}"#,
+            serde_json::to_string(&meta).unwrap()
+        );
+
+        match &meta["custom"] {
+            MetaValue::Table(t) => assert_eq!(MetaValue::Float(Float(0.5)), t["volume"]),
+            v => panic!("unexpected value {:?}", v),
+        }
+    }
+}
@@ -1,21 +1,58 @@
+pub mod command;
+pub mod diagnostic;
 pub mod dialog;
+pub mod font;
 pub mod kukuri_data;
+pub mod markup;
+pub mod meta;
+pub mod namespace;
 pub mod talker;
-
-use crate::config::Config;
-use crate::export::{gd::GDScript, json::Json, po::Po, ExportType, L10nExportType};
-use crate::import::{kukuri_script::KukuriScript, kukuri_talkers::KukuriTalkers, ImportType};
+pub mod wrap;
+
+use crate::config::{command_schema::CommandSchema, Config};
+use crate::export::{
+    bindings::Bindings, gd::GDScript, glyphs::GlyphSet, json::Json, kkr::Kkr, po::Po,
+    tmp::TextMeshPro, txt::Txt, BindingExportType, ExportType, L10nExportType,
+};
+use crate::import::{
+    fountain::Fountain,
+    ink::Ink,
+    kukuri_json::KukuriJson,
+    kukuri_script::KukuriScript,
+    kukuri_talkers::KukuriTalkers,
+    kukuri_talkers_toml::KukuriTalkersToml,
+    macros::{self, Macros},
+    markdown::Markdown,
+    po::PoFile,
+    renpy::RenPy,
+    spreadsheet::Spreadsheet,
+    twee::Twee,
+    yarn::Yarn,
+    ImportType,
+};
 use crate::utils;
-use dialog::{Scene, Scenes};
+use diagnostic::Diagnostic;
+use dialog::{Scene, SceneMetas, Scenes};
+use font::FontCoverage;
 use kukuri_data::KukuriData;
-use std::collections::HashMap;
+use markup::Dialect;
+use namespace::JumpResolver;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 use talker::Talker;
+use wrap::Wrapper;
 
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Kukuri {
     pub conf: Config,
     pub inputs: Vec<PathBuf>,
+    // KukuriScript macros shared by inputs
+    pub macros: Macros,
+    // loaded once with config, None if not configured or unable to load
+    pub command_schema: Option<CommandSchema>,
+    // set by json inputs of use_l10n_output, their empty texts must not overwrite .po
+    has_excluded_texts: Cell<bool>,
 }
 
 impl Default for Kukuri {
@@ -23,21 +60,27 @@ impl Default for Kukuri {
         Kukuri {
             conf: Config::new(),
             inputs: Vec::new(),
+            macros: Macros::new(),
+            command_schema: None,
+            has_excluded_texts: Cell::new(false),
         }
     }
 }
 
 impl Kukuri {
-    #[allow(dead_code)]
-    pub fn new() -> Self {
-        Kukuri {
-            ..Default::default()
-        }
-    }
-
     pub fn from_config(conf: Config) -> Self {
+        let macros = conf
+            .macros
+            .as_ref()
+            .map_or_else(Macros::new, macros::from_file);
+        let command_schema = conf
+            .command_schema
+            .as_ref()
+            .and_then(CommandSchema::from_file);
         Kukuri {
             conf,
+            macros,
+            command_schema,
             ..Default::default()
         }
     }
@@ -69,13 +112,26 @@ impl Kukuri {
             .for_each(|p| match self.import(p) {
                 KukuriData::Scenes(mut sc) => scenes.append(&mut sc),
                 KukuriData::Talkers(mut tk) => talkers.append(&mut tk),
+                KukuriData::Script(mut sc, mut tk) => {
+                    scenes.append(&mut sc);
+                    talkers.append(&mut tk);
+                }
             });
 
+        let resolver = JumpResolver::new(scenes.iter(), &self.conf.jump_commands);
+        Diagnostic::print_all("", &resolver.resolve_scenes(&mut scenes));
+        self.validate(&scenes);
+        self.bindings_export(&scenes);
+
+        // .po keeps unwrapped texts, translations are wrapped by their own length
         if self.conf.use_l10n_output {
             self.l10n_export(&scenes, &talkers);
         }
+        self.wrap(&mut scenes);
         let shm = Kukuri::scenes_to_hashmap(&scenes);
-        self.export(&shm, "output");
+        let metas = Kukuri::scene_metas_to_hashmap(&scenes);
+        self.export(&shm, &metas, "output");
+        self.talkers_export(&talkers);
     }
 
     fn run_with_separate_output(&self) {
@@ -83,7 +139,7 @@ impl Kukuri {
             format!("{}{}", "output", i)
         }
 
-        let mut exported_scenes = Vec::new();
+        let mut file_scenes = Vec::new();
         let mut talkers = Vec::new();
 
         for (i, p) in self.inputs.clone().iter().enumerate() {
@@ -91,41 +147,158 @@ impl Kukuri {
             match self.import(p) {
                 KukuriData::Scenes(mut sc) => scenes.append(&mut sc),
                 KukuriData::Talkers(mut tk) => talkers.append(&mut tk),
+                KukuriData::Script(mut sc, mut tk) => {
+                    scenes.append(&mut sc);
+                    talkers.append(&mut tk);
+                }
             };
 
+            // namespace keeps files of the same name apart
             let file_stem = match p.file_stem() {
+                _ if self.conf.namespace_from_path => self.path_namespace(p),
                 Some(s) => s
                     .to_os_string()
                     .into_string()
                     .unwrap_or(fallback_filestem(i)),
                 None => fallback_filestem(i),
             };
-            let shm = Kukuri::scenes_to_hashmap(&scenes);
+            file_scenes.push((file_stem, scenes));
+        }
+
+        // jumps are resolved with scenes of all files
+        let all_scenes = file_scenes.iter().flat_map(|(_, scenes)| scenes);
+        let resolver = JumpResolver::new(all_scenes, &self.conf.jump_commands);
 
-            self.export(&shm, file_stem);
+        let mut exported_scenes = Vec::new();
+        for (file_stem, mut scenes) in file_scenes {
+            Diagnostic::print_all("", &resolver.resolve_scenes(&mut scenes));
+
+            // .po keeps unwrapped texts, so only exported scenes are wrapped
+            let mut wrapped = scenes.clone();
+            self.wrap(&mut wrapped);
+            let shm = Kukuri::scenes_to_hashmap(&wrapped);
+            let metas = Kukuri::scene_metas_to_hashmap(&wrapped);
+
+            self.export(&shm, &metas, file_stem);
             exported_scenes.append(&mut scenes);
         }
 
+        self.validate(&exported_scenes);
+        self.bindings_export(&exported_scenes);
+
         // l10n_export in a lump
         if self.conf.use_l10n_output {
             self.l10n_export(&exported_scenes, &talkers);
         }
+        self.talkers_export(&talkers);
+    }
+
+    // insert line breaks for message windows
+    fn wrap(&self, scenes: &mut [Scene]) {
+        let diagnostics = Wrapper::new(&self.conf.textbox).wrap_scenes(scenes);
+        Diagnostic::print_all("", &diagnostics);
+    }
+
+    // validate imported scenes with project settings
+    fn validate(&self, scenes: &[Scene]) {
+        if let Some(schema) = &self.command_schema {
+            Diagnostic::print_all("", &schema.validate(scenes));
+        }
+    }
+
+    // e.g. "chapter1/intro.kkr" => "chapter1.intro"
+    fn path_namespace(&self, path: &Path) -> String {
+        namespace::from_path(path, &self.conf.script_dir)
     }
 
-    fn parse(&self, content: &str, ext: &str) -> KukuriData {
+    fn parse(&self, content: &str, ext: &str, source: &str, namespace: &str) -> KukuriData {
         let import_type = ImportType::from_extension(ext, &self.conf.default_script_type);
 
         match import_type {
             ImportType::Yarn => {
-                println!("Sorry, YarnSpinner script is not supported currently.");
-                KukuriData::new()
+                let (data, diagnostics) = Yarn::parse_with_namespace(content, namespace);
+                Diagnostic::print_all(source, &diagnostics);
+                data
             }
             ImportType::Ink => {
-                println!("Sorry, Ink script is not supported currently.");
-                KukuriData::new()
+                let (data, diagnostics) = Ink::parse_with_namespace(content, namespace);
+                Diagnostic::print_all(source, &diagnostics);
+                data
+            }
+            ImportType::RenPy => {
+                let (data, diagnostics) = RenPy::parse_with_namespace(content, namespace);
+                Diagnostic::print_all(source, &diagnostics);
+                data
+            }
+            ImportType::Fountain => {
+                let (data, diagnostics) = Fountain::parse_with_namespace(
+                    content,
+                    namespace,
+                    self.conf.fountain_narrator.as_deref(),
+                );
+                Diagnostic::print_all(source, &diagnostics);
+                data
+            }
+            ImportType::Twee => {
+                let (data, diagnostics) = Twee::parse_with_namespace(content, namespace);
+                Diagnostic::print_all(source, &diagnostics);
+                data
+            }
+            ImportType::Markdown => {
+                let (data, diagnostics) = Markdown::parse_with_namespace(content, namespace);
+                Diagnostic::print_all(source, &diagnostics);
+                data
+            }
+            ImportType::Csv | ImportType::Tsv => {
+                let delimiter = if let ImportType::Tsv = import_type {
+                    '\t'
+                } else {
+                    ','
+                };
+                let (data, diagnostics) = Spreadsheet::parse_with_namespace(
+                    content,
+                    namespace,
+                    delimiter,
+                    &self.conf.spreadsheet,
+                );
+                Diagnostic::print_all(source, &diagnostics);
+                data
+            }
+            // "intro.meta.json" is read with "intro.json"
+            ImportType::KukuriJson if source.ends_with(".meta.json") => KukuriData::new(),
+            ImportType::KukuriJson => {
+                // scene metadata of json output, e.g. "intro.meta.json"
+                let meta_path = Path::new(source).with_extension("meta.json");
+                let meta = utils::read_file(meta_path).ok();
+                let (data, is_text_excluded, diagnostics) =
+                    KukuriJson::parse_with_meta(content, meta.as_deref());
+                Diagnostic::print_all(source, &diagnostics);
+                if is_text_excluded {
+                    self.has_excluded_texts.set(true);
+                }
+                data
+            }
+            ImportType::KukuriScript => {
+                let (data, diagnostics) = KukuriScript::parse_source(
+                    content,
+                    Some(Path::new(source)),
+                    namespace,
+                    &self.macros,
+                    &|p| utils::read_file(p),
+                );
+                Diagnostic::print_all(source, &diagnostics);
+                data
+            }
+            ImportType::KukuriTalkers => {
+                let (data, diagnostics) = KukuriTalkers::parse_with_diagnostics(content);
+                Diagnostic::print_all(source, &diagnostics);
+                data
+            }
+            ImportType::KukuriTalkersToml => {
+                let (data, diagnostics) = KukuriTalkersToml::parse_with_diagnostics(content);
+                Diagnostic::print_all(source, &diagnostics);
+                data
             }
-            ImportType::KukuriScript => KukuriScript::parse(content),
-            ImportType::KukuriTalkers => KukuriTalkers::parse(content),
         }
     }
 
@@ -134,9 +307,27 @@ impl Kukuri {
             Some(s) => s.to_str().unwrap_or(""),
             None => "",
         };
+        // double extension e.g. "cast.talkers.toml",
+        // so config, macros and commands toml are never read as talkers
+        let file_name = path.as_ref().to_str().unwrap_or("");
+        let ext = if file_name.ends_with(".talkers.toml") {
+            "talkers.toml"
+        } else {
+            ext
+        };
 
+        let source = path.as_ref().display().to_string();
         match utils::read_file(path.as_ref()) {
-            Ok(s) => self.parse(&s, ext),
+            Ok(s) => {
+                let namespace = if self.conf.namespace_from_path {
+                    self.path_namespace(path.as_ref())
+                } else {
+                    String::new()
+                };
+                let mut data = self.parse(&s, ext, &source, &namespace);
+                data.set_source_path(&source);
+                data
+            }
             Err(e) => {
                 eprintln!("Failed to load {}: {:?}", path.as_ref().display(), e);
                 KukuriData::new()
@@ -144,7 +335,7 @@ impl Kukuri {
         }
     }
 
-    fn export<T: AsRef<str>>(&self, scenes: &Scenes, file_stem: T) {
+    fn export<T: AsRef<str>>(&self, scenes: &Scenes, metas: &SceneMetas, file_stem: T) {
         if scenes.is_empty() {
             return;
         };
@@ -176,10 +367,12 @@ impl Kukuri {
         let is_minify = self.conf.minified_output;
 
         // export type
-        for et in exports {
+        for &et in &exports {
             let s = match et {
-                ExportType::Json => Json::export_string(&scenes, is_minify),
-                ExportType::GDScript => GDScript::export_string(&scenes, is_minify),
+                ExportType::Json => Json::export_string(scenes, is_minify),
+                ExportType::GDScript => GDScript::export_string(scenes, metas, is_minify),
+                ExportType::TextMeshPro => TextMeshPro::export_string(scenes, is_minify),
+                ExportType::Txt => Txt::export_string(scenes),
             };
 
             // TODO: multiple output feature
@@ -188,6 +381,62 @@ impl Kukuri {
 
             utils::write_file(path, &s).expect("Unable to write file.");
         }
+
+        // json outputs have scene metadata in a separate file
+        let has_json = exports
+            .iter()
+            .any(|et| matches!(et, ExportType::Json | ExportType::TextMeshPro));
+        if has_json && !metas.is_empty() {
+            let mut path = output_dir.clone();
+            path.push(format!("{}.meta.json", file_stem.as_ref()));
+            let s = Json::export_meta_string(metas, is_minify);
+            utils::write_file(path, &s).expect("Unable to write file.");
+        }
+    }
+
+    // talker table of all inputs, beside scene outputs
+    fn talkers_export(&self, talkers: &[Talker]) {
+        if talkers.is_empty() {
+            return;
+        }
+        Diagnostic::print_all("", &talker::check_duplicates(talkers));
+
+        let output_dir = &self.conf.output_dir;
+        if !output_dir.exists() {
+            if let Err(e) = utils::mkdir_recursive(output_dir) {
+                eprintln!("Kukuri::talkers_export() make export dir error: {:?}", e);
+                println!("talkers export skipped");
+                return;
+            }
+        }
+
+        let is_minify = self.conf.minified_output;
+        // talker names are in .po
+        let is_name_excluded = self.conf.use_l10n_output;
+        let mut files: Vec<(&str, String)> = Vec::new();
+        for et in self.conf.outputs.iter().map(|s| ExportType::parse(s)) {
+            let file = match et {
+                ExportType::GDScript => (
+                    "kukuri_talkers.gd",
+                    GDScript::export_talkers_string(talkers, is_minify, is_name_excluded),
+                ),
+                // talker names have no markup
+                ExportType::Json | ExportType::TextMeshPro => (
+                    "kukuri_talkers.json",
+                    Json::export_talkers_string(talkers, is_minify, is_name_excluded),
+                ),
+                ExportType::Txt => continue,
+            };
+            if !files.iter().any(|(name, _)| *name == file.0) {
+                files.push(file);
+            }
+        }
+
+        for (name, s) in files {
+            let mut path = output_dir.clone();
+            path.push(name);
+            utils::write_file(path, &s).expect("Unable to write file.");
+        }
     }
 
     fn l10n_export<T: AsRef<Vec<Scene>>, T2: AsRef<Vec<Talker>>>(&self, scenes: T, talkers: T2) {
@@ -196,6 +445,10 @@ impl Kukuri {
         if (scenes.is_empty() && talkers.is_empty()) || !self.conf.use_l10n_output {
             return;
         };
+        if self.has_excluded_texts.get() {
+            eprintln!("l10n export skipped, texts of json inputs are excluded by use_l10n_output");
+            return;
+        }
 
         let output_dir = &self.conf.l10n_output_dir;
         if !output_dir.exists() {
@@ -215,15 +468,14 @@ impl Kukuri {
 
         exports.dedup();
 
+        let dialect = self.l10n_dialect();
+
         // export type
         for et in exports {
-            let locale = (self.conf.orig_locale.as_str())
-                .splitn(2, "_")
-                .nth(0)
-                .unwrap_or("en");
+            let locale = self.orig_lang();
 
             let s = match et {
-                L10nExportType::Po => Po::export_string(scenes, talkers, locale),
+                L10nExportType::Po => Po::export_string(scenes, talkers, locale, dialect),
             };
 
             let mut path = output_dir.clone();
@@ -234,6 +486,260 @@ impl Kukuri {
         }
     }
 
+    // export command bindings inferred from all scenes
+    fn bindings_export(&self, scenes: &[Scene]) {
+        let mut exports: Vec<BindingExportType> = self
+            .conf
+            .binding_outputs
+            .iter()
+            .filter_map(|s| {
+                let et = BindingExportType::parse(s);
+                if et.is_none() {
+                    eprintln!("Unknown binding output type: {}", s);
+                }
+                et
+            })
+            .collect();
+
+        exports.dedup();
+
+        if exports.is_empty() {
+            return;
+        }
+
+        let output_dir = &self.conf.output_dir;
+        if !output_dir.exists() {
+            if let Err(e) = utils::mkdir_recursive(output_dir) {
+                eprintln!("Kukuri::bindings_export() make export dir error: {:?}", e);
+                println!("bindings export skipped");
+                return;
+            }
+        }
+
+        let signatures = Bindings::infer_signatures(scenes);
+        for et in exports {
+            let (s, diagnostics) = match et {
+                BindingExportType::GDScript => Bindings::export_gdscript(&signatures),
+                BindingExportType::CSharp => Bindings::export_csharp(&signatures),
+                BindingExportType::TypeScript => Bindings::export_typescript(&signatures),
+            };
+            Diagnostic::print_all(et.file_name(), &diagnostics);
+
+            let mut path = output_dir.clone();
+            path.push(et.file_name());
+
+            utils::write_file(path, &s).expect("Unable to write file.");
+        }
+    }
+
+    // language code of original locale e.g. "ja_JP" => "ja"
+    fn orig_lang(&self) -> &str {
+        (self.conf.orig_locale.as_str())
+            .split('_')
+            .next()
+            .unwrap_or("en")
+    }
+
+    fn l10n_dialect(&self) -> Dialect {
+        Dialect::parse(&self.conf.l10n_markup).unwrap_or_else(|| {
+            eprintln!(
+                "Unknown l10n markup: {}, fallback to bbcode",
+                self.conf.l10n_markup
+            );
+            Dialect::BBCode
+        })
+    }
+
+    // collect used characters per locale for font subsetting.
+    // .po inputs are translations, other inputs are original scripts.
+    pub fn run_glyphs(&self) {
+        let mut glyph_sets: BTreeMap<String, GlyphSet> = BTreeMap::new();
+        let (data, po_files) = self.import_with_po();
+
+        let orig = glyph_sets
+            .entry(String::from(self.orig_lang()))
+            .or_default();
+        for d in &data {
+            match d {
+                KukuriData::Scenes(sc) => orig.add_scenes(sc),
+                KukuriData::Talkers(tk) => orig.add_talkers(tk),
+                KukuriData::Script(sc, tk) => {
+                    orig.add_scenes(sc);
+                    orig.add_talkers(tk);
+                }
+            }
+        }
+
+        let dialect = self.l10n_dialect();
+        for (_, po) in &po_files {
+            glyph_sets
+                .entry(po.locale.clone())
+                .or_default()
+                .add_po(po, dialect);
+        }
+
+        let output_dir = &self.conf.output_dir;
+        if !output_dir.exists() {
+            if let Err(e) = utils::mkdir_recursive(output_dir) {
+                eprintln!("Kukuri::run_glyphs() make export dir error: {:?}", e);
+                println!("glyphs export skipped");
+                return;
+            }
+        }
+
+        for (locale, gs) in glyph_sets {
+            let mut path = output_dir.clone();
+            path.push(format!("glyphs_{}.txt", locale));
+            utils::write_file(&path, &gs.export_string()).expect("Unable to write file.");
+            println!("{}: {} glyphs", path.display(), gs.len());
+        }
+    }
+
+    // report texts using characters which the locale font lacks.
+    // .po inputs are translations, other inputs are original scripts.
+    // return: false if any configured font is unable to load
+    pub fn run_check_fonts(&self) -> bool {
+        let (data, po_files) = self.import_with_po();
+        let mut is_loaded = true;
+
+        let orig_locale = &self.conf.orig_locale;
+        if let Some(fc) = self.load_font(orig_locale, &mut is_loaded) {
+            for d in &data {
+                let diagnostics = match d {
+                    KukuriData::Scenes(sc) => fc.check_scenes(sc),
+                    KukuriData::Talkers(tk) => fc.check_talkers(tk),
+                    KukuriData::Script(sc, tk) => {
+                        let mut diagnostics = fc.check_scenes(sc);
+                        diagnostics.extend(fc.check_talkers(tk));
+                        diagnostics
+                    }
+                };
+                Diagnostic::print_all("", &diagnostics);
+            }
+        }
+
+        let dialect = self.l10n_dialect();
+        for (source, po) in &po_files {
+            if let Some(fc) = self.load_font(&po.locale, &mut is_loaded) {
+                Diagnostic::print_all(source, &fc.check_po(po, dialect));
+            }
+        }
+
+        is_loaded
+    }
+
+    // render scenes of each input into "<file stem>.kkr"
+    pub fn run_decompile(&self) {
+        let output_dir = &self.conf.output_dir;
+        if !output_dir.exists() {
+            if let Err(e) = utils::mkdir_recursive(output_dir) {
+                eprintln!("Kukuri::run_decompile() make export dir error: {:?}", e);
+                println!("decompile skipped");
+                return;
+            }
+        }
+
+        // output path: input written to it
+        let mut written: BTreeMap<PathBuf, &PathBuf> = BTreeMap::new();
+        for p in &self.inputs {
+            let scenes = match self.import(p) {
+                KukuriData::Scenes(sc) | KukuriData::Script(sc, _) => sc,
+                KukuriData::Talkers(_) => Vec::new(),
+            };
+            let file_stem = p
+                .file_stem()
+                .map_or(String::from("output"), |s| s.to_string_lossy().into_owned());
+            if scenes.is_empty() {
+                println!("{}: no scenes, decompile skipped", p.display());
+                continue;
+            }
+
+            let mut path = output_dir.clone();
+            path.push(format!("{}.kkr", file_stem));
+            if path.canonicalize().ok() == p.canonicalize().ok() {
+                eprintln!(
+                    "{}: decompile skipped, not to overwrite the input",
+                    p.display()
+                );
+                continue;
+            }
+            // e.g. "a/intro.json" and "b/intro.json"
+            if let Some(other) = written.get(&path) {
+                eprintln!(
+                    "{}: decompile skipped, {} is already written from {}",
+                    p.display(),
+                    path.display(),
+                    other.display()
+                );
+                continue;
+            }
+
+            let (s, diagnostics) = Kkr::export_string(&scenes);
+            Diagnostic::print_all(path.display().to_string(), &diagnostics);
+            utils::write_file(&path, &s).expect("Unable to write file.");
+            println!("{}: {} scenes", path.display(), scenes.len());
+            written.insert(path, p);
+        }
+    }
+
+    // is_loaded becomes false if a configured font is unable to load
+    fn load_font(&self, locale: &str, is_loaded: &mut bool) -> Option<FontCoverage> {
+        let path = match self.conf.font_for(locale) {
+            Some(p) => p,
+            None => {
+                println!("no font for locale `{}`, check skipped", locale);
+                return None;
+            }
+        };
+
+        match FontCoverage::from_file(path) {
+            Ok(fc) => Some(fc),
+            Err(e) => {
+                eprintln!("Failed to load font {}: {}", path.display(), e);
+                *is_loaded = false;
+                None
+            }
+        }
+    }
+
+    // import inputs, .po files are parsed as translations
+    fn import_with_po(&self) -> (Vec<KukuriData>, Vec<(String, PoFile)>) {
+        let (po_inputs, inputs): (Vec<&PathBuf>, Vec<&PathBuf>) = self
+            .inputs
+            .iter()
+            .partition(|p| p.extension().is_some_and(|ext| ext == "po"));
+
+        let data = inputs.iter().map(|p| self.import(p)).collect();
+        let po_files = po_inputs
+            .iter()
+            .filter_map(|p| Some((p.display().to_string(), self.import_po(p)?)))
+            .collect();
+        (data, po_files)
+    }
+
+    fn import_po<P: AsRef<Path>>(&self, path: P) -> Option<PoFile> {
+        let s = match utils::read_file(path.as_ref()) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Failed to load {}: {:?}", path.as_ref().display(), e);
+                return None;
+            }
+        };
+
+        let source = path.as_ref().display().to_string();
+        let (mut po, diagnostics) = PoFile::parse_with_diagnostics(&s);
+        Diagnostic::print_all(&source, &diagnostics);
+
+        // fallback to file name e.g. "locale/en.po"
+        if po.locale.is_empty() {
+            po.locale = path
+                .as_ref()
+                .file_stem()
+                .map_or(String::new(), |s| s.to_string_lossy().into_owned());
+        }
+        Some(po)
+    }
+
     fn scenes_to_hashmap(scenes: &Vec<Scene>) -> Scenes {
         // scenes hash map
         let mut shm = HashMap::new();
@@ -242,6 +748,14 @@ impl Kukuri {
         }
         shm
     }
+
+    fn scene_metas_to_hashmap(scenes: &[Scene]) -> SceneMetas {
+        scenes
+            .iter()
+            .filter(|sc| !sc.meta.is_empty())
+            .map(|sc| (sc.title.clone(), sc.meta.clone()))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -278,7 +792,7 @@ mod tests {
 
         let tests = [(current_dir, kkr0), (tmp_dir, kkr1)];
 
-        for &(ref src, ref expected) in &tests {
+        for (src, expected) in &tests {
             assert_eq!(*src, *expected.conf.l10n_output_dir);
         }
     }
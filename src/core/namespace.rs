@@ -0,0 +1,236 @@
+use super::command::CommandArg;
+use super::diagnostic::Diagnostic;
+use super::dialog::{DialogBody, DialogKind, Scene};
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+
+// "chapter1/intro.kkr" => "chapter1.intro"
+// path is taken relative to root, if it is under root.
+// otherwise only the file stem is used.
+pub fn from_path(path: &Path, root: &Path) -> String {
+    let (path_abs, root_abs) = (absolute(path), absolute(root));
+    let names: Vec<String> = match path_abs.strip_prefix(&root_abs) {
+        Ok(rel) => rel
+            .with_extension("")
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(s) => Some(escape_name(s)),
+                _ => None,
+            })
+            .collect(),
+        Err(_) => path.file_stem().map(escape_name).into_iter().collect(),
+    };
+    names.join(".")
+}
+
+// canonical path, or lexically normalized absolute path if it does not exist
+fn absolute(p: &Path) -> PathBuf {
+    if let Ok(abs) = std::fs::canonicalize(p) {
+        return abs;
+    }
+
+    let joined = std::env::current_dir().map_or_else(|_| p.to_path_buf(), |d| d.join(p));
+    let mut abs = PathBuf::new();
+    for c in joined.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                abs.pop();
+            }
+            c => abs.push(c),
+        }
+    }
+    abs
+}
+
+// "." separates namespaces, so it is replaced in a name
+// e.g. "v1.2" => "v1_2"
+pub fn escape_name<T: AsRef<OsStr>>(name: T) -> String {
+    name.as_ref().to_string_lossy().replace('.', "_")
+}
+
+// "chapter1" + "Opening" => "chapter1.Opening"
+pub fn qualify(namespace: &str, name: &str) -> String {
+    if namespace.is_empty() {
+        String::from(name)
+    } else {
+        format!("{}.{}", namespace, name)
+    }
+}
+
+// "chapter1.intro.Opening" => "chapter1.intro"
+pub fn namespace_of(title: &str) -> &str {
+    title.rsplit_once('.').map_or("", |(ns, _)| ns)
+}
+
+// Resolve scene names in jump commands into qualified titles.
+// a name is looked up from the scene namespace to its parents,
+// then as a qualified title.
+pub struct JumpResolver<'a> {
+    titles: BTreeSet<String>,
+    commands: &'a [String],
+}
+
+impl<'a> JumpResolver<'a> {
+    pub fn new<'b, I: Iterator<Item = &'b Scene>>(scenes: I, commands: &'a [String]) -> Self {
+        Self {
+            titles: scenes.map(|sc| sc.title.clone()).collect(),
+            commands,
+        }
+    }
+
+    pub fn resolve(&self, namespace: &str, name: &str) -> Option<String> {
+        let mut ns = namespace;
+        loop {
+            let title = qualify(ns, name);
+            if self.titles.contains(&title) {
+                return Some(title);
+            }
+            if ns.is_empty() {
+                return None;
+            }
+            ns = namespace_of(ns);
+        }
+    }
+
+    // rewrite the first argument of jump commands, including inline commands
+    // return: warnings of unresolved scene names
+    pub fn resolve_scenes(&self, scenes: &mut [Scene]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for sc in scenes {
+            let ns = String::from(namespace_of(&sc.title));
+            sc.walk_dialogs_mut(|d| {
+                let mut unresolved = Vec::new();
+                if d.kind == DialogKind::Command && self.is_jump(&d.id) {
+                    if let Some(DialogBody::Arg(arg)) = d.args.first_mut() {
+                        unresolved.extend(self.resolve_arg(&ns, arg));
+                    }
+                }
+                for ic in &mut d.inline_commands {
+                    if self.is_jump(&ic.id) {
+                        if let Some(arg) = ic.args.first_mut() {
+                            unresolved.extend(self.resolve_arg(&ns, arg));
+                        }
+                    }
+                }
+                for name in unresolved {
+                    diagnostics.push(
+                        Diagnostic::warning(0, format!("unresolved jump target `{}`", name))
+                            .at(&d.loc),
+                    );
+                }
+            });
+        }
+        diagnostics
+    }
+
+    fn is_jump(&self, id: &str) -> bool {
+        self.commands.iter().any(|c| c == id)
+    }
+
+    // return: the name if it is unresolved
+    fn resolve_arg(&self, namespace: &str, arg: &mut CommandArg) -> Option<String> {
+        if let CommandArg::Ident(s) | CommandArg::Str(s) = arg {
+            match self.resolve(namespace, s) {
+                Some(title) => *s = title,
+                None => return Some(s.clone()),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_path, namespace_of, JumpResolver};
+    use crate::core::command::{CommandArg, InlineCommand};
+    use crate::core::diagnostic::Diagnostic;
+    use crate::core::dialog::{Dialog, DialogBody, DialogKind, Scene};
+    use std::path::Path;
+
+    fn gen_scene(title: &str, dialogs: Vec<Dialog>) -> Scene {
+        let mut sc = Scene::new();
+        sc.title = String::from(title);
+        sc.dialogs = dialogs;
+        sc
+    }
+
+    fn gen_jump(target: &str) -> Dialog {
+        Dialog::from_dialog_data(
+            DialogKind::Command,
+            "jump",
+            vec![DialogBody::Arg(CommandArg::gen_ident(target))],
+        )
+    }
+
+    #[test]
+    fn test_from_path() {
+        let tests = [
+            ("chapter1/intro.kkr", "", "chapter1.intro"),
+            ("./chapter1/intro.kkr", ".", "chapter1.intro"),
+            (
+                "/tmp/kukuri/chapter1/intro.kkr",
+                "/tmp/kukuri",
+                "chapter1.intro",
+            ),
+            ("intro.kkr", "/not_found", "intro"),
+            ("/tmp/other/chapter1/intro.kkr", "/tmp/kukuri", "intro"),
+            ("chapter1/../chapter2/intro.kkr", "", "chapter2.intro"),
+            ("./v1.2/intro.kkr", ".", "v1_2.intro"),
+        ];
+
+        for (path, root, expected) in &tests {
+            assert_eq!(*expected, from_path(Path::new(path), Path::new(root)));
+        }
+        assert_eq!("chapter1.intro", namespace_of("chapter1.intro.Opening"));
+        assert_eq!("", namespace_of("Opening"));
+    }
+
+    #[test]
+    fn test_resolve_scenes() {
+        let mut scenes = vec![
+            gen_scene(
+                "chapter1.intro.Opening",
+                vec![
+                    gen_jump("Ending"),
+                    gen_jump("outro.Ending"),
+                    gen_jump("Common"),
+                    gen_jump("Unknown"),
+                    Dialog::from_dialog_data(DialogKind::Dialog, "Test_5_A", Vec::new())
+                        .with_inline_commands(vec![InlineCommand::from_command_data(
+                            0,
+                            "jump",
+                            vec![CommandArg::gen_str("Ending")],
+                        )]),
+                ],
+            ),
+            gen_scene("chapter1.intro.Ending", Vec::new()),
+            gen_scene("chapter1.outro.Ending", Vec::new()),
+            gen_scene("Common", Vec::new()),
+        ];
+
+        let commands = vec![String::from("jump")];
+        let resolver = JumpResolver::new(scenes.iter(), &commands);
+        let diagnostics = resolver.resolve_scenes(&mut scenes);
+        assert_eq!(
+            vec![Diagnostic::warning(0, "unresolved jump target `Unknown`")],
+            diagnostics
+        );
+
+        let dialogs = &scenes[0].dialogs;
+        let expected = [
+            "chapter1.intro.Ending",
+            "chapter1.outro.Ending",
+            "Common",
+            "Unknown",
+        ];
+        for (d, title) in dialogs.iter().zip(&expected) {
+            assert_eq!(DialogBody::Arg(CommandArg::gen_ident(title)), d.args[0]);
+        }
+        assert_eq!(
+            CommandArg::gen_str("chapter1.intro.Ending"),
+            dialogs[4].inline_commands[0].args[0]
+        );
+    }
+}
@@ -1,30 +1,200 @@
+use super::diagnostic::{Diagnostic, SourceLoc};
+use super::meta::MetaValue;
+use serde::ser::{SerializeMap, Serializer};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-// Talker(id, name)
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
-pub struct Talker(String, String);
+// Talker definition, e.g. a table of talkers toml
+//
+// [A]
+// name = "Alpha"
+// color = "#ff4040"
+// portrait = "portraits/alpha.png"
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize)]
+pub struct Talker {
+    // "TALKER_A", given by the table key
+    #[serde(skip)]
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    // name color e.g. "#ff4040"
+    pub color: Option<String>,
+    pub portrait: Option<String>,
+    // prefix of voice file names e.g. "voice/alpha_"
+    pub voice_prefix: Option<String>,
+    pub textbox_style: Option<String>,
+    pub default_expression: Option<String>,
+    // any other keys
+    #[serde(flatten)]
+    pub custom: BTreeMap<String, MetaValue>,
+    // source location, not serialized
+    #[serde(skip)]
+    pub loc: SourceLoc,
+}
 
 impl Default for Talker {
     fn default() -> Self {
-        Self(String::from("unknown"), String::from("unknown"))
+        Self::from_strs("unknown", "unknown")
     }
 }
 
 impl Talker {
-    #[allow(dead_code)]
-    pub fn new() -> Self {
-        Talker::default()
+    pub fn from_strs<T: AsRef<str>, T2: AsRef<str>>(id: T, name: T2) -> Self {
+        Self {
+            id: String::from(id.as_ref()),
+            name: String::from(name.as_ref()),
+            color: None,
+            portrait: None,
+            voice_prefix: None,
+            textbox_style: None,
+            default_expression: None,
+            custom: BTreeMap::new(),
+            loc: SourceLoc::default(),
+        }
     }
 
-    pub fn from_strs<T: AsRef<str>, T2: AsRef<str>>(id: T, name: T2) -> Self {
-        Self(String::from(id.as_ref()), String::from(name.as_ref()))
+    pub fn with_loc(mut self, loc: SourceLoc) -> Self {
+        self.loc = loc;
+        self
     }
 
     pub fn id(&self) -> &str {
-        &self.0
+        &self.id
     }
 
     pub fn name(&self) -> &str {
-        &self.1
+        &self.name
+    }
+
+    pub fn loc(&self) -> &SourceLoc {
+        &self.loc
+    }
+
+    pub fn loc_mut(&mut self) -> &mut SourceLoc {
+        &mut self.loc
+    }
+
+    fn attributes(&self) -> Vec<(&str, &String)> {
+        let attrs = [
+            ("color", &self.color),
+            ("portrait", &self.portrait),
+            ("voice_prefix", &self.voice_prefix),
+            ("textbox_style", &self.textbox_style),
+            ("default_expression", &self.default_expression),
+        ];
+        attrs
+            .iter()
+            .filter_map(|(k, v)| v.as_ref().map(|v| (*k, v)))
+            .collect()
+    }
+}
+
+// Entry of talker table.
+// id is the key of talker table,
+// and name is excluded when it is in .po with use_l10n_output.
+// {"name": "Alpha", "color": "#ff4040", "age": 17}
+pub struct TalkerEntry<'a> {
+    pub talker: &'a Talker,
+    pub is_name_excluded: bool,
+}
+
+impl Serialize for TalkerEntry<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut sm = serializer.serialize_map(None)?;
+        if !self.is_name_excluded {
+            sm.serialize_entry("name", &self.talker.name)?;
+        }
+        for (k, v) in self.talker.attributes() {
+            sm.serialize_entry(k, v)?;
+        }
+        for (k, v) in &self.talker.custom {
+            sm.serialize_entry(k, v)?;
+        }
+        sm.end()
+    }
+}
+
+impl Serialize for Talker {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let entry = TalkerEntry {
+            talker: self,
+            is_name_excluded: false,
+        };
+        entry.serialize(serializer)
+    }
+}
+
+// talkers defined more than once, the last definition is used
+pub fn check_duplicates(talkers: &[Talker]) -> Vec<Diagnostic> {
+    let mut defined: BTreeMap<&str, &SourceLoc> = BTreeMap::new();
+    let mut diagnostics = Vec::new();
+    for t in talkers {
+        if let Some(loc) = defined.insert(t.id(), t.loc()) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    0,
+                    format!(
+                        "talker `{}` is defined again, the definition at {}:{} is overridden",
+                        t.id(),
+                        loc.path,
+                        loc.line
+                    ),
+                )
+                .at(t.loc()),
+            );
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_duplicates, Talker, TalkerEntry};
+    use crate::core::diagnostic::{Diagnostic, SourceLoc};
+
+    #[test]
+    fn test_serialize_entry() {
+        let mut talker = Talker::from_strs("TALKER_A", "Alpha");
+        talker.color = Some(String::from("#ff4040"));
+        assert_eq!(
+            r##"{"name":"Alpha","color":"#ff4040"}"##,
+            serde_json::to_string(&talker).unwrap()
+        );
+
+        let entry = TalkerEntry {
+            talker: &talker,
+            is_name_excluded: true,
+        };
+        assert_eq!(
+            r##"{"color":"#ff4040"}"##,
+            serde_json::to_string(&entry).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_check_duplicates() {
+        let loc = |path: &str, line: usize| SourceLoc {
+            path: String::from(path),
+            line,
+        };
+        let talkers = vec![
+            Talker::from_strs("TALKER_A", "Alpha").with_loc(loc("a.kkrt", 1)),
+            Talker::from_strs("TALKER_B", "Bravo").with_loc(loc("a.kkrt", 2)),
+            Talker::from_strs("TALKER_A", "Alef").with_loc(loc("cast.talkers.toml", 3)),
+        ];
+        assert_eq!(
+            vec![Diagnostic::warning(
+                0,
+                "talker `TALKER_A` is defined again, the definition at a.kkrt:1 is overridden"
+            )
+            .at(&loc("cast.talkers.toml", 3))],
+            check_duplicates(&talkers)
+        );
     }
 }
@@ -0,0 +1,333 @@
+use super::diagnostic::Diagnostic;
+use super::dialog::{Dialog, DialogBody, DialogKind, Scene};
+use super::markup::TextEdit;
+use crate::config::textbox::TextBoxConfig;
+use std::collections::BTreeMap;
+use unicode_width::UnicodeWidthChar;
+
+pub const PAGE_BREAK: char = '\u{c}';
+
+// kinsoku: chars never placed at line start
+const NO_LINE_START: &str = "、。，．,.・：；:;？！?!ー～…‥ヽヾゝゞ々〻\
+                             ）］｝〕〉》」』】〙〗)]}\"'’”\
+                             ぁぃぅぇぉっゃゅょゎゕゖァィゥェォッャュョヮヵヶ";
+// kinsoku: chars never placed at line end
+const NO_LINE_END: &str = "（［｛〔〈《「『【〘〖([{‘“";
+
+// line count of current page
+struct PageState {
+    rows: usize,
+    max_rows: usize,
+}
+
+// Export time line wrapping for message windows
+pub struct Wrapper<'a> {
+    conf: &'a TextBoxConfig,
+}
+
+impl<'a> Wrapper<'a> {
+    pub fn new(conf: &'a TextBoxConfig) -> Self {
+        Self { conf }
+    }
+
+    // insert line breaks and page breaks into all dialog texts
+    pub fn wrap_scenes(&self, scenes: &mut [Scene]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        if !self.conf.is_enabled() {
+            return diagnostics;
+        }
+
+        for sc in scenes {
+            sc.walk_dialogs_mut(|d| {
+                if let Some(diag) = self.wrap_dialog(d) {
+                    diagnostics.push(diag.at(&d.loc));
+                }
+            });
+        }
+        diagnostics
+    }
+
+    fn wrap_dialog(&self, d: &mut Dialog) -> Option<Diagnostic> {
+        if d.kind != DialogKind::Dialog {
+            return None;
+        }
+
+        let talker = match d.args.get(1) {
+            Some(DialogBody::Text(s)) => s.trim_start_matches("TALKER_"),
+            _ => "",
+        };
+        let (columns, rows) = self.conf.size_for(talker);
+        let mut rt = d.args.first()?.rich_text()?;
+
+        let (edits, max_rows) = self.line_breaks(&rt.plain_units(), columns, rows);
+
+        // inline commands after inserted chars are moved
+        for ic in &mut d.inline_commands {
+            ic.offset += edits
+                .range(..=ic.offset)
+                .filter(|(_, e)| matches!(e, TextEdit::Insert(_)))
+                .count();
+        }
+
+        if !edits.is_empty() {
+            rt.apply_edits(&edits);
+            d.args[0] = DialogBody::from_rich_text(rt);
+        }
+
+        if rows > 0 && max_rows > rows {
+            return Some(Diagnostic::warning(
+                0,
+                format!(
+                    "dialog `{}` has {} lines in a page, but textbox holds {} lines",
+                    d.id, max_rows, rows
+                ),
+            ));
+        }
+        None
+    }
+
+    // return: (edits, max line count in a page)
+    fn line_breaks(
+        &self,
+        units: &[(char, bool)],
+        columns: usize,
+        rows: usize,
+    ) -> (BTreeMap<usize, TextEdit>, usize) {
+        let mut edits = BTreeMap::new();
+        let mut page = PageState {
+            rows: 1,
+            max_rows: 1,
+        };
+
+        // width in half-width cells
+        let max_width = columns * 2;
+        let mut line_start = 0;
+        let mut width = 0;
+
+        for (i, &(c, _)) in units.iter().enumerate() {
+            if c == '\n' {
+                self.new_line(&mut page, rows, i, Some(c), &mut edits);
+                line_start = i + 1;
+                width = 0;
+                continue;
+            }
+            if c == PAGE_BREAK {
+                page.rows = 1;
+                line_start = i + 1;
+                width = 0;
+                continue;
+            }
+
+            let w = self.char_width(c);
+            // trailing spaces may overflow
+            let is_overflow = columns > 0 && width + w > max_width && !c.is_whitespace();
+            if is_overflow && i > line_start {
+                if let Some(pos) = Self::break_pos(units, line_start, i) {
+                    // a space at the break becomes the line break
+                    let prev = units[pos - 1].0;
+                    if prev.is_whitespace() {
+                        self.new_line(&mut page, rows, pos - 1, Some(prev), &mut edits);
+                    } else {
+                        self.new_line(&mut page, rows, pos, None, &mut edits);
+                    }
+                    line_start = pos;
+                    width = units[pos..i].iter().map(|&(c, _)| self.char_width(c)).sum();
+                }
+            }
+            width += w;
+        }
+
+        (edits, page.max_rows)
+    }
+
+    // begin a new line before units[pos],
+    // or in place of units[pos] if it is replaced e.g. an explicit "\n" or a space
+    fn new_line(
+        &self,
+        page: &mut PageState,
+        rows: usize,
+        pos: usize,
+        replaced: Option<char>,
+        edits: &mut BTreeMap<usize, TextEdit>,
+    ) {
+        if rows > 0 && page.rows >= rows && self.conf.page_break {
+            let e = if replaced.is_some() {
+                TextEdit::Replace(PAGE_BREAK)
+            } else {
+                TextEdit::Insert(PAGE_BREAK)
+            };
+            edits.insert(pos, e);
+            page.rows = 1;
+        } else {
+            match replaced {
+                None => {
+                    edits.insert(pos, TextEdit::Insert('\n'));
+                }
+                Some('\n') => {}
+                Some(_) => {
+                    edits.insert(pos, TextEdit::Replace('\n'));
+                }
+            }
+            page.rows += 1;
+            page.max_rows = page.max_rows.max(page.rows);
+        }
+    }
+
+    // find a line break position at or before units[end]
+    fn break_pos(units: &[(char, bool)], line_start: usize, end: usize) -> Option<usize> {
+        let can_break = |pos: usize| {
+            let (c, is_breakable) = units[pos];
+            let prev = units[pos - 1].0;
+            // inside of a latin word
+            let is_in_word = c.is_ascii_alphanumeric() && prev.is_ascii_alphanumeric();
+            is_breakable && !is_in_word && !NO_LINE_START.contains(c) && !NO_LINE_END.contains(prev)
+        };
+
+        (line_start + 1..=end)
+            .rev()
+            .find(|&pos| can_break(pos))
+            // a line without break opportunity, e.g. a long word
+            .or_else(|| Some(end).filter(|&pos| units[pos].1))
+    }
+
+    fn char_width(&self, c: char) -> usize {
+        let w = if self.conf.ambiguous_wide {
+            c.width_cjk()
+        } else {
+            c.width()
+        };
+        w.unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Wrapper;
+    use crate::config::textbox::{TextBoxConfig, TextBoxSize};
+    use crate::core::command::InlineCommand;
+    use crate::core::diagnostic::{Diagnostic, SourceLoc};
+    use crate::core::dialog::{Dialog, DialogBody, DialogKind, Scene};
+    use crate::core::markup::{RichText, Span};
+
+    fn gen_conf(columns: usize, rows: usize, page_break: bool) -> TextBoxConfig {
+        TextBoxConfig {
+            columns,
+            rows,
+            page_break,
+            ..Default::default()
+        }
+    }
+
+    fn gen_dialog(text: &str, talker: &str) -> Dialog {
+        Dialog::from_dialog_data(
+            DialogKind::Dialog,
+            format!("Test_1_{}", talker),
+            vec![
+                DialogBody::gen_text(text),
+                DialogBody::gen_text(format!("TALKER_{}", talker)),
+            ],
+        )
+    }
+
+    fn wrap_text(conf: &TextBoxConfig, text: &str) -> String {
+        let mut sc = Scene::new();
+        sc.dialogs.push(gen_dialog(text, "A"));
+        Wrapper::new(conf).wrap_scenes(std::slice::from_mut(&mut sc));
+        match &sc.dialogs[0].args[0] {
+            DialogBody::Text(s) => s.clone(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        let conf = gen_conf(5, 0, false);
+        let tests = [
+            ("あいうえお", "あいうえお"),
+            ("あいうえおか", "あいうえお\nか"),
+            // kinsoku: no "。" at line start
+            ("あいうえお。か", "あいうえ\nお。か"),
+            // kinsoku: no "「" at line end
+            ("あいうえ「お」", "あいうえ\n「お」"),
+            ("あい\nうえおかきく", "あい\nうえおかき\nく"),
+            ("Hello big world", "Hello big\nworld"),
+            ("Supercalifragilistic", "Supercalif\nragilistic"),
+        ];
+
+        for (src, expected) in &tests {
+            assert_eq!(*expected, wrap_text(&conf, src));
+        }
+    }
+
+    #[test]
+    fn test_wrap_page() {
+        let conf = gen_conf(2, 2, true);
+        assert_eq!(
+            "あい\nうえ\u{c}おか\nき",
+            wrap_text(&conf, "あいうえおかき")
+        );
+        assert_eq!("あ\nい\u{c}う", wrap_text(&conf, "あ\nい\nう"));
+        assert_eq!("ab\ncd\u{c}ef", wrap_text(&conf, "ab cd ef"));
+    }
+
+    #[test]
+    fn test_wrap_rich_text() {
+        let conf = gen_conf(2, 0, false);
+        let mut d = gen_dialog("", "A");
+        d.args[0] = DialogBody::Rich(RichText {
+            spans: vec![
+                Span::Text(String::from("あ")),
+                Span::Ruby(String::from("漢字"), String::from("かんじ")),
+            ],
+        });
+        d.inline_commands = vec![
+            InlineCommand::from_command_data(1, "wait", Vec::new()),
+            InlineCommand::from_command_data(3, "clear", Vec::new()),
+        ];
+        let mut sc = Scene::new();
+        sc.dialogs.push(d);
+        Wrapper::new(&conf).wrap_scenes(std::slice::from_mut(&mut sc));
+
+        // ruby base is never broken
+        let expected = RichText {
+            spans: vec![
+                Span::Text(String::from("あ\n")),
+                Span::Ruby(String::from("漢字"), String::from("かんじ")),
+            ],
+        };
+        assert_eq!(DialogBody::Rich(expected), sc.dialogs[0].args[0]);
+        assert_eq!(2, sc.dialogs[0].inline_commands[0].offset);
+        assert_eq!(4, sc.dialogs[0].inline_commands[1].offset);
+    }
+
+    #[test]
+    fn test_overflow_warning() {
+        let mut conf = gen_conf(2, 2, false);
+        conf.talkers.insert(
+            String::from("N"),
+            TextBoxSize {
+                columns: Some(4),
+                rows: None,
+            },
+        );
+
+        let mut sc = Scene::new();
+        sc.dialogs = vec![
+            gen_dialog("あいうえおか", "A").with_loc(SourceLoc::from_line(1)),
+            gen_dialog("あいうえおか", "N").with_loc(SourceLoc::from_line(2)),
+        ];
+        let diagnostics = Wrapper::new(&conf).wrap_scenes(std::slice::from_mut(&mut sc));
+
+        assert_eq!(
+            vec![Diagnostic::warning(
+                1,
+                "dialog `Test_1_A` has 3 lines in a page, but textbox holds 2 lines"
+            )],
+            diagnostics
+        );
+        assert_eq!(
+            DialogBody::gen_text("あいうえ\nおか"),
+            sc.dialogs[1].args[0]
+        );
+    }
+}
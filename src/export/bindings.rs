@@ -0,0 +1,632 @@
+use crate::core::command::CommandArg;
+use crate::core::diagnostic::Diagnostic;
+use crate::core::dialog::Scene;
+use std::collections::BTreeMap;
+
+const GENERATED_HEADER: &str = "Generated by kukuri. Do not edit.";
+
+// reserved words which can not be identifiers, and GDScript built-in constants
+// split by whitespace, GDScript words are of Godot 4
+const GD_KEYWORDS: &str = "\
+    and as assert await break breakpoint class class_name const continue elif else enum \
+    extends false for func if in is match namespace not null or pass preload return self \
+    signal static super trait true var void when while yield INF NAN PI TAU";
+const CS_KEYWORDS: &str = "\
+    abstract as base bool break byte case catch char checked class const continue decimal \
+    default delegate do double else enum event explicit extern false finally fixed float for \
+    foreach goto if implicit in int interface internal is lock long namespace new null object \
+    operator out override params private protected public readonly ref return sbyte sealed \
+    short sizeof stackalloc static string struct switch this throw true try typeof uint ulong \
+    unchecked unsafe ushort using virtual void volatile while";
+const TS_KEYWORDS: &str = "\
+    break case catch class const continue debugger default delete do else enum export extends \
+    false finally for function if import in instanceof new null return super switch this \
+    throw true try typeof var void while with";
+
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum ArgType {
+    String,
+    Int,
+    Float,
+    Bool,
+    Any,
+}
+
+impl ArgType {
+    fn from_arg(arg: &CommandArg) -> Self {
+        match arg {
+            CommandArg::Str(_) | CommandArg::Ident(_) => ArgType::String,
+            CommandArg::Int(_) => ArgType::Int,
+            CommandArg::Float(_) => ArgType::Float,
+            CommandArg::Bool(_) => ArgType::Bool,
+            CommandArg::Named(_, v) => Self::from_arg(v),
+        }
+    }
+
+    // merge argument types of two usages
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ArgType::Int, ArgType::Float) | (ArgType::Float, ArgType::Int) => ArgType::Float,
+            _ => ArgType::Any,
+        }
+    }
+
+    fn gd(&self) -> &str {
+        match self {
+            ArgType::String => "String",
+            ArgType::Int => "int",
+            ArgType::Float => "float",
+            ArgType::Bool => "bool",
+            ArgType::Any => "",
+        }
+    }
+
+    fn cs(&self, is_optional: bool) -> String {
+        let s = match self {
+            ArgType::String => "string",
+            ArgType::Int => "long",
+            ArgType::Float => "double",
+            ArgType::Bool => "bool",
+            ArgType::Any => "object",
+        };
+        let is_value_type = matches!(self, ArgType::Int | ArgType::Float | ArgType::Bool);
+        if is_optional && is_value_type {
+            format!("{}?", s)
+        } else {
+            String::from(s)
+        }
+    }
+
+    fn ts(&self) -> &str {
+        match self {
+            ArgType::String => "string",
+            ArgType::Int | ArgType::Float => "number",
+            ArgType::Bool => "boolean",
+            ArgType::Any => "unknown",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Param {
+    pub name: String,
+    pub arg_type: ArgType,
+    // true if some usages omit this argument
+    pub is_optional: bool,
+    pub is_named: bool,
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct CommandSignature {
+    pub id: String,
+    pub params: Vec<Param>,
+}
+
+impl CommandSignature {
+    fn positional_params(&self) -> impl Iterator<Item = (usize, &Param)> {
+        self.params.iter().filter(|p| !p.is_named).enumerate()
+    }
+}
+
+pub struct Bindings;
+
+impl Bindings {
+    // infer command signatures from all command usages in scenes
+    pub fn infer_signatures(scenes: &[Scene]) -> Vec<CommandSignature> {
+        // id: usages
+        let mut usages: BTreeMap<String, Vec<Vec<CommandArg>>> = BTreeMap::new();
+        for sc in scenes {
+            sc.walk_dialogs(|d| {
+                for (id, args) in d.command_calls() {
+                    let args = args.into_iter().cloned().collect();
+                    usages.entry(String::from(id)).or_default().push(args);
+                }
+            });
+        }
+
+        usages
+            .into_iter()
+            .map(|(id, usages)| Self::infer_signature(id, &usages))
+            .collect()
+    }
+
+    fn infer_signature(id: String, usages: &[Vec<CommandArg>]) -> CommandSignature {
+        let mut positional: Vec<(ArgType, usize)> = Vec::new();
+        let mut named: BTreeMap<String, (ArgType, usize)> = BTreeMap::new();
+
+        for args in usages {
+            let mut i = 0;
+            for arg in args {
+                let t = ArgType::from_arg(arg);
+                let entry = match arg {
+                    CommandArg::Named(k, _) => named.entry(k.clone()).or_insert((t, 0)),
+                    _ => {
+                        if positional.len() <= i {
+                            positional.push((t, 0));
+                        }
+                        i += 1;
+                        &mut positional[i - 1]
+                    }
+                };
+                entry.0 = entry.0.merge(t);
+                entry.1 += 1;
+            }
+        }
+
+        let usage_cnt = usages.len();
+        let mut params: Vec<Param> = positional
+            .into_iter()
+            .enumerate()
+            .map(|(i, (arg_type, cnt))| Param {
+                name: format!("arg{}", i),
+                arg_type,
+                is_optional: cnt < usage_cnt,
+                is_named: false,
+            })
+            .collect();
+
+        // named arguments are always optional for handlers
+        params.extend(named.into_iter().map(|(name, (arg_type, _))| Param {
+            name,
+            arg_type,
+            is_optional: true,
+            is_named: true,
+        }));
+
+        CommandSignature { id, params }
+    }
+
+    pub fn export_gdscript(signatures: &[CommandSignature]) -> (String, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let mut s = format!("# {}\n", GENERATED_HEADER);
+        s.push_str("class_name KukuriCommandHandler\nextends RefCounted\n\n");
+
+        let names = unique_names(
+            "GDScript command",
+            signatures.iter().map(|sig| sig.id.as_str()),
+            to_snake,
+            &mut diagnostics,
+        );
+        let consts: Vec<String> = names
+            .iter()
+            .map(|n| escape_ident(n.to_uppercase(), GD_KEYWORDS))
+            .collect();
+        s.push_str(&format!("enum Command {{ {} }}\n\n", consts.join(", ")));
+
+        s.push_str("const COMMANDS := {\n");
+        for (sig, c) in signatures.iter().zip(&consts) {
+            s.push_str(&format!("\t\"{}\": Command.{},\n", sig.id, c));
+        }
+        s.push_str("}\n\n");
+
+        s.push_str("func dispatch(id: String, args: Array) -> void:\n");
+        s.push_str("\tvar positional := []\n\tvar named := {}\n");
+        s.push_str("\tfor arg in args:\n");
+        s.push_str("\t\tif arg is Dictionary:\n\t\t\tfor k in arg:\n\t\t\t\tnamed[k] = arg[k]\n");
+        s.push_str("\t\telse:\n\t\t\tpositional.append(arg)\n\n");
+        s.push_str("\tmatch id:\n");
+        for (sig, name) in signatures.iter().zip(&names) {
+            let call_args: Vec<String> = sig
+                .params
+                .iter()
+                .filter(|p| p.is_named)
+                .map(|p| format!("named.get(\"{}\")", p.name))
+                .collect();
+            let positional_args: Vec<String> = sig
+                .positional_params()
+                .map(|(i, p)| {
+                    if p.is_optional {
+                        format!("positional[{0}] if positional.size() > {0} else null", i)
+                    } else {
+                        format!("positional[{}]", i)
+                    }
+                })
+                .collect();
+            let all_args = [positional_args, call_args].concat();
+            s.push_str(&format!(
+                "\t\t\"{}\":\n\t\t\t_cmd_{}({})\n",
+                sig.id,
+                name,
+                all_args.join(", ")
+            ));
+        }
+        s.push_str("\t\t_:\n\t\t\tpush_error(\"Unknown kukuri command: %s\" % id)\n");
+
+        s.push_str("\n# Override handlers below.\n");
+        for (sig, name) in signatures.iter().zip(&names) {
+            // parameters are prefixed with "_", so keywords need no escape
+            let param_names = unique_names(
+                &format!("GDScript parameter of `{}`", sig.id),
+                sig.params.iter().map(|p| p.name.as_str()),
+                to_snake,
+                &mut diagnostics,
+            );
+            let params: Vec<String> = sig
+                .params
+                .iter()
+                .zip(&param_names)
+                .map(|(p, pn)| {
+                    // typed parameter can not have null default value
+                    let t = p.arg_type.gd();
+                    if p.is_optional {
+                        format!("_{} = null", pn)
+                    } else if t.is_empty() {
+                        format!("_{}", pn)
+                    } else {
+                        format!("_{}: {}", pn, t)
+                    }
+                })
+                .collect();
+            s.push_str(&format!(
+                "\nfunc _cmd_{}({}) -> void:\n\tpush_error(\"kukuri command `{}` is not implemented\")\n",
+                name,
+                params.join(", "),
+                sig.id
+            ));
+        }
+
+        (s, diagnostics)
+    }
+
+    pub fn export_csharp(signatures: &[CommandSignature]) -> (String, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let mut s = format!("// {}\n", GENERATED_HEADER);
+        s.push_str("namespace Kukuri\n{\n");
+
+        let names = unique_names(
+            "C# command",
+            signatures.iter().map(|sig| sig.id.as_str()),
+            |id| escape_ident(to_pascal(id), CS_KEYWORDS),
+            &mut diagnostics,
+        );
+
+        s.push_str("    public static class CommandIds\n    {\n");
+        for (sig, name) in signatures.iter().zip(&names) {
+            s.push_str(&format!(
+                "        public const string {} = \"{}\";\n",
+                name, sig.id
+            ));
+        }
+        s.push_str("    }\n\n");
+
+        s.push_str("    public interface ICommandHandler\n    {\n");
+        for (sig, name) in signatures.iter().zip(&names) {
+            let param_names = unique_names(
+                &format!("C# parameter of `{}`", sig.id),
+                sig.params.iter().map(|p| p.name.as_str()),
+                |n| escape_ident(to_camel(n), CS_KEYWORDS),
+                &mut diagnostics,
+            );
+            let params: Vec<String> = sig
+                .params
+                .iter()
+                .zip(&param_names)
+                .map(|(p, pn)| {
+                    let t = p.arg_type.cs(p.is_optional);
+                    if p.is_optional {
+                        format!("{} {} = null", t, pn)
+                    } else {
+                        format!("{} {}", t, pn)
+                    }
+                })
+                .collect();
+            s.push_str(&format!("        void {}({});\n", name, params.join(", ")));
+        }
+        s.push_str("    }\n}\n");
+
+        (s, diagnostics)
+    }
+
+    pub fn export_typescript(signatures: &[CommandSignature]) -> (String, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let mut s = format!("// {}\n\n", GENERATED_HEADER);
+
+        let names = unique_names(
+            "TypeScript command",
+            signatures.iter().map(|sig| sig.id.as_str()),
+            |id| escape_ident(to_pascal(id), TS_KEYWORDS),
+            &mut diagnostics,
+        );
+
+        s.push_str("export const enum CommandId {\n");
+        for (sig, name) in signatures.iter().zip(&names) {
+            s.push_str(&format!("  {} = \"{}\",\n", name, sig.id));
+        }
+        s.push_str("}\n\n");
+
+        s.push_str("export interface CommandHandler {\n");
+        for (sig, name) in signatures.iter().zip(&names) {
+            let param_names = unique_names(
+                &format!("TypeScript parameter of `{}`", sig.id),
+                sig.params.iter().map(|p| p.name.as_str()),
+                |n| escape_ident(to_camel(n), TS_KEYWORDS),
+                &mut diagnostics,
+            );
+            let params: Vec<String> = sig
+                .params
+                .iter()
+                .zip(&param_names)
+                .map(|(p, pn)| {
+                    let q = if p.is_optional { "?" } else { "" };
+                    format!("{}{}: {}", pn, q, p.arg_type.ts())
+                })
+                .collect();
+            s.push_str(&format!(
+                "  {}({}): void;\n",
+                escape_ident(lower_first(name), TS_KEYWORDS),
+                params.join(", ")
+            ));
+        }
+        s.push_str("}\n");
+
+        (s, diagnostics)
+    }
+}
+
+// Convert names into identifiers of a target language.
+// names colliding after conversion are numbered with a warning,
+// e.g. "show-text" => "ShowText_2" if "show_text" => "ShowText" is already used.
+fn unique_names<'a, I, F>(
+    kind: &str,
+    names: I,
+    convert: F,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+    F: Fn(&str) -> String,
+{
+    // identifier: original name
+    let mut used: BTreeMap<String, &str> = BTreeMap::new();
+    names
+        .into_iter()
+        .map(|name| {
+            let ident = convert(name);
+            let mut unique = ident.clone();
+            let mut n = 1;
+            while used.contains_key(&unique) {
+                n += 1;
+                unique = format!("{}_{}", ident, n);
+            }
+            if n > 1 {
+                diagnostics.push(Diagnostic::warning(
+                    0,
+                    format!(
+                        "{} `{}` is renamed to `{}`, because `{}` is used by `{}`",
+                        kind, name, unique, ident, used[&ident]
+                    ),
+                ));
+            }
+            used.insert(unique.clone(), name);
+            unique
+        })
+        .collect()
+}
+
+// "1up" => "_1up", "class" => "class_"
+fn escape_ident(ident: String, keywords: &str) -> String {
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", ident)
+    } else if keywords.split_whitespace().any(|k| k == ident) {
+        format!("{}_", ident)
+    } else {
+        ident
+    }
+}
+
+// split command id into identifier words
+fn id_words(id: &str) -> Vec<String> {
+    id.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn to_snake(id: &str) -> String {
+    id_words(id).join("_").to_lowercase()
+}
+
+fn to_pascal(id: &str) -> String {
+    id_words(id)
+        .iter()
+        .map(|w| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn to_camel(id: &str) -> String {
+    lower_first(&to_pascal(id))
+}
+
+fn lower_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_camel, to_pascal, to_snake, ArgType, Bindings, CommandSignature, Param};
+    use crate::core::command::{CommandArg, Float};
+    use crate::core::dialog::{Dialog, DialogBody, DialogKind, Scene};
+
+    fn gen_command(id: &str, args: Vec<CommandArg>) -> Dialog {
+        Dialog::from_dialog_data(
+            DialogKind::Command,
+            id,
+            args.into_iter().map(DialogBody::Arg).collect(),
+        )
+    }
+
+    fn param(name: &str, arg_type: ArgType, is_optional: bool, is_named: bool) -> Param {
+        Param {
+            name: String::from(name),
+            arg_type,
+            is_optional,
+            is_named,
+        }
+    }
+
+    fn gen_signatures() -> Vec<CommandSignature> {
+        let mut sc = Scene::new();
+        sc.dialogs = vec![
+            gen_command("jump", vec![CommandArg::gen_ident("Opening")]),
+            gen_command(
+                "show_text",
+                vec![CommandArg::gen_str("Hello"), CommandArg::Int(1)],
+            ),
+            gen_command(
+                "show_text",
+                vec![
+                    CommandArg::gen_str("World"),
+                    CommandArg::Float(Float(0.5)),
+                    CommandArg::Bool(true),
+                    CommandArg::Named(String::from("fade"), Box::new(CommandArg::Bool(true))),
+                ],
+            ),
+        ];
+        Bindings::infer_signatures(&[sc])
+    }
+
+    #[test]
+    fn test_infer_signatures() {
+        let expected = vec![
+            CommandSignature {
+                id: String::from("jump"),
+                params: vec![param("arg0", ArgType::String, false, false)],
+            },
+            CommandSignature {
+                id: String::from("show_text"),
+                params: vec![
+                    param("arg0", ArgType::String, false, false),
+                    param("arg1", ArgType::Float, false, false),
+                    param("arg2", ArgType::Bool, true, false),
+                    param("fade", ArgType::Bool, true, true),
+                ],
+            },
+        ];
+
+        assert_eq!(expected, gen_signatures());
+    }
+
+    #[test]
+    fn test_merge() {
+        let tests = [
+            (ArgType::Int, ArgType::Int, ArgType::Int),
+            (ArgType::Int, ArgType::Float, ArgType::Float),
+            (ArgType::String, ArgType::Bool, ArgType::Any),
+        ];
+
+        for &(a, b, expected) in &tests {
+            assert_eq!(expected, a.merge(b));
+        }
+    }
+
+    #[test]
+    fn test_case_conversion() {
+        let tests = [
+            ("show_text", "show_text", "ShowText", "showText"),
+            ("bgm", "bgm", "Bgm", "bgm"),
+            ("play-se", "play_se", "PlaySe", "playSe"),
+        ];
+
+        for &(src, snake, pascal, camel) in &tests {
+            assert_eq!(snake, to_snake(src));
+            assert_eq!(pascal, to_pascal(src));
+            assert_eq!(camel, to_camel(src));
+        }
+    }
+
+    #[test]
+    fn test_export_csharp() {
+        let expected = "\
+// Generated by kukuri. Do not edit.
+namespace Kukuri
+{
+    public static class CommandIds
+    {
+        public const string Jump = \"jump\";
+        public const string ShowText = \"show_text\";
+    }
+
+    public interface ICommandHandler
+    {
+        void Jump(string arg0);
+        void ShowText(string arg0, double arg1, bool? arg2 = null, bool? fade = null);
+    }
+}
+";
+        assert_eq!(expected, Bindings::export_csharp(&gen_signatures()).0);
+    }
+
+    #[test]
+    fn test_export_typescript() {
+        let expected = "\
+// Generated by kukuri. Do not edit.
+
+export const enum CommandId {
+  Jump = \"jump\",
+  ShowText = \"show_text\",
+}
+
+export interface CommandHandler {
+  jump(arg0: string): void;
+  showText(arg0: string, arg1: number, arg2?: boolean, fade?: boolean): void;
+}
+";
+        assert_eq!(expected, Bindings::export_typescript(&gen_signatures()).0);
+    }
+
+    #[test]
+    fn test_export_gdscript() {
+        let (s, diagnostics) = Bindings::export_gdscript(&gen_signatures());
+        assert!(diagnostics.is_empty());
+        assert!(s.contains("class_name KukuriCommandHandler\nextends RefCounted\n"));
+        assert!(s.contains("enum Command { JUMP, SHOW_TEXT }\n"));
+        assert!(s.contains("\t\t\"show_text\":\n\t\t\t_cmd_show_text(positional[0], positional[1], positional[2] if positional.size() > 2 else null, named.get(\"fade\"))\n"));
+        assert!(s.contains("\nfunc _cmd_show_text(_arg0: String, _arg1: float, _arg2 = null, _fade = null) -> void:\n"));
+    }
+
+    #[test]
+    fn test_export_names() {
+        let mut sc = Scene::new();
+        sc.dialogs = vec![
+            gen_command("show-text", vec![CommandArg::Int(1)]),
+            gen_command("show_text", vec![CommandArg::Int(1)]),
+            gen_command(
+                "1up",
+                vec![CommandArg::Named(
+                    String::from("class"),
+                    Box::new(CommandArg::Bool(true)),
+                )],
+            ),
+            gen_command("delete", vec![]),
+        ];
+        let signatures = Bindings::infer_signatures(&[sc]);
+
+        let (s, diagnostics) = Bindings::export_csharp(&signatures);
+        assert!(s.contains("        void _1up(bool? class_ = null);\n"));
+        assert!(
+            s.contains("        void ShowText(long arg0);\n        void ShowText_2(long arg0);\n")
+        );
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            vec!["0: warning: C# command `show_text` is renamed to `ShowText_2`, because `ShowText` is used by `show-text`"],
+            messages
+        );
+
+        let (s, _) = Bindings::export_typescript(&signatures);
+        assert!(s.contains("  _1up(class_?: boolean): void;\n  delete_(): void;\n"));
+
+        let (s, _) = Bindings::export_gdscript(&signatures);
+        assert!(s.contains("enum Command { _1UP, DELETE, SHOW_TEXT, SHOW_TEXT_2 }\n"));
+    }
+}
@@ -1,17 +1,34 @@
 use super::json::Json;
-use crate::core::dialog::Scenes;
+use crate::core::dialog::{self, SceneMetas, Scenes};
+use crate::core::markup::Dialect;
+use crate::core::talker::Talker;
 
-const DEFAULT_GDSCRIPT_TEMPLATE: &'static str = include_str!("../templates/gd");
+const DEFAULT_GDSCRIPT_TEMPLATE: &str = include_str!("../templates/gd");
+const TALKERS_GDSCRIPT_TEMPLATE: &str = include_str!("../templates/gd_talkers");
 
 pub struct GDScript;
 
 impl GDScript {
-    fn replace_template<T: AsRef<str>>(json_str: T) -> String {
-        DEFAULT_GDSCRIPT_TEMPLATE.replace("$SCENES", json_str.as_ref())
+    fn replace_template<T: AsRef<str>, T2: AsRef<str>>(json_str: T, meta_str: T2) -> String {
+        DEFAULT_GDSCRIPT_TEMPLATE
+            .replace("$SCENES", json_str.as_ref())
+            .replace("$SCENE_META", meta_str.as_ref())
     }
 
-    pub fn export_string(scenes: &Scenes, is_minify: bool) -> String {
-        let json_str = Json::export_string(scenes, is_minify);
-        Self::replace_template(json_str)
+    pub fn export_string(scenes: &Scenes, metas: &SceneMetas, is_minify: bool) -> String {
+        // RichTextLabel BBCode
+        let scenes = dialog::render_scenes(scenes, Dialect::BBCode);
+        let json_str = Json::export_string(&scenes, is_minify);
+        let meta_str = Json::export_meta_string(metas, is_minify);
+        Self::replace_template(json_str, meta_str)
+    }
+
+    pub fn export_talkers_string(
+        talkers: &[Talker],
+        is_minify: bool,
+        is_name_excluded: bool,
+    ) -> String {
+        let json_str = Json::export_talkers_string(talkers, is_minify, is_name_excluded);
+        TALKERS_GDSCRIPT_TEMPLATE.replace("$TALKERS", &json_str)
     }
 }
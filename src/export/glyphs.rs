@@ -0,0 +1,192 @@
+use crate::core::dialog::{DialogBody, DialogKind, Scene};
+use crate::core::markup::{Dialect, RichText, Span};
+use crate::core::talker::Talker;
+use crate::import::po::{PoEntry, PoFile};
+use std::collections::BTreeSet;
+
+// Distinct characters for font subsetting
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GlyphSet {
+    chars: BTreeSet<char>,
+}
+
+impl GlyphSet {
+    #[cfg(test)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_str(&mut self, s: &str) {
+        // line breaks and page breaks have no glyph
+        self.chars.extend(s.chars().filter(|c| !c.is_control()));
+    }
+
+    pub fn add_rich_text(&mut self, rt: &RichText) {
+        fn add_spans(gs: &mut GlyphSet, spans: &[Span]) {
+            for span in spans {
+                match span {
+                    Span::Text(s) => gs.add_str(s),
+                    Span::Styled(_, children) => add_spans(gs, children),
+                    Span::Ruby(base, reading) => {
+                        gs.add_str(base);
+                        gs.add_str(reading);
+                    }
+                }
+            }
+        }
+        add_spans(self, &rt.spans);
+    }
+
+    // dialog texts and choice labels
+    pub fn add_scenes(&mut self, scenes: &[Scene]) {
+        for sc in scenes {
+            sc.walk_dialogs(|d| match d.kind {
+                DialogKind::Dialog => {
+                    if let Some(rt) = d.args.first().and_then(|b| b.rich_text()) {
+                        self.add_rich_text(&rt);
+                    }
+                }
+                DialogKind::Choices => {
+                    for arg in &d.args {
+                        if let DialogBody::Choice(cd) = arg {
+                            self.add_str(&cd.label);
+                        }
+                    }
+                }
+                DialogKind::Command => {}
+            });
+        }
+    }
+
+    pub fn add_talkers(&mut self, talkers: &[Talker]) {
+        talkers.iter().for_each(|t| self.add_str(t.name()));
+    }
+
+    // translated texts written in l10n markup dialect
+    pub fn add_po(&mut self, po: &PoFile, dialect: Dialect) {
+        po.entries
+            .iter()
+            .for_each(|e| self.add_po_entry(e, dialect));
+    }
+
+    pub fn add_po_entry(&mut self, e: &PoEntry, dialect: Dialect) {
+        let s = strip_placeholders(&e.msgstr);
+        self.add_str(&dialect.visible_text(&s));
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.chars.iter().copied()
+    }
+
+    // sorted characters in a line
+    pub fn export_string(&self) -> String {
+        let mut s: String = self.chars.iter().collect();
+        s.push('\n');
+        s
+    }
+}
+
+// remove inline command placeholders e.g. "{0}",
+// and decode literal braces "{{", "}}"
+pub fn strip_placeholders(s: &str) -> String {
+    let mut out_s = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find(['{', '}']) {
+        out_s.push_str(&rest[..i]);
+        let (c, after) = (&rest[i..i + 1], &rest[i + 1..]);
+        if let Some(r) = after.strip_prefix(c) {
+            out_s.push_str(c);
+            rest = r;
+            continue;
+        }
+        match after.split_once('}') {
+            Some((n, r)) if c == "{" && !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => {
+                rest = r
+            }
+            _ => {
+                out_s.push_str(c);
+                rest = after;
+            }
+        }
+    }
+    out_s.push_str(rest);
+    out_s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_placeholders, GlyphSet};
+    use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+    use crate::core::markup::{Dialect, RichText, Span};
+    use crate::core::talker::Talker;
+    use crate::import::po::{PoEntry, PoFile};
+
+    #[test]
+    fn test_add_scenes() {
+        let mut sc = Scene::new();
+        sc.dialogs = vec![
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "Test_1_A",
+                vec![
+                    DialogBody::Rich(RichText {
+                        spans: vec![
+                            Span::Ruby(String::from("漢"), String::from("かん")),
+                            Span::Text(String::from("字\nだ")),
+                        ],
+                    }),
+                    DialogBody::gen_text("TALKER_A"),
+                ],
+            ),
+            Dialog::from_dialog_data(
+                DialogKind::Choices,
+                "Test_2_C1",
+                vec![DialogBody::Choice(ChoiceData::from_texts(
+                    "Test_2_C1L1",
+                    "はい",
+                ))],
+            ),
+            Dialog::from_dialog_data(DialogKind::Command, "wait", Vec::new()),
+        ];
+
+        let mut gs = GlyphSet::new();
+        gs.add_scenes(&[sc]);
+        gs.add_talkers(&[Talker::from_strs("TALKER_A", "アルファ")]);
+
+        assert_eq!("いかだはんァアフル字漢\n", gs.export_string());
+    }
+
+    #[test]
+    fn test_add_po() {
+        let po = PoFile {
+            locale: String::from("en"),
+            entries: vec![PoEntry {
+                msgid: String::from("Test_1_A"),
+                msgstr: String::from("[b]Wait[/b]{0} [lb]ok]"),
+                line: 1,
+            }],
+        };
+
+        let mut gs = GlyphSet::new();
+        gs.add_po(&po, Dialect::BBCode);
+        assert_eq!(" W[]aikot\n", gs.export_string());
+        assert_eq!(9, gs.len());
+    }
+
+    #[test]
+    fn test_strip_placeholders() {
+        let tests = [
+            ("{0}Wait{1}{12}", "Wait"),
+            ("{漢字|かんじ} {x} {", "{漢字|かんじ} {x} {"),
+            ("{{0}}{0} {{漢字|かんじ}}", "{0} {漢字|かんじ}"),
+        ];
+
+        for (src, expected) in &tests {
+            assert_eq!(*expected, strip_placeholders(src));
+        }
+    }
+}
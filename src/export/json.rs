@@ -1,13 +1,45 @@
-use crate::core::dialog::Scenes;
+use crate::core::dialog::{SceneMetas, Scenes};
+use crate::core::talker::{Talker, TalkerEntry};
+use serde::Serialize;
+use std::collections::BTreeMap;
 
 pub struct Json;
 
 impl Json {
     pub fn export_string(scenes: &Scenes, is_minify: bool) -> String {
+        Self::to_string(scenes, is_minify)
+    }
+
+    // scene header metadata, keyed by scene title
+    pub fn export_meta_string(metas: &SceneMetas, is_minify: bool) -> String {
+        Self::to_string(metas, is_minify)
+    }
+
+    // talker attributes, keyed by talker id
+    // names are excluded when they are in .po
+    pub fn export_talkers_string(
+        talkers: &[Talker],
+        is_minify: bool,
+        is_name_excluded: bool,
+    ) -> String {
+        let table: BTreeMap<&str, TalkerEntry> = talkers
+            .iter()
+            .map(|talker| {
+                let entry = TalkerEntry {
+                    talker,
+                    is_name_excluded,
+                };
+                (talker.id(), entry)
+            })
+            .collect();
+        Self::to_string(&table, is_minify)
+    }
+
+    fn to_string<T: Serialize>(value: &T, is_minify: bool) -> String {
         let s = if is_minify {
-            serde_json::to_string(scenes)
+            serde_json::to_string(value)
         } else {
-            serde_json::to_string_pretty(scenes)
+            serde_json::to_string_pretty(value)
         };
 
         s.unwrap_or_else(|e| {
@@ -0,0 +1,357 @@
+use crate::core::command::{CommandArg, InlineCommand};
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
+use crate::core::dialog::{Dialog, DialogBody, DialogKind, Scene};
+use crate::core::kukuri_data::KukuriData;
+use crate::core::markup::Dialect;
+use crate::core::namespace;
+use crate::import::kukuri_script::KukuriScript;
+
+const INDENT: &str = "  ";
+
+// Decompile scenes into KukuriScript source.
+//
+// dialog ids of KukuriScript are generated from positions,
+// so the output is parsed again to report ids which are not kept.
+pub struct Kkr;
+
+impl Kkr {
+    pub fn export_string(scenes: &[Scene]) -> (String, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let scene_strs: Vec<String> = scenes
+            .iter()
+            .map(|sc| Self::convert_scene(sc, &mut diagnostics))
+            .collect();
+        let out_s = scene_strs.join("\n===\n\n");
+
+        let (data, _) = KukuriScript::parse_with_diagnostics(&out_s);
+        if let KukuriData::Scenes(parsed) = data {
+            Self::compare_ids(scenes, &parsed, &mut diagnostics);
+        }
+
+        (out_s, diagnostics)
+    }
+
+    fn convert_scene(sc: &Scene, diagnostics: &mut Vec<Diagnostic>) -> String {
+        let mut out_s = String::from("+++\n");
+        // "chapter1.Opening" => namespace = "chapter1", title = "Opening"
+        let ns = namespace::namespace_of(&sc.title);
+        let title = sc
+            .title
+            .strip_prefix(&format!("{}.", ns))
+            .unwrap_or(&sc.title);
+        out_s.push_str(&format!("title = {}\n", toml::Value::from(title)));
+        if !ns.is_empty() {
+            out_s.push_str(&format!("namespace = {}\n", toml::Value::from(ns)));
+        }
+        if !sc.meta.is_empty() {
+            match toml::Value::try_from(&sc.meta).and_then(|v| toml::to_string(&v)) {
+                Ok(s) => out_s.push_str(&s),
+                Err(e) => diagnostics.push(
+                    Diagnostic::warning(0, format!("metadata of {} is dropped: {}", sc.title, e))
+                        .at(&sc.loc),
+                ),
+            }
+        }
+        out_s.push_str("+++\n\n");
+
+        for dialog in &sc.dialogs {
+            Self::convert_dialog(dialog, 0, &mut out_s, diagnostics);
+        }
+        out_s
+    }
+
+    fn convert_dialog(
+        dialog: &Dialog,
+        nest_lv: usize,
+        out_s: &mut String,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let indent = INDENT.repeat(nest_lv);
+        match dialog.kind {
+            DialogKind::Dialog => {
+                let rt = dialog.args.first().and_then(|b| b.rich_text());
+                let inserts: Vec<(usize, String)> = dialog
+                    .inline_commands
+                    .iter()
+                    .map(|ic| (ic.offset, Self::inline_command(ic)))
+                    .collect();
+                let text = rt.map_or(String::new(), |rt| {
+                    Self::escape_comment(&rt.render_with_inserts(Dialect::Kukuri, &inserts))
+                });
+                if text.is_empty() {
+                    diagnostics.push(
+                        Diagnostic::warning(0, format!("dialog {} has no text", dialog.id))
+                            .at(&dialog.loc),
+                    );
+                }
+
+                let talker = match dialog.args.get(1) {
+                    Some(DialogBody::Text(s)) => s.trim_start_matches("TALKER_"),
+                    _ => "unknown",
+                };
+                if talker == "unknown" && !Self::needs_talker(&text) {
+                    out_s.push_str(&format!("{}{}\n", indent, text));
+                } else {
+                    out_s.push_str(&format!("{}{}: {}\n", indent, talker, text));
+                }
+            }
+            DialogKind::Command => {
+                let args: Vec<&CommandArg> = dialog
+                    .args
+                    .iter()
+                    .filter_map(|b| match b {
+                        DialogBody::Arg(arg) => Some(arg),
+                        _ => None,
+                    })
+                    .collect();
+                out_s.push_str(&format!(
+                    "{}$ {}\n",
+                    indent,
+                    Self::command(&dialog.id, &args)
+                ));
+            }
+            DialogKind::Choices => {
+                for arg in &dialog.args {
+                    if let DialogBody::Choice(cd) = arg {
+                        let mut label = String::new();
+                        cd.label
+                            .chars()
+                            .for_each(|c| Self::escape_char(c, &mut label));
+                        out_s.push_str(&format!("{}* {}\n", indent, label));
+                        for d in &cd.dialogs {
+                            Self::convert_dialog(d, nest_lv + 1, out_s, diagnostics);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // a text without talker must not be read as talker, command, choice or macro
+    fn needs_talker(text: &str) -> bool {
+        text.contains(':')
+            || text.starts_with(['$', '*', '+', '-', '=', '@'])
+            || text.starts_with(char::is_whitespace)
+    }
+
+    fn inline_command(ic: &InlineCommand) -> String {
+        let args: Vec<&CommandArg> = ic.args.iter().collect();
+        format!("[{}]", Self::command(&ic.id, &args))
+    }
+
+    // "id arg1 arg2 key=value"
+    fn command(id: &str, args: &[&CommandArg]) -> String {
+        let mut s = String::from(id);
+        for arg in args {
+            s.push(' ');
+            s.push_str(&Self::arg(arg));
+        }
+        s
+    }
+
+    fn arg(arg: &CommandArg) -> String {
+        match arg {
+            CommandArg::Ident(s) => s.clone(),
+            // strings are always quoted, so that they are never read as other types
+            CommandArg::Str(s) => {
+                let mut quoted = String::from("\"");
+                s.chars().for_each(|c| Self::escape_char(c, &mut quoted));
+                quoted.push('"');
+                quoted
+            }
+            CommandArg::Int(n) => n.to_string(),
+            // debug format keeps ".0" of float
+            CommandArg::Float(f) => format!("{:?}", f.0),
+            CommandArg::Bool(b) => b.to_string(),
+            CommandArg::Named(k, v) => format!("{}={}", k, Self::arg(v)),
+        }
+    }
+
+    // "#" in tag values e.g. "<color=#f00>" starts a comment in scripts,
+    // other "#" are already escaped by the dialect
+    fn escape_comment(text: &str) -> String {
+        let mut out_s = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    out_s.push(c);
+                    out_s.extend(chars.next());
+                }
+                '#' => out_s.push_str("\\#"),
+                _ => out_s.push(c),
+            }
+        }
+        out_s
+    }
+
+    // escape for choice labels and quoted arguments
+    fn escape_char(c: char, out_s: &mut String) {
+        match c {
+            '\\' | '"' | '#' => {
+                out_s.push('\\');
+                out_s.push(c);
+            }
+            '\n' => out_s.push_str("\\n"),
+            '\t' => out_s.push_str("\\t"),
+            '\r' => out_s.push_str("\\r"),
+            _ => out_s.push(c),
+        }
+    }
+
+    // ids of dialogs, choices and options in walk order
+    fn ids(scenes: &[Scene]) -> Vec<(String, SourceLoc)> {
+        let mut ids = Vec::new();
+        for sc in scenes {
+            sc.walk_dialogs(|d| {
+                if d.kind != DialogKind::Command {
+                    ids.push((d.id.clone(), d.loc.clone()));
+                }
+                for arg in &d.args {
+                    if let DialogBody::Choice(cd) = arg {
+                        ids.push((cd.id.clone(), cd.loc.clone()));
+                    }
+                }
+            });
+        }
+        ids
+    }
+
+    fn compare_ids(scenes: &[Scene], parsed: &[Scene], diagnostics: &mut Vec<Diagnostic>) {
+        let (ids, parsed_ids) = (Self::ids(scenes), Self::ids(parsed));
+        for ((id, loc), (parsed_id, _)) in ids.iter().zip(parsed_ids.iter()) {
+            if id != parsed_id {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        0,
+                        format!("id {} becomes {} in KukuriScript", id, parsed_id),
+                    )
+                    .at(loc),
+                );
+            }
+        }
+        if ids.len() != parsed_ids.len() {
+            diagnostics.push(Diagnostic::warning(
+                0,
+                format!(
+                    "{} ids are decompiled into {} ids, empty or adjacent choices can not be kept",
+                    ids.len(),
+                    parsed_ids.len()
+                ),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kkr;
+    use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+    use crate::core::kukuri_data::KukuriData;
+    use crate::import::kukuri_script::KukuriScript;
+
+    fn parse_scenes(src: &str) -> Vec<Scene> {
+        let (data, diagnostics) = KukuriScript::parse_with_diagnostics(src);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        match data.without_locs() {
+            KukuriData::Scenes(scenes) => scenes,
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_export_string() {
+        let src = r#"
++++
+title = "Opening"
+namespace = "ch1"
+tags = ["intro"]
++++
+
+$ bgm "bgm/daily.ogg" 0.5 1.0 loop=true
+A: Hi, <b>you</b>[wait 2]. \# not a comment \{x\}
+plain text
+time\: 10\:00
+* Go \#1
+  B: {漢字|かんじ}
+  * Nested
+    $ jump Shop
+* Stay
+
+===
+
++++
+title = "Shop"
++++
+
+A: <color=f00>red</color> line\nbreak
+"#;
+        let scenes = parse_scenes(src);
+        let (out_s, diagnostics) = Kkr::export_string(&scenes);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(scenes, parse_scenes(&out_s));
+
+        let expected = r#"+++
+title = "Opening"
+namespace = "ch1"
+tags = ["intro"]
++++
+
+$ bgm "bgm/daily.ogg" 0.5 1.0 loop=true
+A: Hi, <b>you</b>[wait 2]. \# not a comment \{x}
+plain text
+unknown: time: 10:00
+* Go \#1
+  B: {漢字|かんじ}
+  * Nested
+    $ jump Shop
+* Stay
+
+===
+
++++
+title = "Shop"
++++
+
+A: <color=\#f00>red</color> line\nbreak
+"#;
+        assert_eq!(expected, out_s);
+    }
+
+    #[test]
+    fn test_export_string_ids() {
+        // ids given by other importers, and adjacent choices
+        let choices = |id: &str| {
+            Dialog::from_dialog_data(
+                DialogKind::Choices,
+                id,
+                vec![DialogBody::Choice(ChoiceData::from_texts(
+                    format!("{}L1", id),
+                    "Go",
+                ))],
+            )
+        };
+        let mut sc = Scene::new();
+        sc.title = String::from("Start");
+        sc.dialogs = vec![
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "line:abc123",
+                vec![DialogBody::gen_text("Hi"), DialogBody::gen_text("TALKER_A")],
+            ),
+            choices("Start_2_C1"),
+            choices("Start_3_C1"),
+        ];
+
+        let (_, diagnostics) = Kkr::export_string(&[sc]);
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            vec![
+                "0: warning: id line:abc123 becomes Start_1_A in KukuriScript",
+                "0: warning: id Start_3_C1 becomes Start_2_C1L2 in KukuriScript",
+                "0: warning: 5 ids are decompiled into 4 ids, empty or adjacent choices can not be kept",
+            ],
+            messages
+        );
+    }
+}
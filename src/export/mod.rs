@@ -1,6 +1,11 @@
+pub mod bindings;
 pub mod gd;
+pub mod glyphs;
 pub mod json;
+pub mod kkr;
 pub mod po;
+pub mod tmp;
+pub mod txt;
 
 const DEFAULT_EXPORT_TYPE: ExportType = ExportType::GDScript;
 const DEFAULT_L10N_EXPORT_TYPE: L10nExportType = L10nExportType::Po;
@@ -9,6 +14,8 @@ const DEFAULT_L10N_EXPORT_TYPE: L10nExportType = L10nExportType::Po;
 pub enum ExportType {
     GDScript,
     Json,
+    TextMeshPro,
+    Txt,
 }
 
 impl ExportType {
@@ -16,6 +23,8 @@ impl ExportType {
         match s {
             "gd" => ExportType::GDScript,
             "json" => ExportType::Json,
+            "tmp" => ExportType::TextMeshPro,
+            "txt" => ExportType::Txt,
             _ => DEFAULT_EXPORT_TYPE,
         }
     }
@@ -24,6 +33,8 @@ impl ExportType {
         match self {
             ExportType::GDScript => "gd",
             ExportType::Json => "json",
+            ExportType::TextMeshPro => "tmp.json",
+            ExportType::Txt => "txt",
         }
     }
 }
@@ -49,3 +60,29 @@ impl L10nExportType {
         }
     }
 }
+
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum BindingExportType {
+    GDScript,
+    CSharp,
+    TypeScript,
+}
+
+impl BindingExportType {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "gd" => Some(BindingExportType::GDScript),
+            "cs" => Some(BindingExportType::CSharp),
+            "ts" => Some(BindingExportType::TypeScript),
+            _ => None,
+        }
+    }
+
+    pub fn file_name(&self) -> &str {
+        match self {
+            BindingExportType::GDScript => "kukuri_commands.gd",
+            BindingExportType::CSharp => "KukuriCommands.cs",
+            BindingExportType::TypeScript => "kukuri_commands.ts",
+        }
+    }
+}
@@ -1,12 +1,21 @@
+use crate::core::command::InlineCommand;
 use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+use crate::core::markup::{Dialect, RichText};
 use crate::core::talker::Talker;
 
-const DEFAULT_PO_TEMPLATE: &'static str = include_str!("../templates/po");
+const DEFAULT_PO_TEMPLATE: &str = include_str!("../templates/po");
+// private use char, which never appears in rendered texts
+const SENTINEL: char = '\u{f8ff}';
 
 pub struct Po;
 
 impl Po {
-    pub fn export_string(scenes: &Vec<Scene>, talkers: &Vec<Talker>, locale: &str) -> String {
+    pub fn export_string(
+        scenes: &Vec<Scene>,
+        talkers: &Vec<Talker>,
+        locale: &str,
+        dialect: Dialect,
+    ) -> String {
         // output string
         let mut out_s = Self::gen_init_string(locale);
 
@@ -15,23 +24,23 @@ impl Po {
         }
 
         for scene in scenes {
-            out_s.push_str(&Self::convert_scene(scene));
+            out_s.push_str(&Self::convert_scene(scene, dialect));
         }
 
         out_s
     }
 
-    fn convert_scene(scene: &Scene) -> String {
+    fn convert_scene(scene: &Scene, dialect: Dialect) -> String {
         let mut out_s = String::new();
 
         for dialog in &scene.dialogs {
-            out_s.push_str(&Self::convert_dialog(dialog));
+            out_s.push_str(&Self::convert_dialog(dialog, dialect));
         }
 
         out_s
     }
 
-    fn convert_dialog(dialog: &Dialog) -> String {
+    fn convert_dialog(dialog: &Dialog, dialect: Dialect) -> String {
         if dialog.kind == DialogKind::Command {
             return String::new();
         };
@@ -40,15 +49,19 @@ impl Po {
 
         match dialog.kind {
             DialogKind::Dialog => {
-                if let DialogBody::Text(dialog_body) = &dialog.args[0] {
+                if let Some(rt) = dialog.args[0].rich_text() {
                     out_s.push_str(&Self::gen_msgid(&dialog.id));
-                    out_s.push_str(&Self::gen_msgstr(dialog_body));
+                    out_s.push_str(&Self::gen_msgstr(Self::render_text(
+                        &rt,
+                        &dialog.inline_commands,
+                        dialect,
+                    )));
                 };
             }
             DialogKind::Choices => {
                 for choice in &dialog.args {
                     if let DialogBody::Choice(cd) = choice {
-                        out_s.push_str(&Self::convert_choice(cd));
+                        out_s.push_str(&Self::convert_choice(cd, dialect));
                     }
                 }
             }
@@ -58,12 +71,12 @@ impl Po {
         out_s
     }
 
-    fn convert_choice(cd: &ChoiceData) -> String {
+    fn convert_choice(cd: &ChoiceData, dialect: Dialect) -> String {
         let mut out_s = String::new();
         out_s.push_str(&Self::gen_msgid(&cd.id));
-        out_s.push_str(&Self::gen_msgstr(&cd.label));
+        out_s.push_str(&Self::gen_msgstr(Self::escape_braces(&cd.label)));
         for dialog in &cd.dialogs {
-            out_s.push_str(&Self::convert_dialog(dialog));
+            out_s.push_str(&Self::convert_dialog(dialog, dialect));
         }
 
         out_s
@@ -71,7 +84,7 @@ impl Po {
 
     fn convert_talker(talker: &Talker) -> String {
         let mut out_s = Self::gen_msgid(talker.id());
-        out_s.push_str(&Self::gen_msgstr(talker.name()));
+        out_s.push_str(&Self::gen_msgstr(Self::escape_braces(talker.name())));
         out_s
     }
 
@@ -82,23 +95,70 @@ impl Po {
     }
 
     fn gen_msgid<T: AsRef<str>>(s: T) -> String {
-        format!("msgid \"{}\"\n", Self::quote_escape(s.as_ref()))
+        format!("msgid \"{}\"\n", Self::po_escape(s.as_ref()))
     }
 
     fn gen_msgstr<T: AsRef<str>>(s: T) -> String {
         // add double line break
-        format!("msgstr \"{}\"\n\n\n", Self::quote_escape(s.as_ref()))
+        format!("msgstr \"{}\"\n\n\n", Self::po_escape(s.as_ref()))
     }
 
-    fn quote_escape(s: &str) -> String {
-        s.replace('"', "\\\"")
+    // render markup in l10n dialect,
+    // and replace inline commands with protected placeholders "{0}", "{1}"...
+    // placeholder number is the index of dialog.inline_commands
+    // literal braces are doubled "{{", "}}" so that they are never read as placeholders
+    fn render_text(rt: &RichText, inline_commands: &[InlineCommand], dialect: Dialect) -> String {
+        // placeholders are marked with sentinel chars while rendering
+        let inserts: Vec<(usize, String)> = inline_commands
+            .iter()
+            .enumerate()
+            .map(|(n, ic)| (ic.offset, format!("{}{}{}", SENTINEL, n, SENTINEL)))
+            .collect();
+        let rendered = rt.render_with_inserts(dialect, &inserts);
+
+        // odd parts are placeholder numbers
+        rendered
+            .split(SENTINEL)
+            .enumerate()
+            .map(|(i, part)| {
+                if i % 2 == 1 {
+                    format!("{{{}}}", part)
+                } else {
+                    Self::escape_braces(part)
+                }
+            })
+            .collect()
+    }
+
+    fn escape_braces(s: &str) -> String {
+        s.replace('{', "{{").replace('}', "}}")
+    }
+
+    // escape decoded text into PO (C-like) string literal
+    fn po_escape(s: &str) -> String {
+        let mut out_s = String::with_capacity(s.len());
+        for c in s.chars() {
+            match c {
+                '\\' => out_s.push_str("\\\\"),
+                '"' => out_s.push_str("\\\""),
+                '\n' => out_s.push_str("\\n"),
+                '\t' => out_s.push_str("\\t"),
+                '\r' => out_s.push_str("\\r"),
+                // page break
+                '\u{c}' => out_s.push_str("\\f"),
+                _ => out_s.push(c),
+            }
+        }
+        out_s
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Po;
+    use crate::core::command::InlineCommand;
     use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind};
+    use crate::core::markup::{Dialect, RichText, Span, Style};
     use crate::core::talker::Talker;
 
     #[test]
@@ -131,7 +191,7 @@ msgstr \"てててすと\"
 
 
 ";
-        assert_eq!(expected0, Po::convert_dialog(&d0));
+        assert_eq!(expected0, Po::convert_dialog(&d0, Dialect::BBCode));
 
         let mut cd0 = ChoiceData::from_texts("TestChoice0", "てすと選択肢0");
         cd0.dialogs.push(Dialog::from_dialog_data(
@@ -163,7 +223,62 @@ msgstr \"てすと選択肢1\"
 
 ";
 
-        assert_eq!(expected1, Po::convert_dialog(&d1));
+        assert_eq!(expected1, Po::convert_dialog(&d1, Dialect::BBCode));
+    }
+
+    #[test]
+    fn test_render_text() {
+        let cmds = vec![
+            InlineCommand::from_command_data(0, "se", Vec::new()),
+            InlineCommand::from_command_data(4, "wait", Vec::new()),
+            InlineCommand::from_command_data(4, "speed", Vec::new()),
+            InlineCommand::from_command_data(13, "clear", Vec::new()),
+        ];
+        let rt = RichText {
+            spans: vec![
+                Span::Styled(Style::Bold, vec![Span::Text(String::from("Wait"))]),
+                Span::Text(String::from("... what?")),
+            ],
+        };
+        let tests = [
+            (Dialect::BBCode, "{0}[b]Wait[/b]{1}{2}... what?{3}"),
+            (Dialect::Kukuri, "{0}<b>Wait</b>{1}{2}... what?{3}"),
+            (Dialect::Plain, "{0}Wait{1}{2}... what?{3}"),
+        ];
+
+        for (dialect, expected) in &tests {
+            assert_eq!(*expected, Po::render_text(&rt, &cmds, *dialect));
+        }
+        assert_eq!(
+            "no command",
+            Po::render_text(&RichText::from_plain("no command"), &[], Dialect::BBCode)
+        );
+
+        // literal "{0}" is not a placeholder
+        let cmds = vec![InlineCommand::from_command_data(3, "wait", Vec::new())];
+        assert_eq!(
+            "{{0}}{0} {{漢字|かんじ}}",
+            Po::render_text(
+                &RichText::from_plain("{0} {漢字|かんじ}"),
+                &cmds,
+                Dialect::BBCode
+            )
+        );
+    }
+
+    #[test]
+    fn test_po_escape() {
+        let tests = [
+            ("no escape", "no escape"),
+            ("\"quoted\"", "\\\"quoted\\\""),
+            ("line\nbreak\ttab", "line\\nbreak\\ttab"),
+            ("back\\slash", "back\\\\slash"),
+            ("page\u{c}break", "page\\fbreak"),
+        ];
+
+        for &(src, expected) in &tests {
+            assert_eq!(expected, Po::po_escape(src));
+        }
     }
 
     #[test]
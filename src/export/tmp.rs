@@ -0,0 +1,13 @@
+use super::json::Json;
+use crate::core::dialog::{self, Scenes};
+use crate::core::markup::Dialect;
+
+// Json with TextMeshPro rich text tags for Unity
+pub struct TextMeshPro;
+
+impl TextMeshPro {
+    pub fn export_string(scenes: &Scenes, is_minify: bool) -> String {
+        let scenes = dialog::render_scenes(scenes, Dialect::TextMeshPro);
+        Json::export_string(&scenes, is_minify)
+    }
+}
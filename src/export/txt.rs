@@ -0,0 +1,127 @@
+use crate::core::dialog::{Dialog, DialogBody, DialogKind, Scenes};
+use crate::core::markup::Dialect;
+
+const INDENT: &str = "    ";
+
+// Plain text transcript for logs and proofreading
+pub struct Txt;
+
+impl Txt {
+    pub fn export_string(scenes: &Scenes) -> String {
+        let mut titles: Vec<&String> = scenes.keys().collect();
+        titles.sort();
+
+        let mut out_s = String::new();
+        for title in titles {
+            out_s.push_str(&format!("== {} ==\n", title));
+            for dialog in &scenes[title] {
+                Self::convert_dialog(dialog, 0, &mut out_s);
+            }
+            out_s.push('\n');
+        }
+
+        out_s
+    }
+
+    fn convert_dialog(dialog: &Dialog, nest_lv: usize, out_s: &mut String) {
+        let indent = INDENT.repeat(nest_lv);
+        match dialog.kind {
+            DialogKind::Dialog => {
+                let text = match dialog.args.first().and_then(|b| b.rich_text()) {
+                    Some(rt) => rt.render(Dialect::Plain),
+                    None => String::new(),
+                };
+                let talker = match dialog.args.get(1) {
+                    Some(DialogBody::Text(s)) => s.trim_start_matches("TALKER_"),
+                    _ => "unknown",
+                };
+                // continuation lines are aligned with the first line
+                let text = text.replace('\n', &format!("\n{}{}", indent, INDENT));
+                out_s.push_str(&format!("{}[{}] {}: {}\n", indent, dialog.id, talker, text));
+            }
+            DialogKind::Command => {
+                let mut line = format!("{}$ {}", indent, dialog.id);
+                for arg in &dialog.args {
+                    if let Ok(s) = serde_json::to_string(arg) {
+                        line.push(' ');
+                        line.push_str(&s);
+                    }
+                }
+                out_s.push_str(&line);
+                out_s.push('\n');
+            }
+            DialogKind::Choices => {
+                for arg in &dialog.args {
+                    if let DialogBody::Choice(cd) = arg {
+                        out_s.push_str(&format!("{}* [{}] {}\n", indent, cd.id, cd.label));
+                        for d in &cd.dialogs {
+                            Self::convert_dialog(d, nest_lv + 1, out_s);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Txt;
+    use crate::core::command::{CommandArg, Float};
+    use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind};
+    use crate::core::markup::{RichText, Span, Style};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_export_string() {
+        let mut cd = ChoiceData::from_texts("Test_2_C1L1", "Yes");
+        cd.dialogs.push(Dialog::from_dialog_data(
+            DialogKind::Dialog,
+            "Test_2_C1L1_1_B",
+            vec![
+                DialogBody::gen_text("Line\nbreak"),
+                DialogBody::gen_text("TALKER_B"),
+            ],
+        ));
+
+        let dialogs = vec![
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "Test_1_A",
+                vec![
+                    DialogBody::Rich(RichText {
+                        spans: vec![
+                            Span::Text(String::from("Hello ")),
+                            Span::Styled(Style::Bold, vec![Span::Text(String::from("world"))]),
+                        ],
+                    }),
+                    DialogBody::gen_text("TALKER_A"),
+                ],
+            ),
+            Dialog::from_dialog_data(
+                DialogKind::Command,
+                "wait",
+                vec![DialogBody::Arg(CommandArg::Float(Float(0.5)))],
+            ),
+            Dialog::from_dialog_data(
+                DialogKind::Choices,
+                "Test_2_C1",
+                vec![DialogBody::Choice(cd)],
+            ),
+        ];
+
+        let mut scenes = HashMap::new();
+        scenes.insert(String::from("Test"), dialogs);
+
+        let expected = "\
+== Test ==
+[Test_1_A] A: Hello world
+$ wait 0.5
+* [Test_2_C1L1] Yes
+    [Test_2_C1L1_1_B] B: Line
+        break
+
+";
+        assert_eq!(expected, Txt::export_string(&scenes));
+    }
+}
@@ -0,0 +1,278 @@
+use super::escape;
+use crate::core::command::{CommandArg, Float};
+use crate::core::diagnostic::Diagnostic;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// Parse command arguments string.
+//
+// "quoted string" or 'quoted string' => Str (escape sequences decoded)
+// 10, -3                             => Int
+// 0.5, -1e3                          => Float
+// true, false                        => Bool
+// identifier e.g. DialogTest2        => Ident
+// key=value                          => Named
+// other bare word e.g. bgm/a.ogg     => Str
+pub fn parse_args(s: &str, line: usize, diagnostics: &mut Vec<Diagnostic>) -> Vec<CommandArg> {
+    let mut args = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    loop {
+        skip_whitespace(&mut chars);
+        let c = match chars.peek() {
+            Some(&c) => c,
+            None => break,
+        };
+
+        if c == '"' || c == '\'' {
+            args.push(parse_quoted(&mut chars, line, diagnostics));
+            continue;
+        }
+
+        let word = read_word(&mut chars);
+        if chars.peek() == Some(&'=') && is_identifier(&word) {
+            chars.next();
+            let value = match chars.peek() {
+                Some(&c) if c == '"' || c == '\'' => parse_quoted(&mut chars, line, diagnostics),
+                Some(&c) if !c.is_whitespace() => {
+                    parse_bare_word(&read_word(&mut chars), line, diagnostics)
+                }
+                _ => {
+                    diagnostics.push(Diagnostic::warning(
+                        line,
+                        format!("named argument \"{}\" has no value", word),
+                    ));
+                    CommandArg::Str(String::new())
+                }
+            };
+            args.push(CommandArg::Named(word, Box::new(value)));
+            continue;
+        }
+
+        if chars.peek() == Some(&'=') {
+            // "=" without a valid name is a part of bare word
+            chars.next();
+            let rest = read_word(&mut chars);
+            let word = format!("{}={}", word, rest);
+            args.push(parse_bare_word(&word, line, diagnostics));
+            continue;
+        }
+
+        args.push(parse_bare_word(&word, line, diagnostics));
+    }
+
+    args
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while let Some(&c) = chars.peek() {
+        if !c.is_whitespace() {
+            break;
+        }
+        chars.next();
+    }
+}
+
+// read until whitespace, or "=" of named argument
+fn read_word(chars: &mut Peekable<Chars>) -> String {
+    let mut word = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == '=' {
+            break;
+        }
+        chars.next();
+        word.push(c);
+        if c == '\\' {
+            // keep escaped char in word
+            if let Some(c) = chars.next() {
+                word.push(c);
+            }
+        }
+    }
+    word
+}
+
+fn parse_quoted(
+    chars: &mut Peekable<Chars>,
+    line: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> CommandArg {
+    let quote = chars.next().unwrap_or('"');
+    let mut raw = String::new();
+    let mut is_closed = false;
+
+    while let Some(c) = chars.next() {
+        if c == quote {
+            is_closed = true;
+            break;
+        }
+        raw.push(c);
+        if c == '\\' {
+            if let Some(c) = chars.next() {
+                raw.push(c);
+            }
+        }
+    }
+
+    if !is_closed {
+        diagnostics.push(Diagnostic::warning(
+            line,
+            format!("unterminated quoted argument {}{}", quote, raw),
+        ));
+    }
+
+    CommandArg::Str(escape::unescape(&raw, line, diagnostics))
+}
+
+fn parse_bare_word(word: &str, line: usize, diagnostics: &mut Vec<Diagnostic>) -> CommandArg {
+    match word {
+        "true" => return CommandArg::Bool(true),
+        "false" => return CommandArg::Bool(false),
+        _ => {}
+    }
+
+    if let Ok(n) = word.parse::<i64>() {
+        return CommandArg::Int(n);
+    }
+
+    // "inf", "NaN" or "1e999" are not finite, and warned as a typo
+    if let Ok(f) = word.parse::<f64>() {
+        if f.is_finite() {
+            return CommandArg::Float(Float(f));
+        }
+        diagnostics.push(Diagnostic::warning(
+            line,
+            format!("\"{}\" is not a finite number, read as a text", word),
+        ));
+    }
+
+    if is_identifier(word) {
+        return CommandArg::Ident(String::from(word));
+    }
+
+    CommandArg::Str(escape::unescape(word, line, diagnostics))
+}
+
+// identifier: [alphabetic or "_"][alphanumeric, "_" or "."]*
+pub fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_identifier, parse_args};
+    use crate::core::command::{CommandArg, Float};
+    use crate::core::diagnostic::Diagnostic;
+
+    fn named(k: &str, v: CommandArg) -> CommandArg {
+        CommandArg::Named(String::from(k), Box::new(v))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let tests = [
+            ("", Vec::new()),
+            (
+                r#""Hello world" 'single quoted'"#,
+                vec![
+                    CommandArg::gen_str("Hello world"),
+                    CommandArg::gen_str("single quoted"),
+                ],
+            ),
+            (
+                "0 -3 0.5 -1e3 true false",
+                vec![
+                    CommandArg::Int(0),
+                    CommandArg::Int(-3),
+                    CommandArg::Float(Float(0.5)),
+                    CommandArg::Float(Float(-1000.0)),
+                    CommandArg::Bool(true),
+                    CommandArg::Bool(false),
+                ],
+            ),
+            (
+                "DialogTest2 chapter1.intro bgm/track01.ogg",
+                vec![
+                    CommandArg::gen_ident("DialogTest2"),
+                    CommandArg::gen_ident("chapter1.intro"),
+                    CommandArg::gen_str("bgm/track01.ogg"),
+                ],
+            ),
+            (
+                r#"volume=0.8 fade=true text="a b" target=Opening"#,
+                vec![
+                    named("volume", CommandArg::Float(Float(0.8))),
+                    named("fade", CommandArg::Bool(true)),
+                    named("text", CommandArg::gen_str("a b")),
+                    named("target", CommandArg::gen_ident("Opening")),
+                ],
+            ),
+            (
+                r#""escaped \"quote\"\n" a\#b 1=2"#,
+                vec![
+                    CommandArg::gen_str("escaped \"quote\"\n"),
+                    CommandArg::gen_str("a#b"),
+                    CommandArg::gen_str("1=2"),
+                ],
+            ),
+        ];
+
+        for (src, expected) in &tests {
+            let mut diagnostics = Vec::new();
+            assert_eq!(*expected, parse_args(src, 1, &mut diagnostics));
+            assert!(diagnostics.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parse_args_diagnostics() {
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            vec![
+                named("key", CommandArg::gen_str("")),
+                CommandArg::gen_str("open")
+            ],
+            parse_args(r#"key= "open"#, 4, &mut diagnostics)
+        );
+        assert_eq!(
+            vec![
+                CommandArg::gen_str("-inf"),
+                CommandArg::gen_str("1e999"),
+                CommandArg::gen_ident("NaN")
+            ],
+            parse_args("-inf 1e999 NaN", 5, &mut diagnostics)
+        );
+        assert_eq!(
+            vec![
+                Diagnostic::warning(4, "named argument \"key\" has no value"),
+                Diagnostic::warning(4, "unterminated quoted argument \"open"),
+                Diagnostic::warning(5, "\"-inf\" is not a finite number, read as a text"),
+                Diagnostic::warning(5, "\"1e999\" is not a finite number, read as a text"),
+                Diagnostic::warning(5, "\"NaN\" is not a finite number, read as a text"),
+            ],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_is_identifier() {
+        let tests = [
+            ("jump", true),
+            ("_private", true),
+            ("chapter1.intro", true),
+            ("選択肢", true),
+            ("1st", false),
+            ("a/b", false),
+            ("", false),
+        ];
+
+        for &(src, expected) in &tests {
+            assert_eq!(expected, is_identifier(src));
+        }
+    }
+}
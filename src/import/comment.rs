@@ -0,0 +1,84 @@
+// Cut a line comment starting at marker, e.g. "text # comment" => "text ".
+// marker after "\" or in quotes is not a comment symbol.
+pub fn trim_line<'a>(line: &'a str, marker: char, quotes: &[char]) -> &'a str {
+    let mut quote = None;
+    let mut is_escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if is_escaped => is_escaped = false,
+            '\\' => is_escaped = true,
+            _ if quote.is_none() && quotes.contains(&c) => quote = Some(c),
+            _ if Some(c) == quote => quote = None,
+            _ if c == marker && quote.is_none() => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// Remove comments of (open, close) pairs, line breaks in them are kept.
+// an unclosed comment runs to the end of content,
+// and ("//", "\n") is a comment to the end of line.
+pub fn strip(content: &str, comments: &[(&str, &str)]) -> String {
+    let mut out_s = String::new();
+    let mut rest = content;
+
+    loop {
+        let start = comments
+            .iter()
+            .filter_map(|&(open, close)| rest.find(open).map(|i| (i, open, close)))
+            .min();
+        let (start, open, close) = match start {
+            Some(x) => x,
+            None => break,
+        };
+        out_s.push_str(&rest[..start]);
+
+        let end = rest[start + open.len()..]
+            .find(close)
+            .map_or(rest.len(), |i| start + open.len() + i + close.len());
+        out_s.extend(rest[start..end].chars().filter(|&c| c == '\n'));
+        rest = &rest[end..];
+    }
+
+    out_s.push_str(rest);
+    out_s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip, trim_line};
+
+    #[test]
+    fn test_trim_line() {
+        let tests = [
+            ("# head of line comment", ""),
+            ("end of line comment# comment", "end of line comment"),
+            ("middle of # line comment", "middle of "),
+            ("A: comment nothing", "A: comment nothing"),
+            ("comment nothing", "comment nothing"),
+            ("# $ cmd comment test", ""),
+            ("$ cmd comment # test", "$ cmd comment "),
+            ("escaped \\# comment", "escaped \\# comment"),
+            ("escaped backslash \\\\# comment", "escaped backslash \\\\"),
+        ];
+
+        for &(src, expected) in &tests {
+            assert_eq!(expected, trim_line(src, '#', &[]));
+        }
+
+        let quoted = "e \"# in \\\" string\" # comment";
+        assert_eq!(
+            "e \"# in \\\" string\" ",
+            trim_line(quoted, '#', &['"', '\''])
+        );
+        assert_eq!("e \"", trim_line(quoted, '#', &[]));
+    }
+
+    #[test]
+    fn test_strip() {
+        let comments = [("//", "\n"), ("/*", "*/")];
+        let src = "a // b /* c\nd /* e\nf */ g // h */\n/* unclosed\ni";
+        assert_eq!("a \nd \n g \n\n", strip(src, &comments));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::core::diagnostic::Diagnostic;
+use crate::core::markup::{RichTextBuilder, Style};
+
+// Markdown-like emphasis markers, e.g. "**bold**", "*italic*"
+pub struct Emphasis {
+    // (marker, styles), longest marker first
+    pub markers: &'static [(&'static str, &'static [Style])],
+    // "_" in words e.g. "snake_case" is not a marker
+    pub is_intraword_underscore_literal: bool,
+}
+
+impl Emphasis {
+    // emphasis into styled spans, "\*" is a literal "*".
+    // markers unclosed at the end of text are closed with a warning.
+    pub fn process(
+        &self,
+        text: &str,
+        builder: &mut RichTextBuilder,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let mut opened: Vec<&str> = Vec::new();
+        let mut rest = text;
+        let mut prev = ' ';
+
+        while let Some(c) = rest.chars().next() {
+            if c == '\\' {
+                let escaped = rest[1..].chars().next();
+                let len = escaped.map_or(0, char::len_utf8);
+                builder.push_str(&rest[1..1 + len]);
+                prev = escaped.unwrap_or(c);
+                rest = &rest[1 + len..];
+                continue;
+            }
+
+            let marker = self
+                .markers
+                .iter()
+                .map(|&(m, _)| m)
+                .find(|m| rest.starts_with(m));
+            let after = marker.map_or("", |m| &rest[m.len()..]);
+            let is_intraword = marker.is_some_and(|m| {
+                let next = after.chars().next().unwrap_or(' ');
+                self.is_intraword_underscore_literal
+                    && m.starts_with('_')
+                    && if opened.contains(&m) {
+                        next.is_alphanumeric()
+                    } else {
+                        prev.is_alphanumeric()
+                    }
+            });
+
+            match marker {
+                Some(m) if !is_intraword => {
+                    if let Some(pos) = opened.iter().rposition(|&o| o == m) {
+                        for o in opened.drain(pos..).rev() {
+                            self.close(o, builder);
+                        }
+                    } else if after.contains(m) {
+                        self.styles(m).iter().for_each(|s| builder.open(s.clone()));
+                        opened.push(m);
+                    } else {
+                        builder.push_str(m);
+                    }
+                    prev = m.chars().last().unwrap_or(c);
+                    rest = after;
+                }
+                _ => {
+                    builder.push_str(&rest[..c.len_utf8()]);
+                    prev = c;
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+
+        if !opened.is_empty() {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!("unclosed emphasis \"{}\"", opened.join("\", \"")),
+            ));
+        }
+        for o in opened.into_iter().rev() {
+            self.close(o, builder);
+        }
+    }
+
+    fn styles(&self, marker: &str) -> &'static [Style] {
+        self.markers
+            .iter()
+            .find(|&&(m, _)| m == marker)
+            .map_or(&[], |&(_, styles)| styles)
+    }
+
+    fn close(&self, marker: &str, builder: &mut RichTextBuilder) {
+        for _ in self.styles(marker) {
+            // never fails, styles are opened for each marker
+            let _ = builder.close("");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Emphasis;
+    use crate::core::diagnostic::Diagnostic;
+    use crate::core::markup::{RichTextBuilder, Style};
+
+    const EMPHASIS: Emphasis = Emphasis {
+        markers: &[
+            ("**", &[Style::Bold]),
+            ("*", &[Style::Italic]),
+            ("_", &[Style::Underline]),
+        ],
+        is_intraword_underscore_literal: true,
+    };
+
+    #[test]
+    fn test_process() {
+        let mut builder = RichTextBuilder::new();
+        let mut diagnostics = Vec::new();
+        EMPHASIS.process(
+            r"**a** \*b snake_case _c_ *d",
+            &mut builder,
+            2,
+            &mut diagnostics,
+        );
+
+        let mut expected = RichTextBuilder::new();
+        expected.open(Style::Bold);
+        expected.push_str("a");
+        let _ = expected.close("");
+        expected.push_str(" *b snake_case ");
+        expected.open(Style::Underline);
+        expected.push_str("c");
+        let _ = expected.close("");
+        expected.push_str(" *d");
+
+        assert_eq!(expected.finish().0, builder.finish().0);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_process_unclosed() {
+        let mut builder = RichTextBuilder::new();
+        let mut diagnostics = Vec::new();
+        EMPHASIS.process("*a **b** c", &mut builder, 3, &mut diagnostics);
+
+        let (rich, _) = builder.finish();
+        assert_eq!("a b c", rich.plain_text());
+        assert_eq!(
+            vec![Diagnostic::warning(3, "unclosed emphasis \"*\"")],
+            diagnostics
+        );
+    }
+}
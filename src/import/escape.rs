@@ -0,0 +1,191 @@
+use crate::core::diagnostic::Diagnostic;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// KukuriScript text escape sequences
+//
+// \n        => line feed
+// \t        => tabulator
+// \r        => carriage return
+// \u{XXXX}  => unicode scalar value (1 to 6 hex digits)
+// \<punct>  => ASCII punctuation itself (e.g. "\\", "\#", "\:", "\*")
+//
+// Any other sequence is unknown, and kept as it is with a warning.
+pub fn unescape(s: &str, line: usize, diagnostics: &mut Vec<Diagnostic>) -> String {
+    let mut out_s = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            unescape_next(&mut chars, &mut out_s, line, diagnostics);
+        } else {
+            out_s.push(c);
+        }
+    }
+
+    out_s
+}
+
+// Decode one escape sequence after a backslash, and push it into out_s.
+pub fn unescape_next(
+    chars: &mut Peekable<Chars>,
+    out_s: &mut String,
+    line: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    match chars.next() {
+        Some('n') => out_s.push('\n'),
+        Some('t') => out_s.push('\t'),
+        Some('r') => out_s.push('\r'),
+        Some('u') if chars.peek() == Some(&'{') => {
+            chars.next();
+            // the sequence ends at "}", or at the end of line if unterminated
+            let mut hex = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == '}' || c == '\n' {
+                    break;
+                }
+                hex.push(c);
+                chars.next();
+            }
+            if chars.next_if_eq(&'}').is_none() {
+                diagnostics.push(Diagnostic::warning(
+                    line,
+                    format!("unterminated unicode escape sequence \"\\u{{{}\"", hex),
+                ));
+                out_s.push_str(&format!("\\u{{{}", hex));
+                return;
+            }
+            match decode_unicode(&hex) {
+                Some(c) => out_s.push(c),
+                None => {
+                    diagnostics.push(Diagnostic::warning(
+                        line,
+                        format!("invalid unicode escape sequence \"\\u{{{}}}\"", hex),
+                    ));
+                    out_s.push_str(&format!("\\u{{{}}}", hex));
+                }
+            }
+        }
+        Some(c) if c.is_ascii_punctuation() => out_s.push(c),
+        Some(c) => {
+            diagnostics.push(Diagnostic::warning(
+                line,
+                format!("unknown escape sequence \"\\{}\"", c),
+            ));
+            out_s.push('\\');
+            out_s.push(c);
+        }
+        None => {
+            diagnostics.push(Diagnostic::warning(
+                line,
+                "backslash at end of text has nothing to escape",
+            ));
+            out_s.push('\\');
+        }
+    }
+}
+
+fn decode_unicode(hex: &str) -> Option<char> {
+    if hex.is_empty() || hex.len() > 6 {
+        return None;
+    }
+
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .and_then(std::char::from_u32)
+}
+
+// Find a byte index of the first unescaped target char.
+pub fn find_unescaped(s: &str, target: char) -> Option<usize> {
+    let mut is_escaped = false;
+    for (i, c) in s.char_indices() {
+        if is_escaped {
+            is_escaped = false;
+        } else if c == '\\' {
+            is_escaped = true;
+        } else if c == target {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_unescaped, unescape};
+    use crate::core::diagnostic::Diagnostic;
+
+    #[test]
+    fn test_unescape() {
+        let tests = [
+            ("no escape", "no escape"),
+            (r"line\nbreak", "line\nbreak"),
+            (r"tab\tchar", "tab\tchar"),
+            (r"back\\slash", "back\\slash"),
+            (r"\\n is not line break", "\\n is not line break"),
+            (r"\# is not comment", "# is not comment"),
+            (r"\: \* \[ \{", ": * [ {"),
+            (r"\u{3042}\u{1F600}", "あ😀"),
+        ];
+
+        for &(src, expected) in &tests {
+            let mut diagnostics = Vec::new();
+            assert_eq!(expected, unescape(src, 1, &mut diagnostics));
+            assert!(diagnostics.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_unescape_unknown() {
+        let tests = [
+            (
+                r"unknown \q",
+                r"unknown \q",
+                "unknown escape sequence \"\\q\"",
+            ),
+            (
+                r"bad \u{110000}",
+                r"bad \u{110000}",
+                "invalid unicode escape sequence \"\\u{110000}\"",
+            ),
+            (
+                r"open \u{3042 text",
+                r"open \u{3042 text",
+                "unterminated unicode escape sequence \"\\u{3042 text\"",
+            ),
+            (
+                "open \\u{41\nB}",
+                "open \\u{41\nB}",
+                "unterminated unicode escape sequence \"\\u{41\"",
+            ),
+            (
+                "trailing \\",
+                "trailing \\",
+                "backslash at end of text has nothing to escape",
+            ),
+        ];
+
+        for &(src, expected, message) in &tests {
+            let mut diagnostics = Vec::new();
+            assert_eq!(expected, unescape(src, 3, &mut diagnostics));
+            assert_eq!(vec![Diagnostic::warning(3, message)], diagnostics);
+        }
+    }
+
+    #[test]
+    fn test_find_unescaped() {
+        let tests = [
+            ("A: text", ':', Some(1)),
+            (r"A\: text", ':', None),
+            (r"A\\: text", ':', Some(3)),
+            ("text # comment", '#', Some(5)),
+            (r"text \# not comment", '#', None),
+        ];
+
+        for &(src, target, expected) in &tests {
+            assert_eq!(expected, find_unescaped(src, target));
+        }
+    }
+}
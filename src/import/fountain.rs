@@ -0,0 +1,573 @@
+use super::emphasis::Emphasis;
+use super::title::{self, Titles};
+use crate::core::command::{CommandArg, InlineCommand};
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
+use crate::core::dialog::{Dialog, DialogBody, DialogKind, Scene};
+use crate::core::kukuri_data::KukuriData;
+use crate::core::markup::{RichText, RichTextBuilder, Style};
+use crate::core::meta::MetaValue;
+use crate::core::namespace;
+use crate::core::talker::Talker;
+
+const HEADING_PREFIXES: [&str; 6] = ["INT", "EXT", "EST", "INT./EXT", "INT/EXT", "I/E"];
+// scene of lines before the first scene heading
+const ROOT_SCENE: &str = "Root";
+// action lines without narrator
+const ACTION_COMMAND: &str = "action";
+const TRANSITION_COMMAND: &str = "transition";
+// line metadata of dialogs, as inline commands at the start or in the middle of text
+const PARENTHETICAL_COMMAND: &str = "parenthetical";
+const EXTENSION_COMMAND: &str = "extension";
+const EMPHASIS: Emphasis = Emphasis {
+    markers: &[
+        ("***", &[Style::Bold, Style::Italic]),
+        ("**", &[Style::Bold]),
+        ("*", &[Style::Italic]),
+        ("_", &[Style::Underline]),
+    ],
+    is_intraword_underscore_literal: false,
+};
+
+// Import Fountain screenplay.
+//
+// INT. HOUSE - DAY    => Scene "INT_HOUSE_DAY", heading is scene metadata "heading"
+// ALICE (V.O.)        => DialogKind::Dialog, talker "TALKER_ALICE"
+// (beat)              => inline command "parenthetical"
+// action              => dialog of narrator talker, or Command "action"
+// CUT TO:             => Command "transition"
+// *italic* **bold**   => styled span
+pub struct Fountain;
+
+// a paragraph, lines separated by blank lines
+type Block<'a> = Vec<(usize, &'a str)>;
+
+struct FountainParser<'a> {
+    namespace: &'a str,
+    // talker id of action lines, None => Command "action"
+    narrator: Option<&'a str>,
+    scenes: Vec<Scene>,
+    talkers: Vec<Talker>,
+    // scene title: count, for scenes with the same heading
+    titles: Titles,
+    idx: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Fountain {
+    // scene titles are qualified with namespace.
+    // narrator: talker id of action lines, None => Command "action"
+    pub fn parse_with_namespace(
+        content: &str,
+        namespace: &str,
+        narrator: Option<&str>,
+    ) -> (KukuriData, Vec<Diagnostic>) {
+        let content = Self::strip_notes(content);
+        let mut blocks = Self::blocks(&content);
+
+        // title page e.g. "Title: Big Fish"
+        if blocks
+            .first()
+            .is_some_and(|b| Self::is_title_page_line(b[0].1))
+        {
+            blocks.remove(0);
+        }
+
+        let mut parser = FountainParser {
+            namespace,
+            narrator,
+            scenes: Vec::new(),
+            talkers: Vec::new(),
+            titles: Titles::new(ROOT_SCENE),
+            idx: 0,
+            diagnostics: Vec::new(),
+        };
+        for block in &blocks {
+            parser.block_process(block);
+        }
+
+        let data = KukuriData::from_script(parser.scenes, parser.talkers);
+        (data, parser.diagnostics)
+    }
+
+    // "/* boneyard */" and "[[notes]]" are removed, line breaks in them are kept
+    fn strip_notes(content: &str) -> String {
+        let mut out_s = String::new();
+        let mut rest = content;
+
+        loop {
+            let start = [rest.find("/*"), rest.find("[[")]
+                .iter()
+                .flatten()
+                .min()
+                .copied();
+            let start = match start {
+                Some(i) => i,
+                None => break,
+            };
+            out_s.push_str(&rest[..start]);
+
+            let close = if rest[start..].starts_with("/*") {
+                "*/"
+            } else {
+                "]]"
+            };
+            let end = rest[start + 2..]
+                .find(close)
+                .map_or(rest.len(), |i| start + 2 + i + close.len());
+            out_s.extend(rest[start..end].chars().filter(|&c| c == '\n'));
+            rest = &rest[end..];
+        }
+
+        out_s.push_str(rest);
+        out_s
+    }
+
+    // sections "# Act", synopses "= text" and page breaks "===" are dropped
+    fn blocks(content: &str) -> Vec<Block<'_>> {
+        let mut blocks = Vec::new();
+        let mut block = Block::new();
+
+        for (i, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                if !block.is_empty() {
+                    blocks.push(std::mem::take(&mut block));
+                }
+            } else if !(trimmed.starts_with('#') || trimmed.starts_with('=')) {
+                block.push((i + 1, line.trim_end()));
+            }
+        }
+        if !block.is_empty() {
+            blocks.push(block);
+        }
+
+        blocks
+    }
+
+    fn is_title_page_line(line: &str) -> bool {
+        match line.split_once(':') {
+            Some((key, _)) => {
+                !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == ' ')
+            }
+            None => false,
+        }
+    }
+
+    // "INT. HOUSE - DAY", forced ".FLASHBACK"
+    fn scene_heading(line: &str) -> Option<&str> {
+        let line = line.trim();
+        if let Some(forced) = line.strip_prefix('.') {
+            return if forced.starts_with('.') {
+                None
+            } else {
+                Some(forced.trim())
+            };
+        }
+
+        let upper = line.to_uppercase();
+        let is_heading = HEADING_PREFIXES.iter().any(|p| {
+            upper
+                .strip_prefix(p)
+                .is_some_and(|rest| rest.starts_with(['.', ' ']))
+        });
+        if is_heading {
+            Some(line)
+        } else {
+            None
+        }
+    }
+
+    // "CUT TO:", forced "> FADE OUT"
+    fn transition(line: &str) -> Option<&str> {
+        let line = line.trim();
+        match line.strip_prefix('>') {
+            Some(forced) if !forced.ends_with('<') => Some(forced.trim()),
+            Some(_) => None,
+            None if Self::is_upper(line) && line.ends_with("TO:") => Some(line),
+            None => None,
+        }
+    }
+
+    // "ALICE (V.O.) ^", forced "@McCLANE"
+    // return: (name, extensions, is dual dialogue)
+    fn character_cue(line: &str) -> Option<(&str, Vec<&str>, bool)> {
+        let line = line.trim();
+        let (line, is_dual) = match line.strip_suffix('^') {
+            Some(l) => (l.trim_end(), true),
+            None => (line, false),
+        };
+        let (name, ext_str) = match line.find('(') {
+            Some(i) => line.split_at(i),
+            None => (line, ""),
+        };
+
+        let (name, is_forced) = match name.strip_prefix('@') {
+            Some(n) => (n.trim(), true),
+            None => (name.trim(), false),
+        };
+        if name.is_empty() || !(is_forced || Self::is_upper(name)) {
+            return None;
+        }
+
+        let extensions = ext_str
+            .split(['(', ')'])
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect();
+        Some((name, extensions, is_dual))
+    }
+
+    // has uppercase letters and no lowercase ones,
+    // names in scripts without letter case need "@" e.g. "@アリス"
+    fn is_upper(s: &str) -> bool {
+        s.chars().any(char::is_uppercase) && !s.chars().any(char::is_lowercase)
+    }
+
+    fn text_process(lines: &[&str], line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> RichText {
+        let mut builder = RichTextBuilder::new();
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                builder.push_str("\n");
+            }
+            EMPHASIS.process(line, &mut builder, line_no + i, diagnostics);
+        }
+        builder.finish().0
+    }
+}
+
+impl<'a> FountainParser<'a> {
+    fn block_process(&mut self, block: &[(usize, &str)]) {
+        let (line_no, first) = block[0];
+
+        if let Some(heading) = Fountain::scene_heading(first) {
+            self.scene_process(heading, line_no);
+            if block.len() > 1 {
+                self.action_process(&block[1..]);
+            }
+            return;
+        }
+
+        if block.len() == 1 {
+            if let Some(t) = Fountain::transition(first) {
+                let args = vec![DialogBody::Arg(CommandArg::gen_str(t))];
+                let d = Dialog::from_dialog_data(DialogKind::Command, TRANSITION_COMMAND, args)
+                    .with_loc(SourceLoc::from_line(line_no));
+                self.current_scene().dialogs.push(d);
+                return;
+            }
+        }
+
+        match Fountain::character_cue(first) {
+            Some(cue) if block.len() > 1 => self.dialogue_process(cue, block),
+            _ => self.action_process(block),
+        }
+    }
+
+    // "INT. HOUSE - DAY #12A#" => Scene "INT_HOUSE_DAY", number "12A"
+    fn scene_process(&mut self, heading: &str, line_no: usize) {
+        let (heading, number) = match heading.strip_suffix('#').and_then(|h| h.rsplit_once('#')) {
+            Some((h, n)) => (h.trim(), Some(n.trim())),
+            None => (heading, None),
+        };
+
+        let title = self.titles.unique(heading);
+
+        let mut sc = Scene::new();
+        sc.title = namespace::qualify(self.namespace, &title);
+        sc.loc = SourceLoc::from_line(line_no);
+        sc.meta.insert(
+            String::from("heading"),
+            MetaValue::Str(String::from(heading)),
+        );
+        if let Some(n) = number {
+            sc.meta
+                .insert(String::from("number"), MetaValue::Str(String::from(n)));
+        }
+        self.scenes.push(sc);
+        self.idx = 0;
+    }
+
+    fn dialogue_process(&mut self, cue: (&str, Vec<&str>, bool), block: &[(usize, &str)]) {
+        let (name, extensions, is_dual) = cue;
+        let line_no = block[0].0;
+        if is_dual {
+            self.diagnostics.push(Diagnostic::warning(
+                line_no,
+                "dual dialogue is imported as sequential dialogues",
+            ));
+        }
+
+        let talker_id = title::to_ident(name);
+        let talker = format!("TALKER_{}", talker_id);
+        if !self.talkers.iter().any(|t| t.id() == talker) {
+            self.talkers
+                .push(Talker::from_strs(&talker, name).with_loc(SourceLoc::from_line(line_no)));
+        }
+
+        let mut inline_commands: Vec<InlineCommand> = extensions
+            .into_iter()
+            .map(|e| {
+                InlineCommand::from_command_data(0, EXTENSION_COMMAND, vec![CommandArg::gen_str(e)])
+            })
+            .collect();
+
+        // parentheticals are placed at the char offset of following text,
+        // after the line break to it
+        let mut builder = RichTextBuilder::new();
+        let mut has_text = false;
+        for &(n, line) in &block[1..] {
+            let line = line.trim();
+            if line.starts_with('(') && line.ends_with(')') {
+                let p = line[1..line.len() - 1].trim();
+                inline_commands.push(InlineCommand::from_command_data(
+                    builder.char_count() + has_text as usize,
+                    PARENTHETICAL_COMMAND,
+                    vec![CommandArg::gen_str(p)],
+                ));
+                continue;
+            }
+
+            if has_text {
+                builder.push_str("\n");
+            }
+            EMPHASIS.process(line, &mut builder, n, &mut self.diagnostics);
+            has_text = true;
+        }
+
+        let id = self.next_dialog_id(&talker_id);
+        let body = vec![
+            DialogBody::from_rich_text(builder.finish().0),
+            DialogBody::gen_text(talker),
+        ];
+        let d = Dialog::from_dialog_data(DialogKind::Dialog, id, body)
+            .with_loc(SourceLoc::from_line(line_no))
+            .with_inline_commands(inline_commands);
+        self.current_scene().dialogs.push(d);
+    }
+
+    fn action_process(&mut self, block: &[(usize, &str)]) {
+        let line_no = block[0].0;
+        let lines: Vec<&str> = block
+            .iter()
+            .enumerate()
+            .map(|(i, &(_, line))| {
+                let line = if i == 0 {
+                    line.strip_prefix('!').unwrap_or(line)
+                } else {
+                    line
+                };
+                // centered text e.g. "> THE END <"
+                match line
+                    .trim()
+                    .strip_prefix('>')
+                    .and_then(|l| l.strip_suffix('<'))
+                {
+                    Some(centered) => centered.trim(),
+                    None => line,
+                }
+            })
+            .collect();
+        let rich = Fountain::text_process(&lines, line_no, &mut self.diagnostics);
+
+        let d = match self.narrator {
+            Some(narrator) => {
+                let id = self.next_dialog_id(narrator);
+                let body = vec![
+                    DialogBody::from_rich_text(rich),
+                    DialogBody::gen_text(format!("TALKER_{}", narrator)),
+                ];
+                Dialog::from_dialog_data(DialogKind::Dialog, id, body)
+            }
+            None => {
+                let args = vec![DialogBody::Arg(CommandArg::gen_str(rich.plain_text()))];
+                Dialog::from_dialog_data(DialogKind::Command, ACTION_COMMAND, args)
+            }
+        };
+        self.current_scene()
+            .dialogs
+            .push(d.with_loc(SourceLoc::from_line(line_no)));
+    }
+
+    // e.g. "INT_HOUSE_DAY_1_ALICE"
+    fn next_dialog_id(&mut self, talker_id: &str) -> String {
+        self.idx += 1;
+        let idx = self.idx;
+        format!("{}_{}_{}", self.current_scene().title, idx, talker_id)
+    }
+
+    // lines before the first scene heading are in "Root" scene
+    fn current_scene(&mut self) -> &mut Scene {
+        if self.scenes.is_empty() {
+            let mut sc = Scene::new();
+            sc.title = namespace::qualify(self.namespace, ROOT_SCENE);
+            self.scenes.push(sc);
+        }
+        self.scenes.last_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fountain;
+    use crate::core::command::{CommandArg, InlineCommand};
+    use crate::core::dialog::{Dialog, DialogBody, DialogKind};
+    use crate::core::kukuri_data::KukuriData;
+    use crate::core::markup::{RichText, Span, Style};
+    use crate::core::meta::MetaValue;
+    use crate::core::talker::Talker;
+
+    const SRC: &str = r#"Title: Big Fish
+Author: John August
+
+INT. KITCHEN - NIGHT #1#
+
+Alice pours /* boneyard */ coffee.
+
+ALICE (V.O.)
+(quietly)
+Still up?
+(beat)
+It's *late*.
+
+@McCLANE ^
+Yeah. [[note]]
+
+CUT TO:
+
+INT. KITCHEN - NIGHT
+
+> THE END <
+"#;
+
+    fn scenes_and_talkers(data: KukuriData) -> (Vec<crate::core::dialog::Scene>, Vec<Talker>) {
+        match data.without_locs() {
+            KukuriData::Script(scenes, talkers) => (scenes, talkers),
+            _ => (Vec::new(), Vec::new()),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let (data, diagnostics) = Fountain::parse_with_namespace(SRC, "", None);
+        let (scenes, talkers) = scenes_and_talkers(data);
+
+        let titles: Vec<&str> = scenes.iter().map(|sc| sc.title.as_str()).collect();
+        assert_eq!(vec!["INT_KITCHEN_NIGHT", "INT_KITCHEN_NIGHT_2"], titles);
+        assert_eq!(
+            Some(&MetaValue::Str(String::from("INT. KITCHEN - NIGHT"))),
+            scenes[0].meta.get("heading")
+        );
+        assert_eq!(
+            Some(&MetaValue::Str(String::from("1"))),
+            scenes[0].meta.get("number")
+        );
+        assert_eq!(
+            vec![
+                Talker::from_strs("TALKER_ALICE", "ALICE"),
+                Talker::from_strs("TALKER_McCLANE", "McCLANE"),
+            ],
+            talkers
+        );
+
+        let dialogs = &scenes[0].dialogs;
+        let ids: Vec<&str> = dialogs.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(
+            vec![
+                "action",
+                "INT_KITCHEN_NIGHT_1_ALICE",
+                "INT_KITCHEN_NIGHT_2_McCLANE",
+                "transition"
+            ],
+            ids
+        );
+        assert_eq!(
+            vec![DialogBody::Arg(CommandArg::gen_str("Alice pours  coffee."))],
+            dialogs[0].args
+        );
+
+        let text = RichText {
+            spans: vec![
+                Span::Text(String::from("Still up?\nIt's ")),
+                Span::Styled(Style::Italic, vec![Span::Text(String::from("late"))]),
+                Span::Text(String::from(".")),
+            ],
+        };
+        assert_eq!(DialogBody::from_rich_text(text), dialogs[1].args[0]);
+        assert_eq!(
+            vec![
+                InlineCommand::from_command_data(0, "extension", vec![CommandArg::gen_str("V.O.")]),
+                InlineCommand::from_command_data(
+                    0,
+                    "parenthetical",
+                    vec![CommandArg::gen_str("quietly")]
+                ),
+                InlineCommand::from_command_data(
+                    10,
+                    "parenthetical",
+                    vec![CommandArg::gen_str("beat")]
+                ),
+            ],
+            dialogs[1].inline_commands
+        );
+        assert_eq!(
+            vec![DialogBody::Arg(CommandArg::gen_str("CUT TO:"))],
+            dialogs[3].args
+        );
+
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            vec!["14: warning: dual dialogue is imported as sequential dialogues"],
+            messages
+        );
+    }
+
+    #[test]
+    fn test_parse_narrator() {
+        let (data, _) = Fountain::parse_with_namespace(SRC, "ch1", Some("narrator"));
+        let (scenes, _) = scenes_and_talkers(data);
+
+        assert_eq!("ch1.INT_KITCHEN_NIGHT", scenes[0].title);
+        assert_eq!(
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "ch1.INT_KITCHEN_NIGHT_2_1_narrator",
+                vec![
+                    DialogBody::Text(String::from("THE END")),
+                    DialogBody::Text(String::from("TALKER_narrator")),
+                ],
+            ),
+            scenes[1].dialogs[0]
+        );
+        assert_eq!("ch1.INT_KITCHEN_NIGHT_3_McCLANE", scenes[0].dialogs[2].id);
+    }
+
+    #[test]
+    fn test_emphasis() {
+        let src = "***Both*** **b** _u_ \\*no\\* 2 * 3\n";
+        let (data, diagnostics) = Fountain::parse_with_namespace(src, "", Some("N"));
+        let (scenes, _) = scenes_and_talkers(data);
+
+        let text = RichText {
+            spans: vec![
+                Span::Styled(
+                    Style::Bold,
+                    vec![Span::Styled(
+                        Style::Italic,
+                        vec![Span::Text(String::from("Both"))],
+                    )],
+                ),
+                Span::Text(String::from(" ")),
+                Span::Styled(Style::Bold, vec![Span::Text(String::from("b"))]),
+                Span::Text(String::from(" ")),
+                Span::Styled(Style::Underline, vec![Span::Text(String::from("u"))]),
+                Span::Text(String::from(" *no* 2 * 3")),
+            ],
+        };
+        assert_eq!("Root", scenes[0].title);
+        assert_eq!(
+            DialogBody::from_rich_text(text),
+            scenes[0].dialogs[0].args[0]
+        );
+        assert!(diagnostics.is_empty());
+    }
+}
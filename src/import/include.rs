@@ -0,0 +1,248 @@
+use super::kukuri_script::KukuriScript;
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+const INCLUDE_DIRECTIVE: &str = "@include";
+
+// Expand `@include "file.kkr"` lines into logical lines of the file.
+// Included lines are indented as the directive line,
+// so a fragment can be nested in a choice.
+pub struct Includer<'a> {
+    loader: &'a dyn Fn(&Path) -> io::Result<String>,
+    // files being expanded, for cycle detection
+    stack: Vec<PathBuf>,
+}
+
+impl<'a> Includer<'a> {
+    pub fn new(loader: &'a dyn Fn(&Path) -> io::Result<String>) -> Self {
+        Self {
+            loader,
+            stack: Vec::new(),
+        }
+    }
+
+    // path: source file of content, included paths are relative to it.
+    // return: [(location, logical line)...],
+    // location path is empty for lines of content itself.
+    pub fn expand(
+        &mut self,
+        content: &str,
+        path: Option<&Path>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<(SourceLoc, String)> {
+        let mut lines = Vec::new();
+        if let Some(p) = path {
+            self.stack.push(Self::normalize(p));
+        }
+        let base_dir = path.and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+        self.expand_into(content, base_dir, "", "", &mut lines, diagnostics);
+        self.stack.clear();
+        lines
+    }
+
+    fn expand_into(
+        &mut self,
+        content: &str,
+        base_dir: &Path,
+        display_path: &str,
+        indent: &str,
+        lines: &mut Vec<(SourceLoc, String)>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        for (line_no, line) in KukuriScript::logical_lines(content) {
+            let loc = SourceLoc {
+                path: String::from(display_path),
+                line: line_no,
+            };
+
+            let target = match Self::parse_directive(&line) {
+                Some(t) => t,
+                None => {
+                    lines.push((loc, format!("{}{}", indent, line)));
+                    continue;
+                }
+            };
+
+            if target.is_empty() {
+                diagnostics.push(Diagnostic::error(0, "include path is empty").at(&loc));
+                continue;
+            }
+
+            let path = Self::join(base_dir, target);
+            let key = Self::normalize(&path);
+            if self.stack.contains(&key) {
+                let mut chain: Vec<String> =
+                    self.stack.iter().map(|p| p.display().to_string()).collect();
+                chain.push(key.display().to_string());
+                diagnostics.push(
+                    Diagnostic::error(0, format!("include cycle: {}", chain.join(" -> "))).at(&loc),
+                );
+                continue;
+            }
+
+            let included = match (self.loader)(&path) {
+                Ok(s) => s,
+                Err(e) => {
+                    diagnostics.push(
+                        Diagnostic::error(
+                            0,
+                            format!("failed to include {}: {}", path.display(), e),
+                        )
+                        .at(&loc),
+                    );
+                    continue;
+                }
+            };
+
+            let inner_indent = format!("{}{}", indent, Self::indent_of(&line));
+            let inner_dir = path.parent().unwrap_or_else(|| Path::new(""));
+            self.stack.push(key);
+            self.expand_into(
+                &included,
+                inner_dir,
+                &path.display().to_string(),
+                &inner_indent,
+                lines,
+                diagnostics,
+            );
+            self.stack.pop();
+        }
+    }
+
+    // `@include "common/shop.kkr"` or `@include common/shop.kkr`
+    fn parse_directive(line: &str) -> Option<&str> {
+        let rest = line.trim().strip_prefix(INCLUDE_DIRECTIVE)?;
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let rest = rest.trim();
+        Some(
+            rest.strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .unwrap_or(rest),
+        )
+    }
+
+    fn indent_of(line: &str) -> &str {
+        &line[..line.len() - line.trim_start().len()]
+    }
+
+    // join paths, resolving "." and ".." lexically
+    fn join(base_dir: &Path, target: &str) -> PathBuf {
+        let mut path = PathBuf::new();
+        for c in base_dir.join(target).components() {
+            match c {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if matches!(path.components().next_back(), Some(Component::Normal(_))) {
+                        path.pop();
+                    } else {
+                        path.push("..");
+                    }
+                }
+                c => path.push(c),
+            }
+        }
+        path
+    }
+
+    fn normalize(path: &Path) -> PathBuf {
+        std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Includer;
+    use crate::core::diagnostic::{Diagnostic, SourceLoc};
+    use std::collections::HashMap;
+    use std::io;
+    use std::path::Path;
+
+    fn gen_loader(files: &[(&str, &str)]) -> impl Fn(&Path) -> io::Result<String> {
+        let files: HashMap<String, String> = files
+            .iter()
+            .map(|(p, s)| (String::from(*p), String::from(*s)))
+            .collect();
+        move |p: &Path| {
+            files
+                .get(&p.display().to_string())
+                .cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not found"))
+        }
+    }
+
+    fn gen_loc(path: &str, line: usize) -> SourceLoc {
+        SourceLoc {
+            path: String::from(path),
+            line,
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        let loader = gen_loader(&[
+            (
+                "story/shop.kkr",
+                "A: Welcome\n@include \"../common/bye.kkr\"",
+            ),
+            ("common/bye.kkr", "A: Bye # comment"),
+        ]);
+        let src = "\
+* Shop
+    @include shop.kkr
+* Leave";
+
+        let mut diagnostics = Vec::new();
+        let lines =
+            Includer::new(&loader).expand(src, Some(Path::new("story/main.kkr")), &mut diagnostics);
+
+        let lines: Vec<(String, usize, String)> = lines
+            .into_iter()
+            .map(|(loc, s)| (loc.path, loc.line, s))
+            .collect();
+        let expected = vec![
+            (String::new(), 1, String::from("* Shop")),
+            (
+                String::from("story/shop.kkr"),
+                1,
+                String::from("    A: Welcome"),
+            ),
+            (
+                String::from("common/bye.kkr"),
+                1,
+                String::from("    A: Bye "),
+            ),
+            (String::new(), 3, String::from("* Leave")),
+        ];
+        assert_eq!(expected, lines);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let loader = gen_loader(&[
+            ("a.kkr", "@include b.kkr"),
+            ("b.kkr", "A: b\n@include a.kkr"),
+        ]);
+        let src = "@include a.kkr\n@include\n@include missing.kkr\n@included is not directive";
+
+        let mut diagnostics = Vec::new();
+        let lines =
+            Includer::new(&loader).expand(src, Some(Path::new("main.kkr")), &mut diagnostics);
+
+        assert_eq!(2, lines.len());
+        assert_eq!("A: b", lines[0].1);
+        assert_eq!(
+            vec![
+                Diagnostic::error(0, "include cycle: main.kkr -> a.kkr -> b.kkr -> a.kkr")
+                    .at(&gen_loc("b.kkr", 2)),
+                Diagnostic::error(0, "include path is empty").at(&gen_loc("", 2)),
+                Diagnostic::error(0, "failed to include missing.kkr: not found")
+                    .at(&gen_loc("", 3)),
+            ],
+            diagnostics
+        );
+    }
+}
@@ -0,0 +1,745 @@
+use super::{comment, escape};
+use crate::core::command::{CommandArg, InlineCommand};
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
+use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+use crate::core::kukuri_data::KukuriData;
+use crate::core::markup::RichText;
+use crate::core::meta::MetaValue;
+use crate::core::namespace;
+use std::collections::BTreeSet;
+
+// title of content before the first knot
+const ROOT_TITLE: &str = "Root";
+const JUMP_COMMAND: &str = "jump";
+const END_COMMAND: &str = "end";
+const TAG_COMMAND: &str = "tag";
+// declarations without a kukuri counterpart
+const IGNORED_KEYWORDS: [&str; 5] = ["VAR", "CONST", "LIST", "INCLUDE", "EXTERNAL"];
+
+// Import ink script.
+//
+// === knot ===     => Scene "knot"
+// = stitch         => Scene "knot.stitch"
+// Name: text       => DialogKind::Dialog
+// * [label] text   => DialogKind::Choices, "*" count is nesting depth
+// - gather         => lines after choices
+// -> target        => Command "jump", "-> END" and "-> DONE" => Command "end"
+// text #tag        => inline command "tag" of the dialog
+pub struct Ink;
+
+// a parsed line of a flow
+enum Item {
+    Text {
+        line_no: usize,
+        text: String,
+        tags: Vec<String>,
+    },
+    Divert {
+        line_no: usize,
+        target: String,
+    },
+    Choice {
+        line_no: usize,
+        depth: usize,
+        label: String,
+        // items shown after the choice is taken
+        content: Vec<Item>,
+    },
+    Gather {
+        depth: usize,
+        content: Vec<Item>,
+    },
+}
+
+// a knot or stitch before building dialogs
+struct Flow {
+    // ink path e.g. "knot.stitch"
+    path: String,
+    knot: String,
+    line_no: usize,
+    tags: Vec<String>,
+    items: Vec<Item>,
+    // functions are not imported
+    is_function: bool,
+}
+
+// text, tags and divert of a content line
+struct Content {
+    text: String,
+    tags: Vec<String>,
+    divert: Option<String>,
+}
+
+impl Flow {
+    fn new(path: &str, knot: &str, line_no: usize) -> Self {
+        Self {
+            path: String::from(path),
+            knot: String::from(knot),
+            line_no,
+            tags: Vec::new(),
+            items: Vec::new(),
+            is_function: false,
+        }
+    }
+}
+
+impl Ink {
+    // knot titles are qualified with namespace
+    pub fn parse_with_namespace(content: &str, namespace: &str) -> (KukuriData, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let flows = Self::flows_process(content, &mut diagnostics);
+
+        let paths: BTreeSet<&str> = flows.iter().map(|f| f.path.as_str()).collect();
+        let scenes = flows
+            .iter()
+            .map(|f| {
+                let mut sc = Scene::new();
+                sc.title = namespace::qualify(namespace, &f.path);
+                sc.loc = SourceLoc::from_line(f.line_no);
+                if !f.tags.is_empty() {
+                    let tags = f.tags.iter().map(|t| MetaValue::Str(t.clone())).collect();
+                    sc.meta.insert(String::from("tags"), MetaValue::Array(tags));
+                }
+
+                let builder = DialogBuilder {
+                    knot: &f.knot,
+                    paths: &paths,
+                };
+                let (mut pos, mut idx) = (0, 0);
+                sc.dialogs = builder.block_process(&f.items, &mut pos, 0, &f.path, &mut idx);
+                sc
+            })
+            .collect();
+
+        (KukuriData::from_scenes(scenes), diagnostics)
+    }
+
+    // split content into knots and stitches
+    fn flows_process(content: &str, diagnostics: &mut Vec<Diagnostic>) -> Vec<Flow> {
+        let mut flows = Vec::new();
+        let mut flow = Flow::new(ROOT_TITLE, "", 1);
+        let content = comment::strip(content, &[("//", "\n"), ("/*", "*/")]);
+
+        for (i, line) in content.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with("==") {
+                Self::push_flow(&mut flows, flow, diagnostics);
+                let header = line.trim_matches('=').trim();
+                if header.starts_with("function ") {
+                    diagnostics.push(Diagnostic::warning(
+                        line_no,
+                        format!("function \"{}\" is not supported, ignored", header),
+                    ));
+                    flow = Flow::new(header, header, line_no);
+                    flow.is_function = true;
+                    continue;
+                }
+                let name = Self::flow_name(header, line_no, diagnostics);
+                flow = Flow::new(name, name, line_no);
+                continue;
+            }
+
+            if flow.is_function {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('=') {
+                let name = Self::flow_name(header.trim(), line_no, diagnostics);
+                let path = namespace::qualify(&flow.knot, name);
+                // a knot without own content flows into its first stitch
+                if flow.items.is_empty() && flow.path == flow.knot && !flow.knot.is_empty() {
+                    flow.items.push(Item::Divert {
+                        line_no,
+                        target: path.clone(),
+                    });
+                }
+                let knot = flow.knot.clone();
+                Self::push_flow(&mut flows, flow, diagnostics);
+                flow = Flow::new(&path, &knot, line_no);
+                continue;
+            }
+
+            if let Some(tags) = line.strip_prefix('#') {
+                if flow.items.is_empty() {
+                    // tags at the top of a knot
+                    let tags = format!("#{}", tags);
+                    flow.tags.extend(Self::split_tags(&tags));
+                    continue;
+                }
+            }
+
+            if let Some(item) = Self::line_process(line, line_no, diagnostics) {
+                flow.items.push(item);
+            }
+        }
+
+        Self::push_flow(&mut flows, flow, diagnostics);
+        flows
+    }
+
+    fn push_flow(flows: &mut Vec<Flow>, flow: Flow, diagnostics: &mut Vec<Diagnostic>) {
+        if flow.is_function {
+            return;
+        }
+        if flow.path == ROOT_TITLE && flow.items.is_empty() {
+            if !flow.tags.is_empty() {
+                diagnostics.push(Diagnostic::warning(
+                    flow.line_no,
+                    "global tags are ignored, put them at the top of a knot",
+                ));
+            }
+            return;
+        }
+        flows.push(flow);
+    }
+
+    // "knot(x, y)" => "knot"
+    fn flow_name<'a>(
+        header: &'a str,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> &'a str {
+        match header.split_once('(') {
+            Some((name, _)) => {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!("parameters of \"{}\" are not supported, ignored", header),
+                ));
+                name.trim()
+            }
+            None => header,
+        }
+    }
+
+    fn line_process(line: &str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> Option<Item> {
+        let keyword = line.split_whitespace().next().unwrap_or("");
+        if line.starts_with('~') || IGNORED_KEYWORDS.contains(&keyword) {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!("\"{}\" is not supported, ignored", line),
+            ));
+            return None;
+        }
+        if line.starts_with("<-") {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!("thread \"{}\" is not supported, ignored", line),
+            ));
+            return None;
+        }
+
+        if line.starts_with('*') || line.starts_with('+') {
+            return Some(Self::choice_process(line, line_no, diagnostics));
+        }
+
+        let (depth, rest) = Self::count_bullets(line, &['-']);
+        if depth > 0 {
+            let rest = Self::strip_label(rest, line_no, diagnostics);
+            return Some(Item::Gather {
+                depth,
+                content: Self::content_items(rest, line_no, diagnostics),
+            });
+        }
+
+        let mut items = Self::content_items(line, line_no, diagnostics);
+        match items.len() {
+            0 => None,
+            1 => items.pop(),
+            // "text -> target" is kept as a gather without depth
+            _ => Some(Item::Gather {
+                depth: 0,
+                content: items,
+            }),
+        }
+    }
+
+    fn choice_process(line: &str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> Item {
+        let (depth, rest) = Self::count_bullets(line, &['*', '+']);
+        let rest = Self::strip_label(rest, line_no, diagnostics);
+        let rest = match rest.strip_prefix('{') {
+            Some(s) => {
+                let end = s.find('}').map_or(s.len(), |i| i + 1);
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!(
+                        "choice condition \"{{{}\" is not supported, the choice is always shown",
+                        &s[..end]
+                    ),
+                ));
+                s[end..].trim_start()
+            }
+            None => rest,
+        };
+
+        let c = Self::split_content(rest, line_no, diagnostics);
+        if !c.tags.is_empty() {
+            diagnostics.push(Diagnostic::warning(line_no, "tags of a choice are ignored"));
+        }
+
+        // "a [b] c" => label "a b", shown text "a c"
+        let (label, shown) = match escape::find_unescaped(&c.text, '[') {
+            Some(i) => {
+                let (a, rest) = c.text.split_at(i);
+                let (b, c) = rest[1..].split_once(']').unwrap_or((&rest[1..], ""));
+                (format!("{}{}", a, b), format!("{}{}", a, c))
+            }
+            None => (c.text.clone(), c.text.clone()),
+        };
+
+        let mut content = Vec::new();
+        let shown = shown.trim();
+        if !shown.is_empty() {
+            content.push(Item::Text {
+                line_no,
+                text: escape::unescape(shown, line_no, diagnostics),
+                tags: Vec::new(),
+            });
+        }
+        if let Some(target) = c.divert {
+            content.push(Item::Divert { line_no, target });
+        }
+
+        Item::Choice {
+            line_no,
+            depth,
+            label: escape::unescape(label.trim(), line_no, diagnostics),
+            content,
+        }
+    }
+
+    // "* * text" => (2, "text")
+    fn count_bullets<'a>(line: &'a str, bullets: &[char]) -> (usize, &'a str) {
+        let mut depth = 0;
+        let mut rest = line;
+        loop {
+            let s = rest.trim_start();
+            match s.chars().next() {
+                // "->" is a divert, not a gather
+                Some(c) if bullets.contains(&c) && !s.starts_with("->") => {
+                    depth += 1;
+                    rest = &s[1..];
+                }
+                _ => return (depth, s),
+            }
+        }
+    }
+
+    // "(label) text" => "text"
+    fn strip_label<'a>(s: &'a str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> &'a str {
+        if let Some(rest) = s.strip_prefix('(') {
+            if let Some((label, rest)) = rest.split_once(')') {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!("label \"({})\" is not supported, ignored", label),
+                ));
+                return rest.trim_start();
+            }
+        }
+        s
+    }
+
+    fn content_items(s: &str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> Vec<Item> {
+        let c = Self::split_content(s, line_no, diagnostics);
+        let mut items = Vec::new();
+
+        if !c.text.is_empty() {
+            items.push(Item::Text {
+                line_no,
+                text: escape::unescape(&c.text, line_no, diagnostics),
+                tags: c.tags,
+            });
+        } else if !c.tags.is_empty() {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                "tags without text are ignored",
+            ));
+        }
+        if let Some(target) = c.divert {
+            items.push(Item::Divert { line_no, target });
+        }
+        items
+    }
+
+    // "text -> target #tag" => Content
+    fn split_content(s: &str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> Content {
+        let (s, tags) = match escape::find_unescaped(s, '#') {
+            Some(i) => (&s[..i], Self::split_tags(&s[i..])),
+            None => (s, Vec::new()),
+        };
+
+        let (text, divert) = match s.find("->") {
+            Some(i) => (
+                &s[..i],
+                Self::divert_target(&s[i + 2..], line_no, diagnostics),
+            ),
+            None => (s, None),
+        };
+
+        let mut text = String::from(text.trim());
+        if text.contains("<>") {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                "glue \"<>\" is not supported, removed",
+            ));
+            text = text.replace("<>", "").trim().to_string();
+        }
+        if escape::find_unescaped(&text, '{').is_some() {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!(
+                    "inline logic in \"{}\" is not supported, kept as text",
+                    text
+                ),
+            ));
+        }
+
+        Content { text, tags, divert }
+    }
+
+    fn divert_target(s: &str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> Option<String> {
+        let s = s.trim();
+        if s.is_empty() || s.contains("->") {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!("tunnel \"-> {}\" is not supported, ignored", s),
+            ));
+            return None;
+        }
+
+        let target = match s.split_once('(') {
+            Some((t, _)) => {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!("divert arguments of \"{}\" are not supported, ignored", s),
+                ));
+                t.trim()
+            }
+            None => s,
+        };
+        Some(String::from(target))
+    }
+
+    // "#a #b c" => ["a", "b c"]
+    fn split_tags(s: &str) -> Vec<String> {
+        s.split('#')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+// Builds dialogs of a flow from items
+struct DialogBuilder<'a> {
+    knot: &'a str,
+    // all flow paths, for resolving stitch names
+    paths: &'a BTreeSet<&'a str>,
+}
+
+impl<'a> DialogBuilder<'a> {
+    // build items from pos while they are deeper than depth.
+    // label: id prefix of dialogs in this block
+    // idx: count of dialogs and choices, for id generation
+    fn block_process(
+        &self,
+        items: &[Item],
+        pos: &mut usize,
+        depth: usize,
+        label: &str,
+        idx: &mut usize,
+    ) -> Vec<Dialog> {
+        let mut dialogs = Vec::new();
+
+        while *pos < items.len() {
+            match &items[*pos] {
+                Item::Choice { depth: d, .. } if *d <= depth => break,
+                Item::Gather { depth: d, .. } if *d > 0 && *d <= depth => break,
+                Item::Choice { line_no, .. } => {
+                    *idx += 1;
+                    let choices_id = format!("{}_{}_C1", label, idx);
+                    let choices = self.choices_process(items, pos, &choices_id);
+                    dialogs.push(choices.with_loc(SourceLoc::from_line(*line_no)));
+                }
+                item => {
+                    self.item_process(item, idx, label, &mut dialogs);
+                    *pos += 1;
+                }
+            }
+        }
+
+        dialogs
+    }
+
+    // consecutive choices of the same depth, and their content
+    fn choices_process(&self, items: &[Item], pos: &mut usize, choices_id: &str) -> Dialog {
+        let mut choices = Dialog::from_dialog_data(DialogKind::Choices, choices_id, Vec::new());
+        let mut group_depth = None;
+
+        while let Some(Item::Choice {
+            line_no,
+            depth,
+            label,
+            content,
+        }) = items.get(*pos)
+        {
+            // "**" without a parent "*" is taken as the first level
+            let group_depth = *group_depth.get_or_insert(*depth);
+            if *depth < group_depth {
+                break;
+            }
+            *pos += 1;
+
+            let id = format!("{}L{}", choices_id, choices.args.len() + 1);
+            let mut cd =
+                ChoiceData::from_texts(&id, label).with_loc(SourceLoc::from_line(*line_no));
+            let mut idx = 0;
+            for item in content {
+                self.item_process(item, &mut idx, &id, &mut cd.dialogs);
+            }
+            let dialogs = self.block_process(items, pos, group_depth, &id, &mut idx);
+            cd.dialogs.extend(dialogs);
+            choices.args.push(DialogBody::Choice(cd));
+        }
+
+        choices
+    }
+
+    fn item_process(&self, item: &Item, idx: &mut usize, label: &str, dialogs: &mut Vec<Dialog>) {
+        match item {
+            Item::Text {
+                line_no,
+                text,
+                tags,
+            } => {
+                *idx += 1;
+                dialogs.push(Self::text_process(
+                    text,
+                    tags,
+                    *line_no,
+                    &format!("{}_{}", label, idx),
+                ));
+            }
+            Item::Divert { line_no, target } => {
+                dialogs.push(self.divert_process(target, *line_no));
+            }
+            Item::Gather { content, .. } => {
+                for item in content {
+                    self.item_process(item, idx, label, dialogs);
+                }
+            }
+            // choices are processed in block
+            Item::Choice { .. } => {}
+        }
+    }
+
+    fn text_process(text: &str, tags: &[String], line_no: usize, id_prefix: &str) -> Dialog {
+        // "\:" is not a talker separator
+        let (talker, text) = match escape::find_unescaped(text, ':') {
+            Some(i) if !text[i + 1..].trim().is_empty() => (text[..i].trim(), text[i + 1..].trim()),
+            _ => ("unknown", text),
+        };
+
+        let commands = tags
+            .iter()
+            .map(|t| InlineCommand::from_command_data(0, TAG_COMMAND, vec![CommandArg::gen_str(t)]))
+            .collect();
+        let body = vec![
+            DialogBody::from_rich_text(RichText::from_plain(text)),
+            DialogBody::gen_text(format!("TALKER_{}", talker)),
+        ];
+        Dialog::from_dialog_data(
+            DialogKind::Dialog,
+            format!("{}_{}", id_prefix, talker),
+            body,
+        )
+        .with_inline_commands(commands)
+        .with_loc(SourceLoc::from_line(line_no))
+    }
+
+    fn divert_process(&self, target: &str, line_no: usize) -> Dialog {
+        let loc = SourceLoc::from_line(line_no);
+        if target == "END" || target == "DONE" {
+            return Dialog::from_dialog_data(DialogKind::Command, END_COMMAND, Vec::new())
+                .with_loc(loc);
+        }
+
+        // a stitch name is relative to the current knot
+        let stitch = namespace::qualify(self.knot, target);
+        let target = if !target.contains('.') && self.paths.contains(stitch.as_str()) {
+            stitch
+        } else {
+            String::from(target)
+        };
+        let args = vec![DialogBody::Arg(CommandArg::gen_ident(target))];
+        Dialog::from_dialog_data(DialogKind::Command, JUMP_COMMAND, args).with_loc(loc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ink;
+    use crate::core::command::{CommandArg, InlineCommand};
+    use crate::core::diagnostic::Diagnostic;
+    use crate::core::dialog::{Dialog, DialogBody, DialogKind};
+    use crate::core::kukuri_data::KukuriData;
+    use crate::core::markup::RichText;
+    use crate::core::meta::MetaValue;
+    use crate::import::flatten;
+
+    fn gen_jump(target: &str) -> Dialog {
+        Dialog::from_dialog_data(
+            DialogKind::Command,
+            "jump",
+            vec![DialogBody::Arg(CommandArg::gen_ident(target))],
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let ink_src = r#"
+-> shop
+
+=== shop ===
+# bgm: town
+Alice: Welcome! #happy
+* [Buy] I'll take it.
+    Alice: Thanks.
+    * * [Gift wrap?]
+        -> wrap
+    * * No thanks.
+    - - Alice: Here you go.
++ Leave -> END
+- Alice: Bye. // comment
+-> DONE
+
+= wrap
+/* block
+   comment */ Alice: Wrapped.
+"#;
+        let (data, diagnostics) = Ink::parse_with_namespace(ink_src, "");
+        let scenes = match data.without_locs() {
+            KukuriData::Scenes(scenes) => scenes,
+            _ => Vec::new(),
+        };
+
+        let titles: Vec<&str> = scenes.iter().map(|sc| sc.title.as_str()).collect();
+        assert_eq!(vec!["Root", "shop", "shop.wrap"], titles);
+        assert_eq!(vec![gen_jump("shop")], scenes[0].dialogs);
+        assert_eq!(
+            Some(&MetaValue::Array(vec![MetaValue::Str(String::from(
+                "bgm: town"
+            ))])),
+            scenes[1].meta.get("tags")
+        );
+        assert_eq!(
+            vec![InlineCommand::from_command_data(
+                0,
+                "tag",
+                vec![CommandArg::gen_str("happy")]
+            )],
+            scenes[1].dialogs[0].inline_commands
+        );
+
+        let expected: Vec<(&str, &str)> = vec![
+            ("shop_1_Alice", "Welcome!"),
+            ("shop_2_C1", ""),
+            ("shop_2_C1L1", "Buy"),
+            ("shop_2_C1L1_1_unknown", "I'll take it."),
+            ("shop_2_C1L1_2_Alice", "Thanks."),
+            ("shop_2_C1L1_3_C1", ""),
+            ("shop_2_C1L1_3_C1L1", "Gift wrap?"),
+            ("jump", "shop.wrap"),
+            ("shop_2_C1L1_3_C1L2", "No thanks."),
+            ("shop_2_C1L1_3_C1L2_1_unknown", "No thanks."),
+            ("shop_2_C1L1_4_Alice", "Here you go."),
+            ("shop_2_C1L2", "Leave"),
+            ("shop_2_C1L2_1_unknown", "Leave"),
+            ("end", ""),
+            ("shop_3_Alice", "Bye."),
+            ("end", ""),
+        ];
+        let expected: Vec<(String, String)> = expected
+            .into_iter()
+            .map(|(a, b)| (String::from(a), String::from(b)))
+            .collect();
+        assert_eq!(expected, flatten(&scenes[1].dialogs));
+
+        assert_eq!(
+            vec![Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "shop.wrap_1_Alice",
+                vec![
+                    DialogBody::from_rich_text(RichText::from_plain("Wrapped.")),
+                    DialogBody::gen_text("TALKER_Alice"),
+                ],
+            )],
+            scenes[2].dialogs
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_parse_knot_into_stitch() {
+        let ink_src = "=== chapter ===\n= one\nA: 1 -> two\n= two\nA: 2";
+        let (data, _) = Ink::parse_with_namespace(ink_src, "ch1");
+        let scenes = match data.without_locs() {
+            KukuriData::Scenes(scenes) => scenes,
+            _ => Vec::new(),
+        };
+
+        assert_eq!("ch1.chapter", scenes[0].title);
+        assert_eq!(vec![gen_jump("chapter.one")], scenes[0].dialogs);
+        assert_eq!("ch1.chapter.one", scenes[1].title);
+        assert_eq!(gen_jump("chapter.two"), scenes[1].dialogs[1]);
+    }
+
+    #[test]
+    fn test_parse_unsupported() {
+        let ink_src = r#"
+VAR gold = 0
+=== shop(price) ===
+~ gold = gold - price
+* (buy) {gold > 0} Buy <> it #t
+- (done) You have {gold} gold.
+-> shop(1)
+<- chatter
+=== function add(x) ===
+~ return x + 1
+"#;
+        let (_, diagnostics) = Ink::parse_with_namespace(ink_src, "");
+        assert_eq!(
+            vec![
+                Diagnostic::warning(2, "\"VAR gold = 0\" is not supported, ignored"),
+                Diagnostic::warning(
+                    3,
+                    "parameters of \"shop(price)\" are not supported, ignored"
+                ),
+                Diagnostic::warning(4, "\"~ gold = gold - price\" is not supported, ignored"),
+                Diagnostic::warning(5, "label \"(buy)\" is not supported, ignored"),
+                Diagnostic::warning(
+                    5,
+                    "choice condition \"{gold > 0}\" is not supported, the choice is always shown"
+                ),
+                Diagnostic::warning(5, "glue \"<>\" is not supported, removed"),
+                Diagnostic::warning(5, "tags of a choice are ignored"),
+                Diagnostic::warning(6, "label \"(done)\" is not supported, ignored"),
+                Diagnostic::warning(
+                    6,
+                    "inline logic in \"You have {gold} gold.\" is not supported, kept as text"
+                ),
+                Diagnostic::warning(
+                    7,
+                    "divert arguments of \"shop(1)\" are not supported, ignored"
+                ),
+                Diagnostic::warning(8, "thread \"<- chatter\" is not supported, ignored"),
+                Diagnostic::warning(9, "function \"function add(x)\" is not supported, ignored"),
+            ],
+            diagnostics
+        );
+    }
+}
@@ -0,0 +1,392 @@
+use super::{command, escape};
+use crate::core::command::InlineCommand;
+use crate::core::diagnostic::Diagnostic;
+use crate::core::markup::{RichText, RichTextBuilder, Style};
+use std::iter::Peekable;
+use std::str::Chars;
+
+// Parsed dialog text
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct InlineText {
+    // decoded text as span tree
+    pub rich: RichText,
+    pub commands: Vec<InlineCommand>,
+}
+
+// Parse dialog text markup, and decode escape sequences.
+//
+// [cmd args] => inline command at current char offset of plain text
+// <b>text</b> or <b>text</> => styled span
+// {漢字|かんじ}               => ruby
+// \[ \< \{   => literal "[" "<" "{"
+pub fn parse_text(s: &str, line: usize, diagnostics: &mut Vec<Diagnostic>) -> InlineText {
+    let mut builder = RichTextBuilder::new();
+    let mut commands = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let mut decoded = String::new();
+                escape::unescape_next(&mut chars, &mut decoded, line, diagnostics);
+                builder.push_str(&decoded);
+            }
+            '[' => {
+                let (body, is_closed) = read_bracket_body(&mut chars);
+                let offset = builder.char_count();
+                match parse_command_body(&body, offset, line, diagnostics) {
+                    Some(cmd) if is_closed => commands.push(cmd),
+                    _ => {
+                        let reason = if is_closed {
+                            "empty inline command"
+                        } else {
+                            "unterminated inline command"
+                        };
+                        diagnostics.push(Diagnostic::warning(
+                            line,
+                            format!("{} \"[{}\", use \"\\[\" for literal \"[\"", reason, body),
+                        ));
+                        // keep it as literal text
+                        let literal = format!("[{}{}", body, if is_closed { "]" } else { "" });
+                        builder.push_str(&literal);
+                    }
+                }
+            }
+            '{' => ruby_process(&mut chars, &mut builder, line, diagnostics),
+            '<' if is_tag_start(&mut chars) => {
+                tag_process(&mut chars, &mut builder, line, diagnostics);
+            }
+            _ => builder.push_str(c.encode_utf8(&mut [0; 4])),
+        }
+    }
+
+    let (rich, unclosed) = builder.finish();
+    for name in unclosed {
+        diagnostics.push(Diagnostic::warning(
+            line,
+            format!("unclosed tag \"<{}>\"", name),
+        ));
+    }
+
+    InlineText { rich, commands }
+}
+
+// process a ruby after "{"
+fn ruby_process(
+    chars: &mut Peekable<Chars>,
+    builder: &mut RichTextBuilder,
+    line: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut look = chars.clone();
+    let is_closed = chars.clone().any(|c| c == '}');
+    let body: String = look.by_ref().take_while(|&c| c != '}').collect();
+
+    match body.split_once('|') {
+        Some((base, reading))
+            if is_closed && !base.trim().is_empty() && !reading.trim().is_empty() =>
+        {
+            builder.push_ruby(base.trim(), reading.trim());
+            *chars = look;
+        }
+        _ => {
+            diagnostics.push(Diagnostic::warning(
+                line,
+                format!(
+                    "invalid ruby \"{{{}\", expected \"{{base|reading}}\", use \"\\{{\" for literal \"{{\"",
+                    body
+                ),
+            ));
+            builder.push_str("{");
+        }
+    }
+}
+
+// "a < b" is not a tag
+fn is_tag_start(chars: &mut Peekable<Chars>) -> bool {
+    chars
+        .peek()
+        .is_some_and(|&c| c.is_ascii_alphabetic() || c == '/')
+}
+
+// process a tag after "<"
+fn tag_process(
+    chars: &mut Peekable<Chars>,
+    builder: &mut RichTextBuilder,
+    line: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    // look ahead, so that a broken tag is kept as text
+    let mut look = chars.clone();
+    let body: String = look.by_ref().take_while(|&c| c != '>').collect();
+    let is_closed = chars.clone().any(|c| c == '>');
+
+    let is_processed = if !is_closed {
+        diagnostics.push(Diagnostic::warning(
+            line,
+            format!(
+                "unterminated tag \"<{}\", use \"\\<\" for literal \"<\"",
+                body
+            ),
+        ));
+        false
+    } else if let Some(name) = body.strip_prefix('/') {
+        if let Err(message) = builder.close(name.trim()) {
+            diagnostics.push(Diagnostic::warning(line, message));
+        }
+        true
+    } else if let Some(style) = Style::parse(&body) {
+        builder.open(style);
+        true
+    } else {
+        diagnostics.push(Diagnostic::warning(
+            line,
+            format!("unknown tag \"<{}>\", use \"\\<\" for literal \"<\"", body),
+        ));
+        false
+    };
+
+    if is_processed {
+        *chars = look;
+    } else {
+        builder.push_str("<");
+    }
+}
+
+// read until unquoted and unescaped "]"
+// return: (body, is_closed)
+fn read_bracket_body<I: Iterator<Item = char>>(chars: &mut I) -> (String, bool) {
+    let mut body = String::new();
+    let mut quote: Option<char> = None;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                body.push(c);
+                if let Some(c) = chars.next() {
+                    body.push(c);
+                }
+                continue;
+            }
+            ']' if quote.is_none() => return (body, true),
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            _ if quote == Some(c) => quote = None,
+            _ => {}
+        }
+        body.push(c);
+    }
+
+    (body, false)
+}
+
+fn parse_command_body(
+    body: &str,
+    offset: usize,
+    line: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<InlineCommand> {
+    let body = body.trim();
+    if body.is_empty() {
+        return None;
+    }
+
+    let (id, args_str) = match body.find(char::is_whitespace) {
+        Some(i) => body.split_at(i),
+        None => (body, ""),
+    };
+    let args = command::parse_args(args_str, line, diagnostics);
+
+    Some(InlineCommand::from_command_data(offset, id, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_text, InlineText};
+    use crate::core::command::{CommandArg, Float, InlineCommand};
+    use crate::core::diagnostic::Diagnostic;
+    use crate::core::markup::{RichText, Span, Style};
+
+    fn gen_inline_text(text: &str, commands: Vec<InlineCommand>) -> InlineText {
+        InlineText {
+            rich: RichText::from_plain(text),
+            commands,
+        }
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let tests = [
+            ("no markup", gen_inline_text("no markup", Vec::new())),
+            (
+                "Wait[wait 0.5]... what?",
+                gen_inline_text(
+                    "Wait... what?",
+                    vec![InlineCommand::from_command_data(
+                        4,
+                        "wait",
+                        vec![CommandArg::Float(Float(0.5))],
+                    )],
+                ),
+            ),
+            (
+                r#"[se "door]open.ogg"]あい\nう[speed fast=true]え"#,
+                gen_inline_text(
+                    "あい\nうえ",
+                    vec![
+                        InlineCommand::from_command_data(
+                            0,
+                            "se",
+                            vec![CommandArg::gen_str("door]open.ogg")],
+                        ),
+                        InlineCommand::from_command_data(
+                            4,
+                            "speed",
+                            vec![CommandArg::Named(
+                                String::from("fast"),
+                                Box::new(CommandArg::Bool(true)),
+                            )],
+                        ),
+                    ],
+                ),
+            ),
+            (
+                r"escaped \[bracket] and \\[clear]",
+                gen_inline_text(
+                    "escaped [bracket] and \\",
+                    vec![InlineCommand::from_command_data(23, "clear", Vec::new())],
+                ),
+            ),
+        ];
+
+        for (src, expected) in &tests {
+            let mut diagnostics = Vec::new();
+            assert_eq!(*expected, parse_text(src, 1, &mut diagnostics));
+            assert!(diagnostics.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parse_text_diagnostics() {
+        let mut diagnostics = Vec::new();
+        assert_eq!(
+            gen_inline_text("empty [] and [wait", Vec::new()),
+            parse_text("empty [] and [wait", 2, &mut diagnostics)
+        );
+        assert_eq!(
+            vec![
+                Diagnostic::warning(
+                    2,
+                    "empty inline command \"[\", use \"\\[\" for literal \"[\""
+                ),
+                Diagnostic::warning(
+                    2,
+                    "unterminated inline command \"[wait\", use \"\\[\" for literal \"[\""
+                ),
+            ],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_parse_markup() {
+        let mut diagnostics = Vec::new();
+        let it = parse_text(
+            r"a < b, <b>bold[wait 1] <color=#f00>red</></b> \<i>",
+            1,
+            &mut diagnostics,
+        );
+        let expected = InlineText {
+            rich: RichText {
+                spans: vec![
+                    Span::Text(String::from("a < b, ")),
+                    Span::Styled(
+                        Style::Bold,
+                        vec![
+                            Span::Text(String::from("bold ")),
+                            Span::Styled(
+                                Style::Color(String::from("#f00")),
+                                vec![Span::Text(String::from("red"))],
+                            ),
+                        ],
+                    ),
+                    Span::Text(String::from(" <i>")),
+                ],
+            },
+            commands: vec![InlineCommand::from_command_data(
+                11,
+                "wait",
+                vec![CommandArg::Int(1)],
+            )],
+        };
+        assert_eq!(expected, it);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_parse_markup_diagnostics() {
+        let tests = [
+            ("</b>", "closing tag \"</b>\" has no opening tag"),
+            (
+                "<b><i>a</b>",
+                "tag \"<i>\" is closed by \"</b>\", close inner tags first",
+            ),
+            ("<b>a", "unclosed tag \"<b>\""),
+            (
+                "<blink>a",
+                "unknown tag \"<blink>\", use \"\\<\" for literal \"<\"",
+            ),
+            (
+                "<b a",
+                "unterminated tag \"<b a\", use \"\\<\" for literal \"<\"",
+            ),
+        ];
+
+        for (src, expected) in &tests {
+            let mut diagnostics = Vec::new();
+            parse_text(src, 3, &mut diagnostics);
+            assert_eq!(vec![Diagnostic::warning(3, expected)], diagnostics);
+        }
+
+        let mut diagnostics = Vec::new();
+        let it = parse_text("<blink>a", 3, &mut diagnostics);
+        assert_eq!(RichText::from_plain("<blink>a"), it.rich);
+    }
+
+    #[test]
+    fn test_parse_ruby() {
+        let mut diagnostics = Vec::new();
+        let it = parse_text(
+            r"<b>{漢字|かんじ}</b>[wait 1]の\{読み}",
+            1,
+            &mut diagnostics,
+        );
+        let expected = InlineText {
+            rich: RichText {
+                spans: vec![
+                    Span::Styled(
+                        Style::Bold,
+                        vec![Span::Ruby(String::from("漢字"), String::from("かんじ"))],
+                    ),
+                    Span::Text(String::from("の{読み}")),
+                ],
+            },
+            commands: vec![InlineCommand::from_command_data(
+                2,
+                "wait",
+                vec![CommandArg::Int(1)],
+            )],
+        };
+        assert_eq!(expected, it);
+        assert!(diagnostics.is_empty());
+
+        let it = parse_text("{漢字}", 4, &mut diagnostics);
+        assert_eq!(RichText::from_plain("{漢字}"), it.rich);
+        assert_eq!(
+            vec![Diagnostic::warning(
+                4,
+                "invalid ruby \"{漢字\", expected \"{base|reading}\", use \"\\{\" for literal \"{\""
+            )],
+            diagnostics
+        );
+    }
+}
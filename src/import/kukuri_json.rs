@@ -0,0 +1,441 @@
+use super::command::is_identifier;
+use crate::core::command::{CommandArg, Float, InlineCommand};
+use crate::core::diagnostic::Diagnostic;
+use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+use crate::core::kukuri_data::KukuriData;
+use crate::core::markup::{RichText, Span, Style};
+use crate::core::meta::SceneMeta;
+use serde_json::{Map, Value};
+
+// Import kukuri's own json output back into scenes.
+//
+// {"Opening": [{"id": "Opening_1_A", "kind": 0, "args": ["Hello", "TALKER_A"]}]}
+//
+// flattened args are decoded by dialog kind, string command args are
+// identifiers if they can be, so exporting the scenes again gives the same json.
+// scene metadata is in "<name>.meta.json" of json output.
+pub struct KukuriJson;
+
+struct Decoder {
+    diagnostics: Vec<Diagnostic>,
+    // dialog texts are excluded in json output of use_l10n_output
+    is_text_excluded: bool,
+}
+
+impl KukuriJson {
+    // scene titles in json output are already qualified with namespace.
+    // return: (data, texts are excluded by use_l10n_output, diagnostics)
+    pub fn parse_with_meta(
+        content: &str,
+        meta: Option<&str>,
+    ) -> (KukuriData, bool, Vec<Diagnostic>) {
+        let mut decoder = Decoder {
+            diagnostics: Vec::new(),
+            is_text_excluded: false,
+        };
+
+        let root = match decoder.read_object(content) {
+            Some(root) => root,
+            None => return (KukuriData::new(), false, decoder.diagnostics),
+        };
+        // json output is an object of dialog arrays
+        if !root.values().all(Value::is_array) {
+            decoder.diagnostics.push(Diagnostic::warning(
+                0,
+                "json is not a json output of kukuri, ignored",
+            ));
+            return (KukuriData::new(), false, decoder.diagnostics);
+        }
+
+        let mut scenes: Vec<Scene> = root
+            .iter()
+            .map(|(title, v)| Scene {
+                title: title.clone(),
+                dialogs: decoder.dialogs(v, title),
+                ..Default::default()
+            })
+            .collect();
+
+        if let Some(meta_root) = meta.and_then(|s| decoder.read_object(s)) {
+            decoder.apply_metas(&mut scenes, meta_root);
+        }
+
+        if decoder.is_text_excluded {
+            decoder.diagnostics.push(Diagnostic::warning(
+                0,
+                "dialog texts are excluded by use_l10n_output, they are imported as empty texts",
+            ));
+        }
+
+        (
+            KukuriData::from_scenes(scenes),
+            decoder.is_text_excluded,
+            decoder.diagnostics,
+        )
+    }
+}
+
+impl Decoder {
+    fn read_object(&mut self, content: &str) -> Option<Map<String, Value>> {
+        match serde_json::from_str(content) {
+            Ok(Value::Object(root)) => Some(root),
+            Ok(_) => {
+                self.diagnostics.push(Diagnostic::error(
+                    0,
+                    "json root must be an object of scene titles",
+                ));
+                None
+            }
+            Err(e) => {
+                self.diagnostics
+                    .push(Diagnostic::error(e.line(), format!("invalid json: {}", e)));
+                None
+            }
+        }
+    }
+
+    fn warn<T: AsRef<str>>(&mut self, path: &str, message: T) {
+        self.diagnostics.push(Diagnostic::warning(
+            0,
+            format!("{}: {}", path, message.as_ref()),
+        ));
+    }
+
+    fn dialogs(&mut self, v: &Value, path: &str) -> Vec<Dialog> {
+        match v.as_array() {
+            Some(a) => a
+                .iter()
+                .enumerate()
+                .filter_map(|(i, d)| self.dialog(d, &format!("{}[{}]", path, i)))
+                .collect(),
+            None => {
+                self.warn(path, "dialogs must be an array");
+                Vec::new()
+            }
+        }
+    }
+
+    fn dialog(&mut self, v: &Value, path: &str) -> Option<Dialog> {
+        let id = v.get("id").and_then(Value::as_str);
+        let kind = match v.get("kind").and_then(Value::as_u64) {
+            Some(0) => Some(DialogKind::Dialog),
+            Some(1) => Some(DialogKind::Command),
+            Some(2) => Some(DialogKind::Choices),
+            _ => None,
+        };
+        let args = v.get("args").and_then(Value::as_array);
+        let (id, kind, args) = match (id, kind, args) {
+            (Some(id), Some(kind), Some(args)) => (id, kind, args),
+            _ => {
+                self.warn(
+                    path,
+                    "dialog needs \"id\", \"kind\" (0, 1 or 2) and \"args\"",
+                );
+                return None;
+            }
+        };
+
+        let args_path = format!("{}.args", path);
+        let bodies = match kind {
+            DialogKind::Dialog => self.dialog_args(args, &args_path)?,
+            DialogKind::Command => args
+                .iter()
+                .enumerate()
+                .filter_map(|(i, a)| self.command_arg(a, &format!("{}[{}]", args_path, i)))
+                .map(DialogBody::Arg)
+                .collect(),
+            DialogKind::Choices => args
+                .iter()
+                .enumerate()
+                .filter_map(|(i, a)| self.choice(a, &format!("{}[{}]", args_path, i)))
+                .map(DialogBody::Choice)
+                .collect(),
+        };
+
+        let inline_commands = match v.get("inline_commands").and_then(Value::as_array) {
+            Some(a) => a
+                .iter()
+                .enumerate()
+                .filter_map(|(i, ic)| {
+                    self.inline_command(ic, &format!("{}.inline_commands[{}]", path, i))
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Some(Dialog::from_dialog_data(kind, id, bodies).with_inline_commands(inline_commands))
+    }
+
+    // [text, talker], or [talker] if texts are excluded
+    fn dialog_args(&mut self, args: &[Value], path: &str) -> Option<Vec<DialogBody>> {
+        let (text, talker) = match args {
+            [text, Value::String(talker)] => (self.text(text, &format!("{}[0]", path)), talker),
+            [Value::String(talker)] => {
+                self.is_text_excluded = true;
+                (DialogBody::gen_text(""), talker)
+            }
+            _ => {
+                self.warn(path, "dialog args must be [text, talker]");
+                return None;
+            }
+        };
+        Some(vec![text, DialogBody::gen_text(talker)])
+    }
+
+    fn text(&mut self, v: &Value, path: &str) -> DialogBody {
+        match v {
+            Value::String(s) => DialogBody::gen_text(s),
+            Value::Array(a) => DialogBody::from_rich_text(RichText {
+                spans: self.spans(a, path),
+            }),
+            _ => {
+                self.warn(path, "dialog text must be a string or spans");
+                DialogBody::gen_text("")
+            }
+        }
+    }
+
+    fn spans(&mut self, a: &[Value], path: &str) -> Vec<Span> {
+        a.iter()
+            .enumerate()
+            .flat_map(|(i, v)| self.span(v, &format!("{}[{}]", path, i)))
+            .collect()
+    }
+
+    // a span of unknown tag is replaced with its children
+    fn span(&mut self, v: &Value, path: &str) -> Vec<Span> {
+        if let Value::String(s) = v {
+            return vec![Span::Text(s.clone())];
+        }
+
+        let tag = v.get("tag").and_then(Value::as_str);
+        let value = v.get("value").and_then(Value::as_str);
+        let children = v.get("children").and_then(Value::as_array);
+        let (tag, children) = match (tag, children) {
+            (Some(tag), Some(children)) => (tag, children),
+            _ => {
+                self.warn(path, "span must be a string or {\"tag\", \"children\"}");
+                return Vec::new();
+            }
+        };
+        let children = self.spans(children, &format!("{}.children", path));
+
+        match (tag, value) {
+            ("ruby", Some(reading)) => {
+                let base = RichText { spans: children }.plain_text();
+                vec![Span::Ruby(base, String::from(reading))]
+            }
+            _ => match Style::from_name_value(tag, value) {
+                Some(style) => vec![Span::Styled(style, children)],
+                None => {
+                    self.warn(path, format!("unknown tag \"{}\" is removed", tag));
+                    children
+                }
+            },
+        }
+    }
+
+    fn command_arg(&mut self, v: &Value, path: &str) -> Option<CommandArg> {
+        match v {
+            Value::String(s) if is_identifier(s) => Some(CommandArg::Ident(s.clone())),
+            Value::String(s) => Some(CommandArg::Str(s.clone())),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => Some(CommandArg::Int(i)),
+                None => n.as_f64().map(|f| CommandArg::Float(Float(f))),
+            },
+            Value::Bool(b) => Some(CommandArg::Bool(*b)),
+            Value::Object(m) if m.len() == 1 => {
+                let (k, v) = m.iter().next()?;
+                let value = self.command_arg(v, &format!("{}.{}", path, k))?;
+                Some(CommandArg::Named(k.clone(), Box::new(value)))
+            }
+            _ => {
+                self.warn(path, "unsupported command argument is dropped");
+                None
+            }
+        }
+    }
+
+    fn choice(&mut self, v: &Value, path: &str) -> Option<ChoiceData> {
+        let id = match v.get("id").and_then(Value::as_str) {
+            Some(id) => id,
+            None => {
+                self.warn(path, "choice needs \"id\"");
+                return None;
+            }
+        };
+        // label is excluded in json output of use_l10n_output
+        let label = v.get("label").and_then(Value::as_str).unwrap_or("");
+
+        let mut cd = ChoiceData::from_texts(id, label);
+        if let Some(dialogs) = v.get("dialogs") {
+            cd.dialogs = self.dialogs(dialogs, &format!("{}.dialogs", path));
+        }
+        Some(cd)
+    }
+
+    fn inline_command(&mut self, v: &Value, path: &str) -> Option<InlineCommand> {
+        let offset = v.get("offset").and_then(Value::as_u64);
+        let id = v.get("id").and_then(Value::as_str);
+        let (offset, id) = match (offset, id) {
+            (Some(offset), Some(id)) => (offset as usize, id),
+            _ => {
+                self.warn(path, "inline command needs \"offset\" and \"id\"");
+                return None;
+            }
+        };
+
+        let args = match v.get("args").and_then(Value::as_array) {
+            Some(a) => a
+                .iter()
+                .enumerate()
+                .filter_map(|(i, a)| self.command_arg(a, &format!("{}.args[{}]", path, i)))
+                .collect(),
+            None => Vec::new(),
+        };
+        Some(InlineCommand::from_command_data(offset, id, args))
+    }
+
+    fn apply_metas(&mut self, scenes: &mut [Scene], metas: Map<String, Value>) {
+        for (title, v) in metas {
+            let meta: SceneMeta = match serde_json::from_value(v) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    self.warn(&title, format!("invalid scene metadata: {}", e));
+                    continue;
+                }
+            };
+            match scenes.iter_mut().find(|sc| sc.title == title) {
+                Some(sc) => sc.meta = meta,
+                None => self.warn(&title, "metadata of unknown scene is ignored"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KukuriJson;
+    use crate::core::command::{CommandArg, Float, InlineCommand};
+    use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scenes};
+    use crate::core::kukuri_data::KukuriData;
+    use crate::core::markup::{RichText, Span, Style};
+    use crate::core::meta::MetaValue;
+    use crate::export::json::Json;
+
+    fn scenes_of(data: KukuriData) -> Scenes {
+        match data {
+            KukuriData::Scenes(scenes) => scenes
+                .into_iter()
+                .map(|sc| (sc.title, sc.dialogs))
+                .collect(),
+            _ => panic!("scenes are expected"),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rich = RichText {
+            spans: vec![
+                Span::Text(String::from("Hi, ")),
+                Span::Styled(
+                    Style::Color(String::from("#ff0000")),
+                    vec![Span::Styled(
+                        Style::Bold,
+                        vec![Span::Text(String::from("you"))],
+                    )],
+                ),
+                Span::Ruby(String::from("漢字"), String::from("かんじ")),
+            ],
+        };
+        let mut cd = ChoiceData::from_texts("Opening_2_C1L1", "Go");
+        cd.dialogs = vec![Dialog::from_dialog_data(
+            DialogKind::Command,
+            "jump",
+            vec![DialogBody::Arg(CommandArg::gen_ident("Shop"))],
+        )];
+
+        let mut scenes = Scenes::new();
+        scenes.insert(
+            String::from("Opening"),
+            vec![
+                Dialog::from_dialog_data(
+                    DialogKind::Command,
+                    "bgm",
+                    vec![
+                        DialogBody::Arg(CommandArg::gen_str("bgm/daily.ogg")),
+                        DialogBody::Arg(CommandArg::Float(Float(0.5))),
+                        DialogBody::Arg(CommandArg::Named(
+                            String::from("loop"),
+                            Box::new(CommandArg::Bool(true)),
+                        )),
+                    ],
+                ),
+                Dialog::from_dialog_data(
+                    DialogKind::Dialog,
+                    "Opening_1_A",
+                    vec![
+                        DialogBody::from_rich_text(rich),
+                        DialogBody::gen_text("TALKER_A"),
+                    ],
+                )
+                .with_inline_commands(vec![InlineCommand::from_command_data(
+                    4,
+                    "wait",
+                    vec![CommandArg::Int(2)],
+                )]),
+                Dialog::from_dialog_data(
+                    DialogKind::Choices,
+                    "Opening_2_C1",
+                    vec![DialogBody::Choice(cd)],
+                ),
+            ],
+        );
+
+        let json = Json::export_string(&scenes, true);
+        let (data, is_text_excluded, diagnostics) = KukuriJson::parse_with_meta(&json, None);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert!(!is_text_excluded);
+        let imported = scenes_of(data);
+        assert_eq!(scenes, imported);
+        assert_eq!(json, Json::export_string(&imported, true));
+    }
+
+    #[test]
+    fn test_parse_with_meta() {
+        let src = r#"{"Opening": [{"id": "Opening_1_A", "kind": 0, "args": ["TALKER_A"]}]}"#;
+        let meta = r#"{"Opening": {"chapter": 2, "tags": ["intro"]}, "Gone": {"x": 1}}"#;
+        let (data, is_text_excluded, diagnostics) = KukuriJson::parse_with_meta(src, Some(meta));
+        assert!(is_text_excluded);
+
+        let sc = match data {
+            KukuriData::Scenes(scenes) => scenes.into_iter().next().unwrap(),
+            _ => panic!("scenes are expected"),
+        };
+        assert_eq!(
+            vec![DialogBody::gen_text(""), DialogBody::gen_text("TALKER_A")],
+            sc.dialogs[0].args
+        );
+        assert_eq!(MetaValue::Int(2), sc.meta["chapter"]);
+        assert_eq!(
+            vec![
+                "0: warning: Gone: metadata of unknown scene is ignored",
+                "0: warning: dialog texts are excluded by use_l10n_output, they are imported as empty texts",
+            ],
+            diagnostics
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+        );
+
+        // a meta file or other json is not a script
+        for src in [meta, r#"{"name": "kukuri", "version": "1.0"}"#] {
+            let (data, _, diagnostics) = KukuriJson::parse_with_meta(src, None);
+            assert_eq!(KukuriData::new(), data);
+            assert_eq!(
+                "0: warning: json is not a json output of kukuri, ignored",
+                diagnostics[0].to_string()
+            );
+        }
+    }
+}
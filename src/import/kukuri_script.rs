@@ -1,160 +1,413 @@
+use super::include::Includer;
+use super::macros::{self, MacroCall, Macros};
+use super::{command, comment, escape, inline};
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
 use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
 use crate::core::kukuri_data::KukuriData;
+use crate::core::meta::SceneMeta;
+use crate::core::namespace;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::Path;
 
 pub struct KukuriScript;
 
 impl KukuriScript {
-    pub fn parse(content: &str) -> KukuriData {
+    pub fn parse_with_diagnostics(content: &str) -> (KukuriData, Vec<Diagnostic>) {
+        Self::parse_with_namespace(content, "")
+    }
+
+    // scene titles are qualified with namespace,
+    // unless "namespace" header key is given.
+    // content is parsed without disk access, so `@include` is an error.
+    // use parse_source() with a loader to include files.
+    pub fn parse_with_namespace(content: &str, namespace: &str) -> (KukuriData, Vec<Diagnostic>) {
+        Self::parse_source(content, None, namespace, &Macros::new(), &|_| {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "no file loader for this source",
+            ))
+        })
+    }
+
+    // path: source file of content, for resolving `@include` paths.
+    // macros: project macros, overridden by macros in scene header.
+    // loader: reads an included file.
+    pub fn parse_source(
+        content: &str,
+        path: Option<&Path>,
+        namespace: &str,
+        macros: &Macros,
+        loader: &dyn Fn(&Path) -> io::Result<String>,
+    ) -> (KukuriData, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
         let mut sp_data = SceneProcessData::new();
+        sp_data.file_namespace = String::from(namespace);
         let mut scenes: Vec<Scene> = Vec::new();
         // current scene
         let mut sc = Scene::new();
 
-        for full_line in content.lines() {
-            sp_data.line_count_up();
-            let line = Self::trim_comment(full_line);
+        let lines = Includer::new(loader).expand(content, path, &mut diagnostics);
+        for (loc, line) in lines {
+            sp_data.line_cnt = loc.line;
+            sp_data.path = loc.path;
+
+            let diag_cnt = diagnostics.len();
+            Self::macro_line_process(
+                &line,
+                macros,
+                &mut Vec::new(),
+                &mut sp_data,
+                &mut scenes,
+                &mut sc,
+                &mut diagnostics,
+            );
+
+            // diagnostics in an included file
+            if !sp_data.path.is_empty() {
+                for d in &mut diagnostics[diag_cnt..] {
+                    d.path = sp_data.path.clone();
+                }
+            }
+            // Self::debug_print(line, &sp_data);
+        }
+
+        if !sc.dialogs.is_empty() {
+            Self::scene_end_process(&mut sp_data, &mut scenes, &mut sc);
+        }
 
-            if line.trim().is_empty() {
-                continue;
-            };
+        (KukuriData::from_scenes(scenes), diagnostics)
+    }
 
-            if sp_data.is_header {
-                Self::header_process(line, &mut sp_data, &mut sc);
-                continue;
+    // expand a macro call line recursively, or process a line as is.
+    // expanded lines are located at the call site.
+    fn macro_line_process(
+        line: &str,
+        macros: &Macros,
+        // macros being expanded, for recursion detection
+        stack: &mut Vec<String>,
+        sp_data: &mut SceneProcessData,
+        scenes: &mut Vec<Scene>,
+        sc: &mut Scene,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        // argument diagnostics are kept only for a defined macro
+        let mut call_diagnostics = Vec::new();
+        let call = if sp_data.is_header {
+            None
+        } else {
+            MacroCall::parse(line, sp_data.line_cnt, &mut call_diagnostics)
+        };
+        // scene macros take precedence,
+        // and "@word" which is not a macro is a normal line
+        let found = call.and_then(|c| {
+            let def = sp_data
+                .meta_data
+                .macros
+                .get(c.name)
+                .or_else(|| macros.get(c.name))?;
+            Some((c, def))
+        });
+        let (call, def) = match found {
+            Some(x) => x,
+            None => return Self::line_process(line, sp_data, scenes, sc, diagnostics),
+        };
+        diagnostics.append(&mut call_diagnostics);
+        let def_loc = def.loc_str();
+
+        if stack.iter().any(|name| name == call.name) {
+            // reported once per macro, the chain already shows where it is expanded
+            if sp_data.recursive_macros.insert(String::from(call.name)) {
+                diagnostics.push(Diagnostic::error(
+                    sp_data.line_cnt,
+                    format!(
+                        "recursive macro: {} -> {} (defined at {})",
+                        stack.join(" -> "),
+                        call.name,
+                        def_loc
+                    ),
+                ));
             }
+            return;
+        }
 
-            if Self::is_header_symbol(line) {
-                sp_data.is_header = true;
-                continue;
+        let body = match def.expand(&call) {
+            Ok(b) => b,
+            Err(errors) => {
+                for e in errors {
+                    diagnostics.push(Diagnostic::error(
+                        sp_data.line_cnt,
+                        format!("{} (defined at {})", e, def_loc),
+                    ));
+                }
+                return;
             }
+        };
 
-            if Self::is_scene_end_symbol(line) {
-                // scene end
-                Self::scene_end_process(&mut sp_data, &mut scenes, &mut sc);
-                continue;
+        stack.push(String::from(call.name));
+        for (_, l) in Self::logical_lines(&body) {
+            let diag_cnt = diagnostics.len();
+            let l = format!("{}{}", call.indent, l);
+            Self::macro_line_process(&l, macros, stack, sp_data, scenes, sc, diagnostics);
+
+            // recursion error already has the whole chain
+            let inner = diagnostics[diag_cnt..]
+                .iter_mut()
+                .filter(|d| !d.message.starts_with("recursive macro:"));
+            for d in inner {
+                d.message.push_str(&format!(
+                    " (in macro `{}` defined at {})",
+                    call.name, def_loc
+                ));
             }
+        }
+        stack.pop();
+    }
 
-            let kind = Self::parse_dialog_kind(line);
+    fn line_process(
+        line: &str,
+        sp_data: &mut SceneProcessData,
+        scenes: &mut Vec<Scene>,
+        sc: &mut Scene,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if line.trim().is_empty() {
+            return;
+        };
 
-            let indent_lv = sp_data.parse_indent_lv(line);
+        if sp_data.is_header {
+            Self::header_process(line, sp_data, sc, diagnostics);
+            return;
+        }
 
-            match kind {
-                DialogKind::Dialog => {
-                    if indent_lv < sp_data.nest_lv {
-                        sp_data.nest_lv_count_down(indent_lv);
-                        sp_data.truncate_idxs(indent_lv);
-                    }
+        if Self::is_header_symbol(line) {
+            sp_data.is_header = true;
+            sc.loc = sp_data.loc();
+            return;
+        }
 
-                    sp_data.dialog_count_up();
+        if Self::is_scene_end_symbol(line) {
+            // scene end
+            Self::scene_end_process(sp_data, scenes, sc);
+            return;
+        }
+
+        let kind = Self::parse_dialog_kind(line);
 
-                    // dialog push
-                    let target_dialogs = sc.inner_dialogs_as_mut(&mut sp_data.inner_scene_idxs());
-                    target_dialogs.push(Self::dialog_process(line, &sp_data));
+        let indent_lv = sp_data.parse_indent_lv(line);
+
+        match kind {
+            DialogKind::Dialog => {
+                if indent_lv < sp_data.nest_lv {
+                    sp_data.nest_lv_count_down(indent_lv);
+                    sp_data.truncate_idxs(indent_lv);
+                }
+
+                sp_data.dialog_count_up();
+
+                // dialog push
+                let target_dialogs = sc.inner_dialogs_as_mut(&mut sp_data.inner_scene_idxs());
+                target_dialogs.push(Self::dialog_process(line, sp_data, diagnostics));
+            }
+            DialogKind::Command => {
+                if indent_lv < sp_data.nest_lv {
+                    sp_data.nest_lv_count_down(indent_lv);
+                    sp_data.truncate_idxs(indent_lv);
                 }
-                DialogKind::Command => {
-                    if indent_lv < sp_data.nest_lv {
-                        sp_data.nest_lv_count_down(indent_lv);
-                        sp_data.truncate_idxs(indent_lv);
-                    }
 
-                    sp_data.dialog_count_up_without_did_idx();
+                sp_data.dialog_count_up_without_did_idx();
 
-                    // command push
-                    let target_dialogs = sc.inner_dialogs_as_mut(&mut sp_data.inner_scene_idxs());
-                    target_dialogs.push(Self::command_process(line));
+                // command push
+                let target_dialogs = sc.inner_dialogs_as_mut(&mut sp_data.inner_scene_idxs());
+                target_dialogs.push(Self::command_process(line, sp_data, diagnostics));
+            }
+            DialogKind::Choices => {
+                let is_choices_parent = indent_lv >= sp_data.nest_lv;
+                if is_choices_parent {
+                    sp_data.dialog_count_up();
+                    sp_data.nest_lv_count_up();
+                } else {
+                    sp_data.truncate_idxs(indent_lv);
                 }
-                DialogKind::Choices => {
-                    let is_choices_parent = indent_lv >= sp_data.nest_lv;
-                    if is_choices_parent {
-                        sp_data.dialog_count_up();
-                        sp_data.nest_lv_count_up();
-                    } else {
-                        sp_data.truncate_idxs(indent_lv);
-                    }
-
-                    sp_data.choice_idx_count_up();
-
-                    let mut idxs = sp_data.inner_scene_idxs();
-                    if is_choices_parent {
-                        // choices parent push
-                        let target_dialogs = sc.inner_parent_dialogs_as_mut(&mut idxs.clone());
-                        target_dialogs.push(Self::choices_parent_process(&sp_data));
-                    }
-
-                    // choice push
-                    let target_choice = sc.inner_choices_as_mut(&mut idxs);
-                    let cd = Self::choices_child_process(line, &sp_data);
-                    target_choice.args.push(DialogBody::Choice(cd));
+
+                sp_data.choice_idx_count_up();
+
+                let mut idxs = sp_data.inner_scene_idxs();
+                if is_choices_parent {
+                    // choices parent push
+                    let target_dialogs = sc.inner_parent_dialogs_as_mut(&mut idxs.clone());
+                    target_dialogs.push(Self::choices_parent_process(sp_data));
                 }
+
+                // choice push
+                let target_choice = sc.inner_choices_as_mut(&mut idxs);
+                let cd = Self::choices_child_process(line, sp_data, diagnostics);
+                target_choice.args.push(DialogBody::Choice(cd));
             }
+        }
+    }
 
-            // Self::debug_print(line, &sp_data);
+    // Split content into logical lines.
+    // A line ending with an unescaped backslash continues on the next line,
+    // and those lines are joined with a real line break.
+    // Indentation of continuation lines is discarded,
+    // so they never affect choice nesting.
+    // return: [(first physical line number, comment trimmed line)...]
+    pub fn logical_lines(content: &str) -> Vec<(usize, String)> {
+        let mut lines = Vec::new();
+        let mut pending: Option<(usize, String)> = None;
+
+        for (i, full_line) in content.lines().enumerate() {
+            // "\#" is not a comment symbol
+            let line = comment::trim_line(full_line, '#', &[]);
+
+            let (line_no, mut joined) = match pending.take() {
+                Some((n, mut s)) => {
+                    s.push('\n');
+                    s.push_str(line.trim_start());
+                    (n, s)
+                }
+                None => (i + 1, String::from(line)),
+            };
+
+            if Self::is_continued_line(&joined) {
+                // remove continuation backslash and whitespaces before it
+                let l = joined.trim_end().len() - 1;
+                joined.truncate(l);
+                let l = joined.trim_end().len();
+                joined.truncate(l);
+                pending = Some((line_no, joined));
+            } else {
+                lines.push((line_no, joined));
+            }
         }
 
-        if !sc.dialogs.is_empty() {
-            Self::scene_end_process(&mut sp_data, &mut scenes, &mut sc);
+        if let Some(p) = pending {
+            lines.push(p);
         }
 
-        KukuriData::from_scenes(scenes)
+        lines
+    }
+
+    fn is_continued_line(line: &str) -> bool {
+        // odd number of trailing backslashes: last one is not escaped
+        let bs_cnt = line
+            .trim_end()
+            .chars()
+            .rev()
+            .take_while(|&c| c == '\\')
+            .count();
+        bs_cnt % 2 == 1
     }
 
-    fn header_process(line: &str, sp_data: &mut SceneProcessData, sc: &mut Scene) {
+    fn header_process(
+        line: &str,
+        sp_data: &mut SceneProcessData,
+        sc: &mut Scene,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
         // if line text is "+++", end header section.
         if Self::is_header_symbol(line) {
             let s = &sp_data.header_str;
             sp_data.meta_data.parse(s);
             sp_data.is_header = false;
 
+            for (name, loc) in &sp_data.macro_locs {
+                if let Some(def) = sp_data.meta_data.macros.get_mut(name) {
+                    def.loc = loc.clone();
+                }
+            }
+
+            // "." separates namespaces, use "namespace" key instead
+            if sp_data.meta_data.title.contains('.') {
+                let escaped = namespace::escape_name(&sp_data.meta_data.title);
+                diagnostics.push(Diagnostic::warning(
+                    sp_data.line_cnt,
+                    format!(
+                        "scene title `{}` contains \".\", renamed to `{}`",
+                        sp_data.meta_data.title, escaped
+                    ),
+                ));
+                sp_data.meta_data.title = escaped;
+            }
             if !sp_data.meta_data.title.is_empty() {
-                sc.title = sp_data.meta_data.title.clone();
+                sc.title = sp_data.qualified_title();
             }
+            sc.meta = sp_data.meta_data.extra.clone();
             return;
         }
 
+        if let Some(name) = macros::def_name(line) {
+            let loc = sp_data.loc();
+            sp_data.macro_locs.push((String::from(name), loc));
+        }
         sp_data.header_str.push_str(line);
         sp_data.header_str.push('\n');
     }
 
-    fn dialog_process(line: &str, sp_data: &SceneProcessData) -> Dialog {
-        let mut iter = line.splitn(2, ':');
-
-        let s0 = iter.next().unwrap_or("").trim();
-        let s1 = iter.next().unwrap_or("").trim();
+    fn dialog_process(
+        line: &str,
+        sp_data: &SceneProcessData,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Dialog {
+        // "\:" is not a talker separator
+        let (s0, s1) = match escape::find_unescaped(line, ':') {
+            Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+            None => (line.trim(), ""),
+        };
 
         let talker = if !s1.is_empty() { s0 } else { "unknown" };
+        let text = if !s1.is_empty() { s1 } else { s0 };
+        let it = inline::parse_text(text, sp_data.line_cnt, diagnostics);
         let body = vec![
-            DialogBody::gen_text(if !s1.is_empty() { s1 } else { s0 }),
+            DialogBody::from_rich_text(it.rich),
             DialogBody::gen_text(format!("TALKER_{}", talker)),
         ];
 
         let id = format!("{}_{}", sp_data.gen_dialog_label(), talker);
 
         Dialog::from_dialog_data(DialogKind::Dialog, id, body)
+            .with_inline_commands(it.commands)
+            .with_loc(sp_data.loc())
     }
 
-    fn command_process(line: &str) -> Dialog {
-        let cmd_str = line.splitn(2, '$').nth(1).unwrap_or("").trim();
+    fn command_process(
+        line: &str,
+        sp_data: &SceneProcessData,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Dialog {
+        let cmd_str = line.split_once('$').map_or("", |(_, s)| s).trim();
 
-        let mut iter = cmd_str.split_whitespace();
+        let (id, args_str) = match cmd_str.find(char::is_whitespace) {
+            Some(i) => cmd_str.split_at(i),
+            None => (cmd_str, ""),
+        };
 
-        let id = String::from(iter.nth(0).unwrap_or(""));
-        let args: Vec<DialogBody> = iter.map(|s| DialogBody::gen_text(s)).collect();
+        let args: Vec<DialogBody> = command::parse_args(args_str, sp_data.line_cnt, diagnostics)
+            .into_iter()
+            .map(DialogBody::Arg)
+            .collect();
 
-        Dialog::from_dialog_data(DialogKind::Command, id, args)
+        Dialog::from_dialog_data(DialogKind::Command, id, args).with_loc(sp_data.loc())
     }
 
     fn choices_parent_process(sp_data: &SceneProcessData) -> Dialog {
         let label = sp_data.gen_dialog_label();
         let s = label.rsplitn(2, 'L').last().unwrap_or("Choices");
-        Dialog::from_dialog_data(DialogKind::Choices, s, Vec::new())
+        Dialog::from_dialog_data(DialogKind::Choices, s, Vec::new()).with_loc(sp_data.loc())
     }
 
-    fn choices_child_process(line: &str, sp_data: &SceneProcessData) -> ChoiceData {
+    fn choices_child_process(
+        line: &str,
+        sp_data: &SceneProcessData,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> ChoiceData {
         let (_, label) = line.trim_start().split_at(1);
         let id = sp_data.gen_dialog_label();
+        let label = escape::unescape(label.trim(), sp_data.line_cnt, diagnostics);
 
-        ChoiceData::from_texts(id, label.trim())
+        ChoiceData::from_texts(id, label).with_loc(sp_data.loc())
     }
 
     fn scene_end_process(
@@ -172,7 +425,7 @@ impl KukuriScript {
         let mut chars = line.trim_start().chars();
         for _ in 0..3 {
             match chars.next() {
-                Some(c) if c == '+' => {}
+                Some('+') => {}
                 _ => {
                     is_header = false;
                     break;
@@ -187,7 +440,7 @@ impl KukuriScript {
         let mut chars = line.trim_start().chars();
         for _ in 0..3 {
             match chars.next() {
-                Some(c) if c == '=' => {}
+                Some('=') => {}
                 _ => {
                     is_end_symbol = false;
                     break;
@@ -197,29 +450,6 @@ impl KukuriScript {
         is_end_symbol
     }
 
-    fn trim_comment(line: &str) -> &str {
-        let mut bs_i = 0;
-        let symbol_idx = line.char_indices().find(|&(i, c)| {
-            // match backslash char
-            if c == '\\' {
-                bs_i = i;
-            }
-
-            let mut is_comment = c == '#';
-
-            if bs_i + 1 == i {
-                is_comment = false;
-            }
-
-            is_comment
-        });
-
-        match symbol_idx {
-            Some((i, _)) => line.split_at(i).0,
-            None => line,
-        }
-    }
-
     fn parse_dialog_kind(line: &str) -> DialogKind {
         let mut c_iter = line.trim_start().chars();
         // '\0' is placeholder char
@@ -252,6 +482,8 @@ impl KukuriScript {
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
 struct SceneProcessData {
     line_cnt: usize,
+    // included file path of current line, empty for the parsed file
+    path: String,
     indent_cnts: Vec<usize>,
     // only using for dialog_id generate.
     dialog_id_idxs: Vec<usize>,
@@ -261,6 +493,14 @@ struct SceneProcessData {
     pub choice_idxs: Vec<(usize, usize)>,
     pub header_str: String,
     pub meta_data: MetaData,
+    // "[macros.<name>]" lines in header
+    #[serde(skip)]
+    pub macro_locs: Vec<(String, SourceLoc)>,
+    // namespace of input file, kept over scenes
+    pub file_namespace: String,
+    // reported recursive macros, kept over scenes
+    #[serde(skip)]
+    pub recursive_macros: BTreeSet<String>,
 }
 
 impl Default for SceneProcessData {
@@ -270,11 +510,15 @@ impl Default for SceneProcessData {
             dialog_id_idxs: Vec::new(),
             nest_lv: 0,
             line_cnt: 0,
+            path: String::new(),
             is_header: false,
             indent_cnts: Vec::new(),
             choice_idxs: Vec::new(),
             header_str: String::new(),
             meta_data: MetaData::new(),
+            macro_locs: Vec::new(),
+            file_namespace: String::new(),
+            recursive_macros: BTreeSet::new(),
         }
     }
 }
@@ -285,7 +529,30 @@ impl SceneProcessData {
     }
 
     pub fn reset(&mut self) {
-        *self = Self::new()
+        let file_namespace = std::mem::take(&mut self.file_namespace);
+        let recursive_macros = std::mem::take(&mut self.recursive_macros);
+        *self = Self {
+            file_namespace,
+            recursive_macros,
+            ..Self::new()
+        }
+    }
+
+    pub fn loc(&self) -> SourceLoc {
+        SourceLoc {
+            path: self.path.clone(),
+            line: self.line_cnt,
+        }
+    }
+
+    // e.g. "chapter1.intro.Opening"
+    pub fn qualified_title(&self) -> String {
+        let ns = if self.meta_data.namespace.is_empty() {
+            &self.file_namespace
+        } else {
+            &self.meta_data.namespace
+        };
+        namespace::qualify(ns, &self.meta_data.title)
     }
 
     pub fn dialog_count_up(&mut self) {
@@ -353,10 +620,6 @@ impl SceneProcessData {
         self.nest_lv = indent_lv;
     }
 
-    pub fn line_count_up(&mut self) {
-        self.line_cnt += 1;
-    }
-
     // return: [di, ci, li, di, ci, li...] if nest_lv > 0
     //       : [] if nest_lv == 0
     // where : di = dialog_idx, ci = choice_idx, li = choice_label_idx
@@ -392,7 +655,7 @@ impl SceneProcessData {
     }
 
     pub fn gen_dialog_label(&self) -> String {
-        let mut s = self.meta_data.title.clone();
+        let mut s = self.qualified_title();
 
         (0..self.dialog_id_idxs.len()).for_each(|i| {
             let dialog_idx = self.dialog_id_idxs[i];
@@ -419,12 +682,22 @@ impl SceneProcessData {
 #[serde(default)]
 pub struct MetaData {
     pub title: String,
+    // overrides namespace from file path
+    pub namespace: String,
+    // scene local macros
+    pub macros: Macros,
+    // any other header keys
+    #[serde(flatten)]
+    pub extra: SceneMeta,
 }
 
 impl Default for MetaData {
     fn default() -> Self {
         Self {
             title: String::from("UnknownScene"),
+            namespace: String::new(),
+            macros: Macros::new(),
+            extra: SceneMeta::new(),
         }
     }
 }
@@ -449,8 +722,15 @@ impl MetaData {
 #[cfg(test)]
 mod tests {
     use super::{KukuriScript, SceneProcessData};
-    use crate::core::dialog::{Dialog, DialogBody, DialogKind, Scene};
+    use crate::core::command::{CommandArg, Float, InlineCommand};
+    use crate::core::diagnostic::{Diagnostic, SourceLoc};
+    use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
     use crate::core::kukuri_data::KukuriData;
+    use crate::core::meta::MetaValue;
+    use crate::import::macros::{MacroDef, Macros};
+    use std::collections::BTreeMap;
+    use std::io;
+    use std::path::Path;
 
     #[test]
     fn test_parse_dialog_kind() {
@@ -472,19 +752,39 @@ mod tests {
     }
 
     #[test]
-    fn test_trim_comment() {
+    fn test_logical_lines() {
+        let src = "\
+A: first line \\
+   second line
+B: single line
+C: escaped backslash \\\\
+* choice \\
+  continued # comment \\
+  A: inner";
+
+        let expected = vec![
+            (1, String::from("A: first line\nsecond line")),
+            (3, String::from("B: single line")),
+            (4, String::from("C: escaped backslash \\\\")),
+            (5, String::from("* choice\ncontinued ")),
+            (7, String::from("  A: inner")),
+        ];
+
+        assert_eq!(expected, KukuriScript::logical_lines(src));
+    }
+
+    #[test]
+    fn test_is_continued_line() {
         let tests = [
-            ("# head of line comment", ""),
-            ("end of line comment# comment", "end of line comment"),
-            ("middle of # line comment", "middle of "),
-            ("A: comment nothing", "A: comment nothing"),
-            ("comment nothing", "comment nothing"),
-            ("# $ cmd comment test", ""),
-            ("$ cmd comment # test", "$ cmd comment "),
+            ("A: text \\", true),
+            ("A: text \\  ", true),
+            ("A: text \\\\", false),
+            ("A: text \\\\\\", true),
+            ("A: text", false),
         ];
 
-        for &(src, ref expected) in &tests {
-            assert_eq!(expected, &KukuriScript::trim_comment(src));
+        for &(src, expected) in &tests {
+            assert_eq!(expected, KukuriScript::is_continued_line(src));
         }
     }
 
@@ -691,8 +991,437 @@ B: Are tests passssssssed?
                 ],
             ),
         ];
-        let expected = KukuriData::from_scenes(vec![sc]);
+        let expected = (KukuriData::from_scenes(vec![sc]), Vec::new());
+
+        let (data, diagnostics) = KukuriScript::parse_with_diagnostics(kkr_src);
+        assert_eq!(expected, (data.without_locs(), diagnostics))
+    }
+
+    #[test]
+    fn test_parse_header_meta() {
+        let kkr_src = r#"
++++
+title = "TestDialog"
+chapter = 2
+tags = ["intro", "night"]
+bgm = "theme.ogg" # comment
+
+[custom]
+weather = "rain"
++++
+A: Hello
+"#;
+        let mut custom = BTreeMap::new();
+        custom.insert(
+            String::from("weather"),
+            MetaValue::Str(String::from("rain")),
+        );
+
+        let mut sc = Scene::new();
+        sc.title = String::from("TestDialog");
+        sc.meta.insert(String::from("chapter"), MetaValue::Int(2));
+        sc.meta.insert(
+            String::from("tags"),
+            MetaValue::Array(vec![
+                MetaValue::Str(String::from("intro")),
+                MetaValue::Str(String::from("night")),
+            ]),
+        );
+        sc.meta.insert(
+            String::from("bgm"),
+            MetaValue::Str(String::from("theme.ogg")),
+        );
+        sc.meta
+            .insert(String::from("custom"), MetaValue::Table(custom));
+        sc.dialogs = vec![Dialog::from_dialog_data(
+            DialogKind::Dialog,
+            "TestDialog_1_A",
+            vec![
+                DialogBody::gen_text("Hello"),
+                DialogBody::gen_text("TALKER_A"),
+            ],
+        )];
+
+        let (data, diagnostics) = KukuriScript::parse_with_diagnostics(kkr_src);
+        assert_eq!(
+            KukuriData::from_scenes(vec![sc]),
+            data.clone().without_locs()
+        );
+        assert!(diagnostics.is_empty());
+        if let KukuriData::Scenes(scenes) = data {
+            assert_eq!(2, scenes[0].loc.line);
+        }
+    }
 
-        assert_eq!(expected, KukuriScript::parse(kkr_src))
+    #[test]
+    fn test_parse_with_namespace() {
+        let kkr_src = r#"
++++
+title = "Opening"
++++
+A: Hello
+===
++++
+title = "Ending"
+namespace = "common"
++++
+A: Bye
+"#;
+        let titles_and_ids = |data: KukuriData| match data {
+            KukuriData::Scenes(scenes) => scenes
+                .iter()
+                .map(|sc| (sc.title.clone(), sc.dialogs[0].id.clone()))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let (data, _) = KukuriScript::parse_with_namespace(kkr_src, "chapter1.intro");
+        assert_eq!(
+            vec![
+                (
+                    String::from("chapter1.intro.Opening"),
+                    String::from("chapter1.intro.Opening_1_A")
+                ),
+                (
+                    String::from("common.Ending"),
+                    String::from("common.Ending_1_A")
+                ),
+            ],
+            titles_and_ids(data)
+        );
+
+        let (data, diagnostics) =
+            KukuriScript::parse_with_namespace("+++\ntitle = \"Act1.Opening\"\n+++\nA: Hi\n", "");
+        assert_eq!(
+            vec![(
+                String::from("Act1_Opening"),
+                String::from("Act1_Opening_1_A")
+            )],
+            titles_and_ids(data)
+        );
+        assert_eq!(
+            vec![Diagnostic::warning(
+                3,
+                "scene title `Act1.Opening` contains \".\", renamed to `Act1_Opening`"
+            )],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_parse_include() {
+        let kkr_src = r#"
++++
+title = "Shop"
++++
+A: Hi
+* Buy
+    @include "fragments/menu.kkr"
+"#;
+        let loader = |p: &Path| match p.to_str() {
+            Some("story/fragments/menu.kkr") => Ok(String::from("B: Menu\\q\nC: Bye")),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+        };
+
+        let (data, diagnostics) = KukuriScript::parse_source(
+            kkr_src,
+            Some(Path::new("story/shop.kkr")),
+            "",
+            &Macros::new(),
+            &loader,
+        );
+
+        let mut locs = Vec::new();
+        let mut ids = Vec::new();
+        if let KukuriData::Scenes(scenes) = &data {
+            scenes[0].walk_dialogs(|d| {
+                ids.push(d.id.clone());
+                locs.push((d.loc.path.clone(), d.loc.line));
+            });
+        }
+
+        assert_eq!(
+            vec![
+                "Shop_1_A",
+                "Shop_2_C1",
+                "Shop_2_C1L1_1_B",
+                "Shop_2_C1L1_2_C"
+            ],
+            ids
+        );
+        let menu_path = String::from("story/fragments/menu.kkr");
+        assert_eq!(
+            vec![
+                (String::new(), 5),
+                (String::new(), 6),
+                (menu_path.clone(), 1),
+                (menu_path.clone(), 2),
+            ],
+            locs
+        );
+        assert_eq!(
+            vec![
+                Diagnostic::warning(1, "unknown escape sequence \"\\q\"").at(&SourceLoc {
+                    path: menu_path,
+                    line: 1
+                })
+            ],
+            diagnostics
+        );
+
+        // no disk access without a loader
+        let (_, diagnostics) = KukuriScript::parse_with_diagnostics(kkr_src);
+        assert_eq!(
+            vec![Diagnostic::error(
+                7,
+                "failed to include fragments/menu.kkr: no file loader for this source"
+            )],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_parse_macros() {
+        let kkr_src = r#"
++++
+title = "Party"
+[macros.greet]
+params = ["name"]
+body = """
+{name}: Hi # comment
+@enter {name}
+"""
++++
+@greet Alice
+* Join
+    @greet name="Bob"
+@greet
+@unknown x
+@loop
+@loop
+@warn
+"#;
+        let gen_def = |params: &[&str], body: &str, line: usize| MacroDef {
+            params: params.iter().map(|p| String::from(*p)).collect(),
+            body: String::from(body),
+            loc: SourceLoc {
+                path: String::from("macros.toml"),
+                line,
+            },
+        };
+        let mut macros = Macros::new();
+        macros.insert(String::from("enter"), gen_def(&["who"], "$ show {who}", 1));
+        macros.insert(String::from("greet"), gen_def(&[], "$ overridden", 4));
+        macros.insert(String::from("loop"), gen_def(&[], "@loop", 7));
+        macros.insert(String::from("warn"), gen_def(&[], "A: \\q", 10));
+
+        let loader = |_: &Path| Err(io::Error::new(io::ErrorKind::NotFound, "not found"));
+        let (data, diagnostics) = KukuriScript::parse_source(kkr_src, None, "", &macros, &loader);
+
+        let mut ids = Vec::new();
+        let mut lines = Vec::new();
+        if let KukuriData::Scenes(scenes) = &data {
+            scenes[0].walk_dialogs(|d| {
+                ids.push(d.id.clone());
+                lines.push(d.loc.line);
+            });
+        }
+        assert_eq!(
+            vec![
+                "Party_1_Alice",
+                "show",
+                "Party_2_C1",
+                "Party_2_C1L1_1_Bob",
+                "show",
+                "Party_3_unknown",
+                "Party_4_A"
+            ],
+            ids
+        );
+        assert_eq!(vec![11, 11, 12, 13, 13, 15, 18], lines);
+
+        assert_eq!(
+            vec![
+                Diagnostic::error(
+                    14,
+                    "macro `greet` requires argument `name` (defined at line 4)"
+                ),
+                Diagnostic::error(
+                    16,
+                    "recursive macro: loop -> loop (defined at macros.toml:7)"
+                ),
+                Diagnostic::warning(
+                    18,
+                    "unknown escape sequence \"\\q\" (in macro `warn` defined at macros.toml:10)"
+                ),
+            ],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_parse_escape_sequence() {
+        let kkr_src = r#"
++++
+title = "TestDialog"
++++
+A: Line\nbreak and \# and \\n
+Note\: not a talker
+B: unknown \q escape
+"#;
+        let mut sc = Scene::new();
+        sc.title = String::from("TestDialog");
+        sc.dialogs = vec![
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "TestDialog_1_A",
+                vec![
+                    DialogBody::gen_text("Line\nbreak and # and \\n"),
+                    DialogBody::gen_text("TALKER_A"),
+                ],
+            ),
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "TestDialog_2_unknown",
+                vec![
+                    DialogBody::gen_text("Note: not a talker"),
+                    DialogBody::gen_text("TALKER_unknown"),
+                ],
+            ),
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "TestDialog_3_B",
+                vec![
+                    DialogBody::gen_text("unknown \\q escape"),
+                    DialogBody::gen_text("TALKER_B"),
+                ],
+            ),
+        ];
+        let expected = (
+            KukuriData::from_scenes(vec![sc]),
+            vec![Diagnostic::warning(7, "unknown escape sequence \"\\q\"")],
+        );
+
+        let (data, diagnostics) = KukuriScript::parse_with_diagnostics(kkr_src);
+        assert_eq!(expected, (data.without_locs(), diagnostics))
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let kkr_src = r#"
++++
+title = "TestDialog"
++++
+$ show_text "Hello world" 0.5 fast=true
+$ jump DialogTest2
+"#;
+        let mut sc = Scene::new();
+        sc.title = String::from("TestDialog");
+        sc.dialogs = vec![
+            Dialog::from_dialog_data(
+                DialogKind::Command,
+                "show_text",
+                vec![
+                    DialogBody::Arg(CommandArg::gen_str("Hello world")),
+                    DialogBody::Arg(CommandArg::Float(Float(0.5))),
+                    DialogBody::Arg(CommandArg::Named(
+                        String::from("fast"),
+                        Box::new(CommandArg::Bool(true)),
+                    )),
+                ],
+            ),
+            Dialog::from_dialog_data(
+                DialogKind::Command,
+                "jump",
+                vec![DialogBody::Arg(CommandArg::gen_ident("DialogTest2"))],
+            ),
+        ];
+        let expected = (KukuriData::from_scenes(vec![sc]), Vec::new());
+
+        let (data, diagnostics) = KukuriScript::parse_with_diagnostics(kkr_src);
+        assert_eq!(expected, (data.without_locs(), diagnostics))
+    }
+
+    #[test]
+    fn test_parse_inline_command() {
+        let kkr_src = r#"
++++
+title = "TestDialog"
++++
+A: Wait[wait 0.5]... what?[se "door.ogg"]
+B: Literal \[bracket]
+"#;
+        let mut sc = Scene::new();
+        sc.title = String::from("TestDialog");
+        sc.dialogs = vec![
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "TestDialog_1_A",
+                vec![
+                    DialogBody::gen_text("Wait... what?"),
+                    DialogBody::gen_text("TALKER_A"),
+                ],
+            )
+            .with_inline_commands(vec![
+                InlineCommand::from_command_data(4, "wait", vec![CommandArg::Float(Float(0.5))]),
+                InlineCommand::from_command_data(13, "se", vec![CommandArg::gen_str("door.ogg")]),
+            ]),
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "TestDialog_2_B",
+                vec![
+                    DialogBody::gen_text("Literal [bracket]"),
+                    DialogBody::gen_text("TALKER_B"),
+                ],
+            ),
+        ];
+        let expected = (KukuriData::from_scenes(vec![sc]), Vec::new());
+
+        let (data, diagnostics) = KukuriScript::parse_with_diagnostics(kkr_src);
+        assert_eq!(expected, (data.without_locs(), diagnostics))
+    }
+
+    #[test]
+    fn test_parse_continuation_line() {
+        let kkr_src = r#"
++++
+title = "TestDialog"
++++
+* choice
+  A: This text \
+    continues on the next line.
+  B: Still in choice.
+"#;
+        let mut cd = ChoiceData::from_texts("TestDialog_1_C1L1", "choice");
+        cd.dialogs = vec![
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "TestDialog_1_C1L1_1_A",
+                vec![
+                    DialogBody::gen_text("This text\ncontinues on the next line."),
+                    DialogBody::gen_text("TALKER_A"),
+                ],
+            ),
+            Dialog::from_dialog_data(
+                DialogKind::Dialog,
+                "TestDialog_1_C1L1_2_B",
+                vec![
+                    DialogBody::gen_text("Still in choice."),
+                    DialogBody::gen_text("TALKER_B"),
+                ],
+            ),
+        ];
+
+        let mut sc = Scene::new();
+        sc.title = String::from("TestDialog");
+        sc.dialogs = vec![Dialog::from_dialog_data(
+            DialogKind::Choices,
+            "TestDialog_1_C1",
+            vec![DialogBody::Choice(cd)],
+        )];
+        let expected = (KukuriData::from_scenes(vec![sc]), Vec::new());
+
+        let (data, diagnostics) = KukuriScript::parse_with_diagnostics(kkr_src);
+        assert_eq!(expected, (data.without_locs(), diagnostics))
     }
 }
@@ -1,60 +1,46 @@
-use crate::core::{kukuri_data::KukuriData, talker::Talker};
+use super::{comment, escape};
+use crate::core::{
+    diagnostic::{Diagnostic, SourceLoc},
+    kukuri_data::KukuriData,
+    talker::Talker,
+};
 
 pub struct KukuriTalkers;
 impl KukuriTalkers {
-    pub fn parse(content: &str) -> KukuriData {
+    pub fn parse_with_diagnostics(content: &str) -> (KukuriData, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
         let mut talkers: Vec<Talker> = Vec::new();
 
-        for full_line in content.lines() {
-            let line = Self::trim_comment(full_line);
+        for (i, full_line) in content.lines().enumerate() {
+            let line = comment::trim_line(full_line, '#', &[]);
             if line.trim().is_empty() {
                 continue;
             }
 
-            if let Some(talker) = Self::line_parse(line) {
+            if let Some(talker) = Self::line_parse(line, i + 1, &mut diagnostics) {
                 talkers.push(talker);
             }
         }
 
-        KukuriData::from_talkers(talkers)
+        (KukuriData::from_talkers(talkers), diagnostics)
     }
 
-    fn line_parse(line: &str) -> Option<Talker> {
-        match line.find(':') {
+    fn line_parse(line: &str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> Option<Talker> {
+        match escape::find_unescaped(line, ':') {
             Some(i) => {
                 let (id, _) = line.split_at(i);
                 let (_, name) = line.split_at(i + 1);
-                Some(Talker::from_strs(
-                    format!("TALKER_{}", id.trim()),
-                    name.trim(),
-                ))
+                Some(
+                    Talker::from_strs(
+                        format!("TALKER_{}", id.trim()),
+                        escape::unescape(name.trim(), line_no, diagnostics),
+                    )
+                    .with_loc(SourceLoc::from_line(line_no)),
+                )
             }
             None => None,
         }
     }
-
-    fn trim_comment(line: &str) -> &str {
-        let mut bs_i = 0;
-        let symbol_idx = line.char_indices().find(|&(i, c)| {
-            // match backslash char
-            if c == '\\' {
-                bs_i = i;
-            }
-
-            let mut is_comment = c == '#';
-
-            if bs_i + 1 == i {
-                is_comment = false;
-            }
-
-            is_comment
-        });
-
-        match symbol_idx {
-            Some((i, _)) => line.split_at(i).0,
-            None => line,
-        }
-    }
 }
 
 #[cfg(test)]
@@ -68,13 +54,17 @@ mod tests {
 A : Alpha
  B: Bravo
  C  :  Charlie
+D: Delta\: \#4 # comment
 "#;
         let expected = KukuriData::from_talkers(vec![
             Talker::from_strs("TALKER_A", "Alpha"),
             Talker::from_strs("TALKER_B", "Bravo"),
             Talker::from_strs("TALKER_C", "Charlie"),
+            Talker::from_strs("TALKER_D", "Delta: #4"),
         ]);
 
-        assert_eq!(expected, KukuriTalkers::parse(talker_src));
+        let (data, diagnostics) = KukuriTalkers::parse_with_diagnostics(talker_src);
+        assert_eq!(expected, data.without_locs());
+        assert!(diagnostics.is_empty());
     }
 }
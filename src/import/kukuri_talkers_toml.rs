@@ -0,0 +1,164 @@
+use crate::core::{
+    diagnostic::{Diagnostic, SourceLoc},
+    kukuri_data::KukuriData,
+    talker::Talker,
+};
+
+// Import talkers with attributes from toml.
+//
+// A = "Alpha"              => name only
+// [B]
+// name = "Bravo"
+// color = "#4040ff"        => attributes, and any other keys are custom fields
+//
+// keys are talker ids without "TALKER_", as in .kkrt
+pub struct KukuriTalkersToml;
+
+impl KukuriTalkersToml {
+    pub fn parse_with_diagnostics(content: &str) -> (KukuriData, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let table = match toml::from_str::<toml::value::Table>(content) {
+            Ok(t) => t,
+            Err(e) => {
+                let line = e.line_col().map_or(0, |(l, _)| l + 1);
+                diagnostics.push(Diagnostic::error(
+                    line,
+                    format!("talkers toml parse error: {}", e),
+                ));
+                return (KukuriData::new(), diagnostics);
+            }
+        };
+
+        let mut talkers: Vec<Talker> = Vec::new();
+        for (key, value) in table {
+            let line_no = Self::key_line(content, &key);
+            let id = format!("TALKER_{}", key);
+            let talker = match value {
+                toml::Value::String(name) => Talker::from_strs(&id, name),
+                toml::Value::Table(_) => match value.try_into::<Talker>() {
+                    Ok(mut t) => {
+                        t.id = id;
+                        if t.name.is_empty() {
+                            t.name = key;
+                        }
+                        t
+                    }
+                    Err(e) => {
+                        diagnostics.push(Diagnostic::error(
+                            line_no,
+                            format!("talker `{}`: {}", key, e),
+                        ));
+                        continue;
+                    }
+                },
+                v => {
+                    diagnostics.push(Diagnostic::warning(
+                        line_no,
+                        format!(
+                            "talker `{}` must be a name or a table, not {}",
+                            key,
+                            v.type_str()
+                        ),
+                    ));
+                    continue;
+                }
+            };
+            talkers.push(talker.with_loc(SourceLoc::from_line(line_no)));
+        }
+
+        // keep the order in file
+        talkers.sort_by_key(|t| t.loc().line);
+
+        (KukuriData::from_talkers(talkers), diagnostics)
+    }
+
+    // line number of "[key]" or "key = ...", 0 if not found
+    fn key_line(content: &str, key: &str) -> usize {
+        let quoted = format!("\"{}\"", key);
+        let is_key = |s: &str| {
+            [key, quoted.as_str()].iter().any(|k| {
+                s.strip_prefix(k)
+                    .is_some_and(|rest| rest.trim_start().starts_with(['=', ']']))
+            })
+        };
+
+        content
+            .lines()
+            .position(|l| {
+                let l = l.trim_start();
+                is_key(l.strip_prefix('[').unwrap_or(l).trim_start())
+            })
+            .map_or(0, |i| i + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KukuriTalkersToml;
+    use crate::core::{kukuri_data::KukuriData, meta::MetaValue, talker::Talker};
+
+    #[test]
+    fn test_parse() {
+        let src = r##"
+B = "Bravo"
+
+[A]
+name = "Alpha"
+color = "#ff4040"
+portrait = "portraits/alpha.png"
+voice_prefix = "voice/alpha_"
+textbox_style = "calm"
+default_expression = "smile"
+age = 17
+
+["C"]
+color = "#4040ff"
+"##;
+        let (data, diagnostics) = KukuriTalkersToml::parse_with_diagnostics(src);
+        assert!(diagnostics.is_empty());
+
+        let mut a = Talker::from_strs("TALKER_A", "Alpha");
+        a.color = Some(String::from("#ff4040"));
+        a.portrait = Some(String::from("portraits/alpha.png"));
+        a.voice_prefix = Some(String::from("voice/alpha_"));
+        a.textbox_style = Some(String::from("calm"));
+        a.default_expression = Some(String::from("smile"));
+        a.custom.insert(String::from("age"), MetaValue::Int(17));
+        let mut c = Talker::from_strs("TALKER_C", "C");
+        c.color = Some(String::from("#4040ff"));
+        let expected = vec![Talker::from_strs("TALKER_B", "Bravo"), a, c];
+        assert_eq!(
+            KukuriData::from_talkers(expected.clone()),
+            data.clone().without_locs()
+        );
+
+        let lines: Vec<usize> = match data {
+            KukuriData::Talkers(talkers) => talkers.iter().map(|t| t.loc().line).collect(),
+            _ => Vec::new(),
+        };
+        assert_eq!(vec![2, 4, 13], lines);
+
+        assert_eq!(
+            r##"{"name":"Alpha","color":"#ff4040","portrait":"portraits/alpha.png","voice_prefix":"voice/alpha_","textbox_style":"calm","default_expression":"smile","age":17}"##,
+            serde_json::to_string(&expected[1]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let src = "A = 1\n\n[B]\ncolor = 2\n";
+        let (data, diagnostics) = KukuriTalkersToml::parse_with_diagnostics(src);
+        assert_eq!(KukuriData::from_talkers(Vec::new()), data);
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            vec![
+                "1: warning: talker `A` must be a name or a table, not integer",
+                "3: error: talker `B`: invalid type: integer `2`, expected a string for key `color`",
+            ],
+            messages
+        );
+
+        let (_, diagnostics) = KukuriTalkersToml::parse_with_diagnostics("[A\n");
+        assert_eq!(1, diagnostics[0].line);
+    }
+}
@@ -0,0 +1,348 @@
+use super::escape;
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// macro name: definition
+pub type Macros = BTreeMap<String, MacroDef>;
+
+// Parameterized lines, expanded at `@name args...` lines.
+//
+// [macros.enter]
+// params = ["name", "greeting"]
+// body = """
+// $ show {name}
+// {name}: {greeting}
+// """
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroDef {
+    pub params: Vec<String>,
+    pub body: String,
+    // location of "[macros.<name>]" line, not serialized
+    #[serde(skip)]
+    pub loc: SourceLoc,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct MacroFile {
+    macros: Macros,
+}
+
+// `@name arg1 "arg 2" key=value`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MacroCall<'a> {
+    pub indent: &'a str,
+    pub name: &'a str,
+    // (param name of named argument, raw text)
+    pub args: Vec<(Option<&'a str>, &'a str)>,
+}
+
+// macro file shared by all scripts
+pub fn from_file<P: AsRef<Path>>(path: P) -> Macros {
+    let source = path.as_ref().display().to_string();
+    let s = match utils::read_file(path.as_ref()) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to load macros {}: {:?}", source, e);
+            return Macros::new();
+        }
+    };
+
+    match toml::from_str::<MacroFile>(&s) {
+        Ok(mf) => {
+            let mut macros = mf.macros;
+            let lines = s.lines().enumerate().map(|(i, l)| (i + 1, l));
+            set_def_locs(&mut macros, lines, &source);
+            macros
+        }
+        Err(e) => {
+            eprintln!("Macros toml parse error: {:?}", e);
+            Macros::new()
+        }
+    }
+}
+
+// name of "[macros.<name>]" table header line
+pub fn def_name(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("[macros.")?
+        .strip_suffix(']')
+        .map(|s| s.trim().trim_matches('"'))
+}
+
+// locate definitions by their table header lines
+pub fn set_def_locs<'a, I: Iterator<Item = (usize, &'a str)>>(
+    macros: &mut Macros,
+    lines: I,
+    path: &str,
+) {
+    for (line_no, line) in lines {
+        if let Some(def) = def_name(line).and_then(|name| macros.get_mut(name)) {
+            def.loc = SourceLoc {
+                path: String::from(path),
+                line: line_no,
+            };
+        }
+    }
+}
+
+impl MacroDef {
+    // "macros.toml:3", or "line 3" in the parsed file itself
+    pub fn loc_str(&self) -> String {
+        if self.loc.path.is_empty() {
+            format!("line {}", self.loc.line)
+        } else {
+            self.loc.to_string()
+        }
+    }
+
+    // substitute "{param}" in body with arguments.
+    // return: substituted body, or error messages
+    pub fn expand(&self, call: &MacroCall) -> Result<String, Vec<String>> {
+        let mut values: Vec<Option<&str>> = vec![None; self.params.len()];
+        let mut errors = Vec::new();
+        let mut positional_idx = 0;
+
+        for &(name, value) in &call.args {
+            let idx = match name {
+                Some(n) => match self.params.iter().position(|p| p == n) {
+                    Some(i) => i,
+                    None => {
+                        errors.push(format!("macro `{}` has no parameter `{}`", call.name, n));
+                        continue;
+                    }
+                },
+                None => {
+                    positional_idx += 1;
+                    positional_idx - 1
+                }
+            };
+
+            match values.get_mut(idx) {
+                Some(Some(_)) => errors.push(format!(
+                    "argument `{}` of macro `{}` is given twice",
+                    self.params[idx], call.name
+                )),
+                Some(v) => *v = Some(value),
+                // reported after all arguments are counted
+                None => {}
+            }
+        }
+
+        if positional_idx > self.params.len() {
+            errors.push(format!(
+                "macro `{}` takes {} argument(s), but {} given",
+                call.name,
+                self.params.len(),
+                positional_idx
+            ));
+        }
+        for (p, v) in self.params.iter().zip(&values) {
+            if v.is_none() {
+                errors.push(format!("macro `{}` requires argument `{}`", call.name, p));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // single pass, so that braces in arguments are never substituted
+        let mut body = String::with_capacity(self.body.len());
+        let mut rest = self.body.as_str();
+        while let Some(i) = rest.find('{') {
+            body.push_str(&rest[..i]);
+            let after = &rest[i + 1..];
+            let param = after.split_once('}').and_then(|(name, r)| {
+                let idx = self.params.iter().position(|p| p == name)?;
+                Some((values[idx].unwrap_or(""), r))
+            });
+            match param {
+                Some((v, r)) => {
+                    body.push_str(v);
+                    rest = r;
+                }
+                None => {
+                    body.push('{');
+                    rest = after;
+                }
+            }
+        }
+        body.push_str(rest);
+        Ok(body)
+    }
+}
+
+impl<'a> MacroCall<'a> {
+    // "@name ..." line, but not a talker e.g. "@name: text"
+    pub fn parse(line: &'a str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> Option<Self> {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        let rest = trimmed.strip_prefix('@')?;
+
+        let name_len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let (name, args_str) = rest.split_at(name_len);
+        if name.is_empty() || !(args_str.is_empty() || args_str.starts_with(char::is_whitespace)) {
+            return None;
+        }
+
+        Some(Self {
+            indent,
+            name,
+            args: Self::split_args(args_str, line_no, diagnostics),
+        })
+    }
+
+    // split arguments keeping raw text, escape sequences are decoded after expansion
+    fn split_args(
+        s: &'a str,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<(Option<&'a str>, &'a str)> {
+        let mut args = Vec::new();
+        let mut rest = s.trim_start();
+
+        while !rest.is_empty() {
+            let mut name = None;
+            if let Some(eq) = rest.find('=') {
+                let key = &rest[..eq];
+                let is_key =
+                    !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+                if is_key {
+                    name = Some(key);
+                    rest = &rest[eq + 1..];
+                }
+            }
+
+            let (value, r) = if let Some(quoted) = rest.strip_prefix('"') {
+                match escape::find_unescaped(quoted, '"') {
+                    Some(i) => (&quoted[..i], &quoted[i + 1..]),
+                    None => {
+                        diagnostics.push(Diagnostic::warning(
+                            line_no,
+                            format!("unterminated quoted argument \"{}", quoted),
+                        ));
+                        (quoted, "")
+                    }
+                }
+            } else {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                rest.split_at(end)
+            };
+
+            args.push((name, value));
+            rest = r.trim_start();
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{def_name, set_def_locs, MacroCall, MacroDef, Macros};
+
+    fn gen_def() -> MacroDef {
+        MacroDef {
+            params: vec![String::from("name"), String::from("greeting")],
+            body: String::from("$ show {name}\n{name}: {greeting} {漢字|かんじ}\n"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_call() {
+        let mut diagnostics = Vec::new();
+        let tests = [
+            (
+                "    @enter Alice \"Hi, \\\"you\\\"\" greeting=\"Yo\"",
+                Some(MacroCall {
+                    indent: "    ",
+                    name: "enter",
+                    args: vec![
+                        (None, "Alice"),
+                        (None, "Hi, \\\"you\\\""),
+                        (Some("greeting"), "Yo"),
+                    ],
+                }),
+            ),
+            (
+                "@wave",
+                Some(MacroCall {
+                    indent: "",
+                    name: "wave",
+                    args: Vec::new(),
+                }),
+            ),
+            ("@Alice: text", None),
+            ("A: @enter", None),
+        ];
+
+        for (src, expected) in &tests {
+            assert_eq!(*expected, MacroCall::parse(src, 1, &mut diagnostics));
+        }
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_expand() {
+        let mut diagnostics = Vec::new();
+        let def = gen_def();
+
+        let call = MacroCall::parse("  @enter Bob greeting=Hello", 1, &mut diagnostics).unwrap();
+        assert_eq!(
+            Ok(String::from("$ show Bob\nBob: Hello {漢字|かんじ}\n")),
+            def.expand(&call)
+        );
+
+        // arguments are not substituted again
+        let call = MacroCall::parse("@enter {greeting} \"{name}\"", 1, &mut diagnostics).unwrap();
+        assert_eq!(
+            Ok(String::from(
+                "$ show {greeting}\n{greeting}: {name} {漢字|かんじ}\n"
+            )),
+            def.expand(&call)
+        );
+
+        let call = MacroCall::parse("@enter Bob x=1 Hi Hey", 1, &mut diagnostics).unwrap();
+        assert_eq!(
+            Err(vec![
+                String::from("macro `enter` has no parameter `x`"),
+                String::from("macro `enter` takes 2 argument(s), but 3 given"),
+            ]),
+            def.expand(&call)
+        );
+
+        let call = MacroCall::parse("@enter name=Bob Alice", 1, &mut diagnostics).unwrap();
+        assert_eq!(
+            Err(vec![
+                String::from("argument `name` of macro `enter` is given twice"),
+                String::from("macro `enter` requires argument `greeting`"),
+            ]),
+            def.expand(&call)
+        );
+    }
+
+    #[test]
+    fn test_set_def_locs() {
+        let src = "# macros\n[macros.enter]\nparams = []\n\n[macros.\"leave\"]\n";
+        let mut macros = Macros::new();
+        macros.insert(String::from("enter"), MacroDef::default());
+        macros.insert(String::from("leave"), MacroDef::default());
+
+        set_def_locs(
+            &mut macros,
+            src.lines().enumerate().map(|(i, l)| (i + 1, l)),
+            "m.toml",
+        );
+        assert_eq!("m.toml:2", macros["enter"].loc_str());
+        assert_eq!("m.toml:5", macros["leave"].loc_str());
+        assert_eq!(Some("enter"), def_name(" [macros.enter] "));
+        assert_eq!(None, def_name("[commands.enter]"));
+    }
+}
@@ -0,0 +1,463 @@
+use super::emphasis::Emphasis;
+use super::title::Titles;
+use super::{command, comment};
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
+use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+use crate::core::kukuri_data::KukuriData;
+use crate::core::markup::{RichText, RichTextBuilder, Style};
+use crate::core::meta::MetaValue;
+use crate::core::namespace;
+
+// scene of lines before the first heading
+const ROOT_SCENE: &str = "Root";
+// talker of text lines without "**Talker**:"
+const UNKNOWN_TALKER: &str = "unknown";
+const FENCES: [&str; 2] = ["```", "~~~"];
+const EMPHASIS: Emphasis = Emphasis {
+    markers: &[
+        ("***", &[Style::Bold, Style::Italic]),
+        ("**", &[Style::Bold]),
+        ("__", &[Style::Bold]),
+        ("~~", &[Style::Strike]),
+        ("*", &[Style::Italic]),
+        ("_", &[Style::Italic]),
+    ],
+    is_intraword_underscore_literal: true,
+};
+
+// Import Markdown screenplay.
+//
+// # Title               => Scene "Title"
+// **Talker**: text      => DialogKind::Dialog
+// - option              => DialogKind::Choices, indented lines are option dialogs
+// ```                   => DialogKind::Command, one command per line
+// *italic* **bold**     => styled span
+pub struct Markdown;
+
+enum Block {
+    Heading(String),
+    // (talker, text)
+    Text(Option<String>, String),
+    Item(String),
+    Command(String),
+}
+
+struct MdLine {
+    line_no: usize,
+    indent: usize,
+    block: Block,
+}
+
+impl Markdown {
+    // scene titles are qualified with namespace
+    pub fn parse_with_namespace(content: &str, namespace: &str) -> (KukuriData, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let mut scenes = Vec::new();
+        let lines = Self::lines(content, &mut diagnostics);
+
+        // (line_no, heading, start) of scene lines
+        let mut starts: Vec<(usize, &str, usize)> = Vec::new();
+        for (i, l) in lines.iter().enumerate() {
+            match &l.block {
+                Block::Heading(h) => starts.push((l.line_no, h, i + 1)),
+                _ if starts.is_empty() => starts.push((0, ROOT_SCENE, i)),
+                _ => {}
+            }
+        }
+        let ranges = starts
+            .iter()
+            .enumerate()
+            .map(|(n, &(line_no, heading, start))| {
+                let end = starts.get(n + 1).map_or(lines.len(), |next| next.2 - 1);
+                (line_no, heading, start, end)
+            });
+
+        let mut titles = Titles::new(ROOT_SCENE);
+        for (line_no, heading, start, end) in ranges {
+            let title = titles.unique(heading);
+            let mut sc = Scene::new();
+            sc.title = namespace::qualify(namespace, &title);
+            if line_no > 0 {
+                sc.loc = SourceLoc::from_line(line_no);
+            }
+            if title != heading {
+                sc.meta.insert(
+                    String::from("heading"),
+                    MetaValue::Str(String::from(heading)),
+                );
+            }
+
+            let mut idx = 0;
+            let label = sc.title.clone();
+            sc.dialogs =
+                Self::block_process(&lines[start..end], &label, &mut idx, &mut diagnostics);
+            scenes.push(sc);
+        }
+
+        (KukuriData::from_scenes(scenes), diagnostics)
+    }
+
+    fn lines(content: &str, diagnostics: &mut Vec<Diagnostic>) -> Vec<MdLine> {
+        let content = comment::strip(content, &[("<!--", "-->")]);
+        let mut lines: Vec<MdLine> = Vec::new();
+        // (fence, indent) of the open code block
+        let mut fence: Option<(&str, usize)> = None;
+        let mut is_after_blank = true;
+
+        for (i, line) in content.lines().enumerate() {
+            let line_no = i + 1;
+            let text = line.trim();
+            let indent = line.len() - line.trim_start().len();
+
+            if let Some((f, fence_indent)) = fence {
+                if text.starts_with(f) {
+                    fence = None;
+                } else if !text.is_empty() {
+                    lines.push(MdLine {
+                        line_no,
+                        indent: fence_indent,
+                        block: Block::Command(String::from(text)),
+                    });
+                }
+                continue;
+            }
+            if text.is_empty() {
+                is_after_blank = true;
+                continue;
+            }
+            let was_after_blank = std::mem::replace(&mut is_after_blank, false);
+
+            if let Some(f) = FENCES.iter().find(|f| text.starts_with(*f)) {
+                fence = Some((f, indent));
+                continue;
+            }
+            // blockquotes are notes for writers
+            if text.starts_with('>') || Self::is_thematic_break(text) {
+                is_after_blank = true;
+                continue;
+            }
+
+            let block = if let Some(h) = Self::heading(text) {
+                Block::Heading(String::from(h))
+            } else if let Some(item) = Self::list_item(text) {
+                Block::Item(String::from(item))
+            } else if let Some((talker, t)) = Self::talker_line(text) {
+                Block::Text(Some(String::from(talker)), String::from(t))
+            } else {
+                // lazy continuation of the last text
+                if let Some(MdLine {
+                    block: Block::Text(_, last),
+                    ..
+                }) = lines.last_mut()
+                {
+                    if !was_after_blank {
+                        last.push('\n');
+                        last.push_str(text);
+                        continue;
+                    }
+                }
+                Block::Text(None, String::from(text))
+            };
+            lines.push(MdLine {
+                line_no,
+                indent,
+                block,
+            });
+        }
+
+        if fence.is_some() {
+            diagnostics.push(Diagnostic::warning(
+                content.lines().count(),
+                "code block is not closed",
+            ));
+        }
+        lines
+    }
+
+    fn block_process(
+        lines: &[MdLine],
+        label: &str,
+        idx: &mut usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<Dialog> {
+        let mut dialogs = Vec::new();
+        let mut i = 0;
+
+        while i < lines.len() {
+            let line = &lines[i];
+            let loc = SourceLoc::from_line(line.line_no);
+            match &line.block {
+                Block::Item(_) => {
+                    *idx += 1;
+                    let choices_id = format!("{}_{}_C1", label, idx);
+                    let choices = Self::choices_process(lines, &mut i, &choices_id, diagnostics);
+                    dialogs.push(choices.with_loc(loc));
+                    continue;
+                }
+                Block::Text(talker, text) => {
+                    *idx += 1;
+                    let talker = talker.as_deref().unwrap_or(UNKNOWN_TALKER);
+                    let rich = Self::text_process(text, line.line_no, diagnostics);
+                    let body = vec![
+                        DialogBody::from_rich_text(rich),
+                        DialogBody::gen_text(format!("TALKER_{}", talker)),
+                    ];
+                    let id = format!("{}_{}_{}", label, idx, talker);
+                    dialogs
+                        .push(Dialog::from_dialog_data(DialogKind::Dialog, id, body).with_loc(loc));
+                }
+                Block::Command(s) => {
+                    let (name, args_str) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+                    let args = command::parse_args(args_str.trim(), line.line_no, diagnostics)
+                        .into_iter()
+                        .map(DialogBody::Arg)
+                        .collect();
+                    dialogs.push(
+                        Dialog::from_dialog_data(DialogKind::Command, name, args).with_loc(loc),
+                    );
+                }
+                // headings split scenes before
+                Block::Heading(_) => {}
+            }
+            i += 1;
+        }
+
+        dialogs
+    }
+
+    // consecutive list items of the same indent, and their indented lines
+    fn choices_process(
+        lines: &[MdLine],
+        pos: &mut usize,
+        choices_id: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Dialog {
+        let mut choices = Dialog::from_dialog_data(DialogKind::Choices, choices_id, Vec::new());
+        let indent = lines[*pos].indent;
+
+        while let Some(MdLine {
+            line_no,
+            indent: item_indent,
+            block: Block::Item(item),
+        }) = lines.get(*pos)
+        {
+            if *item_indent != indent {
+                break;
+            }
+            let end = lines[*pos + 1..]
+                .iter()
+                .position(|l| l.indent <= indent)
+                .map_or(lines.len(), |n| *pos + 1 + n);
+
+            let id = format!("{}L{}", choices_id, choices.args.len() + 1);
+            let label = Self::text_process(item, *line_no, diagnostics).plain_text();
+            let mut cd =
+                ChoiceData::from_texts(&id, label).with_loc(SourceLoc::from_line(*line_no));
+            let mut idx = 0;
+            cd.dialogs = Self::block_process(&lines[*pos + 1..end], &id, &mut idx, diagnostics);
+            choices.args.push(DialogBody::Choice(cd));
+            *pos = end;
+        }
+
+        choices
+    }
+
+    // "## Opening ##" => "Opening"
+    fn heading(text: &str) -> Option<&str> {
+        let rest = text.trim_start_matches('#');
+        let level = text.len() - rest.len();
+        if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+            return None;
+        }
+        Some(rest.trim().trim_end_matches('#').trim_end())
+    }
+
+    // "- text", "* text", "+ text", "1. text", "1) text"
+    fn list_item(text: &str) -> Option<&str> {
+        let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(0);
+        let rest = if digits > 0 {
+            text[digits..].strip_prefix(['.', ')'])?
+        } else {
+            text.strip_prefix(['-', '*', '+'])?
+        };
+        if rest.is_empty() || rest.starts_with(' ') {
+            Some(rest.trim())
+        } else {
+            None
+        }
+    }
+
+    // "---", "***", "___", spaces between are allowed
+    fn is_thematic_break(text: &str) -> bool {
+        ['-', '*', '_'].iter().any(|&c| {
+            text.chars().filter(|&x| x == c).count() >= 3
+                && text.chars().all(|x| x == c || x == ' ')
+        })
+    }
+
+    // "**Alice**: text", "**Alice:** text"
+    fn talker_line(text: &str) -> Option<(&str, &str)> {
+        let rest = text.strip_prefix("**")?;
+        let end = rest.find("**")?;
+        let (name, after) = (&rest[..end], &rest[end + 2..]);
+        let (name, text) = match name.strip_suffix(':') {
+            Some(n) => (n, after),
+            None => (name, after.strip_prefix(':')?),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        Some((name, text.trim()))
+    }
+
+    fn text_process(text: &str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> RichText {
+        let mut builder = RichTextBuilder::new();
+        EMPHASIS.process(text, &mut builder, line_no, diagnostics);
+        builder.finish().0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Markdown;
+    use crate::core::command::CommandArg;
+    use crate::core::dialog::{Dialog, DialogBody, DialogKind};
+    use crate::core::kukuri_data::KukuriData;
+    use crate::core::markup::{RichText, Span, Style};
+    use crate::core::meta::MetaValue;
+    use crate::import::flatten;
+
+    #[test]
+    fn test_parse() {
+        let md_src = r#"
+# Opening Scene
+
+```kukuri
+bgm "bgm/daily.ogg" loop=true
+```
+
+**A**: Hello,
+how are *you*?
+**B:** Fine. <!-- note for writers -->
+
+> this is a note
+
+- Go left
+  **A**: Left it is.
+  - Run
+  - Walk
+    ```
+    wait 0.5
+    ```
+- Go right
+
+Narration.
+
+## Next
+**A**: Bye.
+"#;
+        let (data, diagnostics) = Markdown::parse_with_namespace(md_src, "");
+        let scenes = match data.without_locs() {
+            KukuriData::Scenes(scenes) => scenes,
+            _ => Vec::new(),
+        };
+        assert!(diagnostics.is_empty());
+
+        let titles: Vec<&str> = scenes.iter().map(|sc| sc.title.as_str()).collect();
+        assert_eq!(vec!["Opening_Scene", "Next"], titles);
+        assert_eq!(
+            Some(&MetaValue::Str(String::from("Opening Scene"))),
+            scenes[0].meta.get("heading")
+        );
+        assert_eq!(
+            Dialog::from_dialog_data(
+                DialogKind::Command,
+                "bgm",
+                vec![
+                    DialogBody::Arg(CommandArg::gen_str("bgm/daily.ogg")),
+                    DialogBody::Arg(CommandArg::Named(
+                        String::from("loop"),
+                        Box::new(CommandArg::Bool(true))
+                    )),
+                ],
+            ),
+            scenes[0].dialogs[0]
+        );
+        assert_eq!(
+            DialogBody::from_rich_text(RichText {
+                spans: vec![
+                    Span::Text(String::from("Hello,\nhow are ")),
+                    Span::Styled(Style::Italic, vec![Span::Text(String::from("you"))]),
+                    Span::Text(String::from("?")),
+                ]
+            }),
+            scenes[0].dialogs[1].args[0]
+        );
+
+        let expected: Vec<(&str, &str)> = vec![
+            ("bgm", "bgm/daily.ogg"),
+            ("Opening_Scene_1_A", "Hello,\nhow are you?"),
+            ("Opening_Scene_2_B", "Fine."),
+            ("Opening_Scene_3_C1", ""),
+            ("Opening_Scene_3_C1L1", "Go left"),
+            ("Opening_Scene_3_C1L1_1_A", "Left it is."),
+            ("Opening_Scene_3_C1L1_2_C1", ""),
+            ("Opening_Scene_3_C1L1_2_C1L1", "Run"),
+            ("Opening_Scene_3_C1L1_2_C1L2", "Walk"),
+            ("wait", ""),
+            ("Opening_Scene_3_C1L2", "Go right"),
+            ("Opening_Scene_4_unknown", "Narration."),
+        ];
+        let actual = flatten(&scenes[0].dialogs);
+        let actual: Vec<(&str, &str)> = actual
+            .iter()
+            .map(|(id, s)| (id.as_str(), s.as_str()))
+            .collect();
+        assert_eq!(expected, actual);
+        assert_eq!("Next_1_A", scenes[1].dialogs[0].id);
+    }
+
+    #[test]
+    fn test_emphasis() {
+        let md_src = "***both*** __b__ ~~s~~ snake_case_name \\*lit\\* 2 * 3\n";
+        let (data, diagnostics) = Markdown::parse_with_namespace(md_src, "ch1");
+        let scenes = match data {
+            KukuriData::Scenes(scenes) => scenes,
+            _ => Vec::new(),
+        };
+        assert!(diagnostics.is_empty());
+
+        assert_eq!("ch1.Root", scenes[0].title);
+        assert_eq!(
+            DialogBody::from_rich_text(RichText {
+                spans: vec![
+                    Span::Styled(
+                        Style::Bold,
+                        vec![Span::Styled(
+                            Style::Italic,
+                            vec![Span::Text(String::from("both"))]
+                        )]
+                    ),
+                    Span::Text(String::from(" ")),
+                    Span::Styled(Style::Bold, vec![Span::Text(String::from("b"))]),
+                    Span::Text(String::from(" ")),
+                    Span::Styled(Style::Strike, vec![Span::Text(String::from("s"))]),
+                    Span::Text(String::from(" snake_case_name *lit* 2 * 3")),
+                ]
+            }),
+            scenes[0].dialogs[0].args[0]
+        );
+    }
+
+    #[test]
+    fn test_parse_titles() {
+        let md_src = "text\n# Root\n# ???\n# Shop\n## Shop\n";
+        let (data, _) = Markdown::parse_with_namespace(md_src, "");
+        let titles: Vec<String> = match data {
+            KukuriData::Scenes(scenes) => scenes.into_iter().map(|sc| sc.title).collect(),
+            _ => Vec::new(),
+        };
+        assert_eq!(vec!["Root", "Root_2", "Root_3", "Shop", "Shop_2"], titles);
+    }
+}
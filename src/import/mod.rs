@@ -1,13 +1,39 @@
+pub mod command;
+pub mod comment;
+pub mod emphasis;
+pub mod escape;
+pub mod fountain;
+pub mod include;
+pub mod ink;
+pub mod inline;
+pub mod kukuri_json;
 pub mod kukuri_script;
 pub mod kukuri_talkers;
+pub mod kukuri_talkers_toml;
+pub mod macros;
+pub mod markdown;
+pub mod po;
+pub mod renpy;
+pub mod spreadsheet;
+pub mod title;
+pub mod twee;
+pub mod yarn;
 
 const DEFAULT_FALLBACK_TYPE: ImportType = ImportType::KukuriScript;
 
 pub enum ImportType {
     KukuriScript,
     KukuriTalkers,
+    KukuriTalkersToml,
     Yarn,
     Ink,
+    RenPy,
+    Fountain,
+    Twee,
+    Markdown,
+    Csv,
+    Tsv,
+    KukuriJson,
 }
 
 impl ImportType {
@@ -15,8 +41,16 @@ impl ImportType {
         match typename {
             "kukuri" => ImportType::KukuriScript,
             "kukuri_talkers" => ImportType::KukuriTalkers,
+            "kukuri_talkers_toml" => ImportType::KukuriTalkersToml,
             "yarn" => ImportType::Yarn,
             "ink" => ImportType::Ink,
+            "renpy" => ImportType::RenPy,
+            "fountain" => ImportType::Fountain,
+            "twee" => ImportType::Twee,
+            "markdown" => ImportType::Markdown,
+            "csv" => ImportType::Csv,
+            "tsv" => ImportType::Tsv,
+            "kukuri_json" => ImportType::KukuriJson,
             _ => DEFAULT_FALLBACK_TYPE,
         }
     }
@@ -26,9 +60,43 @@ impl ImportType {
         match ext {
             "kkr" => ImportType::KukuriScript,
             "kkrt" => ImportType::KukuriTalkers,
+            "talkers.toml" => ImportType::KukuriTalkersToml,
             "yarn" => ImportType::Yarn,
             "ink" => ImportType::Ink,
+            "rpy" => ImportType::RenPy,
+            "fountain" => ImportType::Fountain,
+            "twee" | "tw" => ImportType::Twee,
+            "md" | "markdown" => ImportType::Markdown,
+            "csv" => ImportType::Csv,
+            "tsv" => ImportType::Tsv,
+            "json" => ImportType::KukuriJson,
             _ => fallback,
         }
     }
 }
+
+// (id, text or label) of dialogs and choices, in source order
+#[cfg(test)]
+pub fn flatten(dialogs: &[crate::core::dialog::Dialog]) -> Vec<(String, String)> {
+    use crate::core::command::CommandArg;
+    use crate::core::dialog::DialogBody;
+
+    let mut v = Vec::new();
+    for d in dialogs {
+        let text = match d.args.first() {
+            Some(DialogBody::Text(s))
+            | Some(DialogBody::Arg(CommandArg::Ident(s)))
+            | Some(DialogBody::Arg(CommandArg::Str(s))) => s.clone(),
+            Some(DialogBody::Rich(rt)) => rt.plain_text(),
+            _ => String::new(),
+        };
+        v.push((d.id.clone(), text));
+        for arg in &d.args {
+            if let DialogBody::Choice(cd) = arg {
+                v.push((cd.id.clone(), cd.label.clone()));
+                v.extend(flatten(&cd.dialogs));
+            }
+        }
+    }
+    v
+}
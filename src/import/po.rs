@@ -0,0 +1,196 @@
+use crate::core::diagnostic::Diagnostic;
+
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct PoEntry {
+    pub msgid: String,
+    pub msgstr: String,
+    // line number of msgid
+    pub line: usize,
+}
+
+// Translated gettext po file
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct PoFile {
+    // "Language" header value
+    pub locale: String,
+    pub entries: Vec<PoEntry>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Field {
+    None,
+    MsgId,
+    MsgStr,
+    // msgctxt, msgid_plural, msgstr[1]...
+    Ignored,
+}
+
+impl PoFile {
+    pub fn parse_with_diagnostics(content: &str) -> (Self, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let mut po = PoFile::default();
+        let mut entry: Option<PoEntry> = None;
+        let mut field = Field::None;
+
+        for (i, full_line) in content.lines().enumerate() {
+            let line_no = i + 1;
+            let line = full_line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, rest) = match line.find('"') {
+                Some(idx) => (line[..idx].trim(), &line[idx..]),
+                None => {
+                    diagnostics.push(Diagnostic::warning(
+                        line_no,
+                        format!("unknown po line \"{}\"", line),
+                    ));
+                    continue;
+                }
+            };
+
+            match keyword {
+                "msgid" => {
+                    if let Some(e) = entry.take() {
+                        po.push_entry(e);
+                    }
+                    entry = Some(PoEntry {
+                        line: line_no,
+                        ..Default::default()
+                    });
+                    field = Field::MsgId;
+                }
+                "msgstr" | "msgstr[0]" => field = Field::MsgStr,
+                // continuation of current field
+                "" => {}
+                _ => field = Field::Ignored,
+            }
+
+            let s = Self::unquote(rest, line_no, &mut diagnostics);
+            match (field, entry.as_mut()) {
+                (Field::MsgId, Some(e)) => e.msgid.push_str(&s),
+                (Field::MsgStr, Some(e)) => e.msgstr.push_str(&s),
+                _ => {}
+            }
+        }
+
+        if let Some(e) = entry.take() {
+            po.push_entry(e);
+        }
+
+        (po, diagnostics)
+    }
+
+    fn push_entry(&mut self, e: PoEntry) {
+        if !e.msgid.is_empty() {
+            self.entries.push(e);
+            return;
+        }
+
+        // header entry
+        for header in e.msgstr.lines() {
+            if let Some((k, v)) = header.split_once(':') {
+                if k.trim() == "Language" {
+                    self.locale = String::from(v.trim());
+                }
+            }
+        }
+    }
+
+    // decode a po (C-like) string literal
+    fn unquote(s: &str, line: usize, diagnostics: &mut Vec<Diagnostic>) -> String {
+        let inner = s
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .unwrap_or_else(|| {
+                diagnostics.push(Diagnostic::warning(
+                    line,
+                    format!("unterminated po string {}", s),
+                ));
+                s.trim_start_matches('"')
+            });
+
+        let mut out_s = String::with_capacity(inner.len());
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out_s.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => out_s.push('\n'),
+                Some('t') => out_s.push('\t'),
+                Some('r') => out_s.push('\r'),
+                Some('f') => out_s.push('\u{c}'),
+                Some(c @ ('"' | '\\')) => out_s.push(c),
+                Some(c) => {
+                    diagnostics.push(Diagnostic::warning(
+                        line,
+                        format!("unknown escape sequence \"\\{}\"", c),
+                    ));
+                    out_s.push('\\');
+                    out_s.push(c);
+                }
+                None => out_s.push('\\'),
+            }
+        }
+        out_s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PoEntry, PoFile};
+    use crate::core::diagnostic::Diagnostic;
+
+    #[test]
+    fn test_parse() {
+        let po_src = r#"msgid ""
+msgstr ""
+"Last-Translator: Automatically generated\n"
+"Language: en\n"
+
+# comment
+msgid "Test_1_A"
+msgstr "Line\nbreak and \"quote\""
+
+msgid "Test_2_B"
+msgstr ""
+"multi "
+"line"
+
+msgctxt "ignored"
+msgid "Test_3_C"
+msgstr "\q"
+"#;
+        let expected = PoFile {
+            locale: String::from("en"),
+            entries: vec![
+                PoEntry {
+                    msgid: String::from("Test_1_A"),
+                    msgstr: String::from("Line\nbreak and \"quote\""),
+                    line: 7,
+                },
+                PoEntry {
+                    msgid: String::from("Test_2_B"),
+                    msgstr: String::from("multi line"),
+                    line: 10,
+                },
+                PoEntry {
+                    msgid: String::from("Test_3_C"),
+                    msgstr: String::from("\\q"),
+                    line: 16,
+                },
+            ],
+        };
+
+        assert_eq!(
+            (
+                expected,
+                vec![Diagnostic::warning(17, "unknown escape sequence \"\\q\"")]
+            ),
+            PoFile::parse_with_diagnostics(po_src)
+        );
+    }
+}
@@ -0,0 +1,632 @@
+use super::{command, comment, escape};
+use crate::core::command::CommandArg;
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
+use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+use crate::core::kukuri_data::KukuriData;
+use crate::core::markup::{RichText, RichTextBuilder, Style};
+use crate::core::namespace;
+use crate::core::talker::Talker;
+
+// statements imported as commands with arguments
+const COMMANDS: [&str; 13] = [
+    "jump", "call", "show", "scene", "hide", "play", "stop", "queue", "pause", "with", "voice",
+    "window", "return",
+];
+// "$ code" => Command "python"
+const PYTHON_COMMAND: &str = "python";
+// talker of a say statement without character
+const NARRATOR: &str = "narrator";
+
+// Import Ren'Py script.
+//
+// label start:                       => Scene "start"
+// e "text", "text"                   => DialogKind::Dialog, talker "e" or "narrator"
+// menu:                              => DialogKind::Choices
+// jump, call, show, scene...         => DialogKind::Command
+// $ code                             => Command "python" with code string
+// define e = Character("Eileen")     => Talker "TALKER_e"
+pub struct RenPy;
+
+// a logical line, comment trimmed
+struct Line {
+    line_no: usize,
+    indent: usize,
+    text: String,
+}
+
+// state while building a label
+struct LabelData<'a> {
+    // global label, for ".local" label names
+    global: &'a str,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl RenPy {
+    // label names are qualified with namespace
+    pub fn parse_with_namespace(content: &str, namespace: &str) -> (KukuriData, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let mut scenes = Vec::new();
+        let mut talkers = Vec::new();
+        let lines = Self::lines(content);
+        let mut global = String::new();
+        let mut i = 0;
+
+        while i < lines.len() {
+            let line = &lines[i];
+            let end = Self::block_end(&lines, i);
+
+            if let Some(header) = line.text.strip_prefix("label ") {
+                let name = Self::label_name(header, &global, line.line_no, &mut diagnostics);
+                if !header.trim_start().starts_with('.') {
+                    global = name.clone();
+                }
+
+                let mut sc = Scene::new();
+                sc.title = namespace::qualify(namespace, &name);
+                sc.loc = SourceLoc::from_line(line.line_no);
+                let mut data = LabelData {
+                    global: &global,
+                    diagnostics: &mut diagnostics,
+                };
+                let mut idx = 0;
+                sc.dialogs =
+                    Self::block_process(&lines[i + 1..end], &sc.title, &mut idx, &mut data);
+                scenes.push(sc);
+            } else if let Some(def) = line.text.strip_prefix("define ") {
+                if let Some(t) = Self::define_process(def, line.line_no, &mut diagnostics) {
+                    talkers.push(t);
+                }
+            } else {
+                let keyword = line
+                    .text
+                    .split(|c: char| c.is_whitespace() || c == ':')
+                    .next();
+                diagnostics.push(Diagnostic::warning(
+                    line.line_no,
+                    format!(
+                        "\"{}\" statement is not supported, ignored",
+                        keyword.unwrap_or("")
+                    ),
+                ));
+            }
+            i = end;
+        }
+
+        (KukuriData::from_script(scenes, talkers), diagnostics)
+    }
+
+    // "start:" => "start", ".local:" => "global.local"
+    fn label_name(
+        header: &str,
+        global: &str,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> String {
+        let header = header.trim().trim_end_matches(':').trim();
+        let name = match header.split_once('(') {
+            Some((name, _)) => {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!(
+                        "parameters of label \"{}\" are not supported, ignored",
+                        header
+                    ),
+                ));
+                name.trim()
+            }
+            None => header,
+        };
+
+        match name.strip_prefix('.') {
+            Some(local) => namespace::qualify(global, local),
+            None => String::from(name),
+        }
+    }
+
+    // "e = Character("Eileen", color="#c8ffc8")" => Talker "TALKER_e"
+    fn define_process(
+        def: &str,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<Talker> {
+        let (var, value) = def.split_once('=')?;
+        let (var, value) = (var.trim(), value.trim());
+
+        let args = match value.strip_prefix("Character(") {
+            Some(args) => args,
+            None => {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!("define \"{}\" is not a Character, ignored", var),
+                ));
+                return None;
+            }
+        };
+
+        // _("Eileen") is a translatable string
+        let args = args.trim_start();
+        let args = args.strip_prefix("_(").unwrap_or(args);
+        let name = match Self::read_string(args) {
+            Some((s, _)) => escape::unescape(s, line_no, diagnostics),
+            None => {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!(
+                        "character \"{}\" has no name string, its name is \"{}\"",
+                        var, var
+                    ),
+                ));
+                String::from(var)
+            }
+        };
+
+        Some(
+            Talker::from_strs(format!("TALKER_{}", var), name)
+                .with_loc(SourceLoc::from_line(line_no)),
+        )
+    }
+
+    // label: id prefix of dialogs in this block
+    fn block_process(
+        lines: &[Line],
+        label: &str,
+        idx: &mut usize,
+        data: &mut LabelData,
+    ) -> Vec<Dialog> {
+        let mut dialogs = Vec::new();
+        let mut i = 0;
+
+        while i < lines.len() {
+            let line = &lines[i];
+            let end = Self::block_end(lines, i);
+            let children = &lines[i + 1..end];
+            let keyword = line
+                .text
+                .split(|c: char| c.is_whitespace() || c == ':')
+                .next()
+                .unwrap_or("");
+
+            match keyword {
+                "menu" => {
+                    let menu = Self::menu_process(line, children, label, idx, data);
+                    dialogs.extend(menu);
+                }
+                "if" | "elif" | "else" | "while" => {
+                    data.diagnostics.push(Diagnostic::warning(
+                        line.line_no,
+                        format!(
+                            "\"{}\" is not supported, lines in it are imported unconditionally",
+                            keyword
+                        ),
+                    ));
+                    dialogs.extend(Self::block_process(children, label, idx, data));
+                }
+                "label" => {
+                    data.diagnostics.push(Diagnostic::warning(
+                        line.line_no,
+                        "nested label is not supported, lines in it are imported in place",
+                    ));
+                    dialogs.extend(Self::block_process(children, label, idx, data));
+                }
+                _ if line.text.starts_with('$') => {
+                    let code = line.text[1..].trim();
+                    let args = vec![DialogBody::Arg(CommandArg::gen_str(code))];
+                    dialogs.push(
+                        Dialog::from_dialog_data(DialogKind::Command, PYTHON_COMMAND, args)
+                            .with_loc(SourceLoc::from_line(line.line_no)),
+                    );
+                }
+                _ if COMMANDS.contains(&keyword) => {
+                    dialogs.push(Self::command_process(line, keyword, data));
+                }
+                _ if line.text.ends_with(':') => {
+                    data.diagnostics.push(Diagnostic::warning(
+                        line.line_no,
+                        format!("\"{}\" block is not supported, ignored", keyword),
+                    ));
+                }
+                _ => match Self::say_process(line, label, idx, data) {
+                    Some(d) => dialogs.push(d),
+                    None => data.diagnostics.push(Diagnostic::warning(
+                        line.line_no,
+                        format!("\"{}\" statement is not supported, ignored", keyword),
+                    )),
+                },
+            }
+            i = end;
+        }
+
+        dialogs
+    }
+
+    // a say statement in menu is the prompt before choices
+    fn menu_process(
+        line: &Line,
+        children: &[Line],
+        label: &str,
+        idx: &mut usize,
+        data: &mut LabelData,
+    ) -> Vec<Dialog> {
+        // prompts are numbered before the choices
+        let mut dialogs: Vec<Dialog> = children
+            .iter()
+            .filter(|c| c.indent == children[0].indent && !c.text.ends_with(':'))
+            .filter_map(|c| Self::say_process(c, label, idx, data))
+            .collect();
+
+        *idx += 1;
+        let choices_id = format!("{}_{}_C1", label, idx);
+        let mut choices = Dialog::from_dialog_data(DialogKind::Choices, &choices_id, Vec::new())
+            .with_loc(SourceLoc::from_line(line.line_no));
+
+        let mut i = 0;
+        while i < children.len() {
+            let child = &children[i];
+            let end = Self::block_end(children, i);
+            if !child.text.ends_with(':') {
+                i = end;
+                continue;
+            }
+
+            let header = child.text.trim_end_matches(':').trim_end();
+            let (text, rest) = match Self::read_string(header) {
+                Some(x) => x,
+                None => {
+                    data.diagnostics.push(Diagnostic::warning(
+                        child.line_no,
+                        format!("\"{}\" in menu is not supported, ignored", header),
+                    ));
+                    i = end;
+                    continue;
+                }
+            };
+            if !rest.trim().is_empty() {
+                data.diagnostics.push(Diagnostic::warning(
+                    child.line_no,
+                    format!(
+                        "choice condition \"{}\" is not supported, the choice is always shown",
+                        rest.trim()
+                    ),
+                ));
+            }
+
+            let id = format!("{}L{}", choices.id, choices.args.len() + 1);
+            let rich = Self::text_process(text, child.line_no, data.diagnostics);
+            let mut cd = ChoiceData::from_texts(&id, rich.plain_text())
+                .with_loc(SourceLoc::from_line(child.line_no));
+            let mut inner_idx = 0;
+            cd.dialogs = Self::block_process(&children[i + 1..end], &id, &mut inner_idx, data);
+            choices.args.push(DialogBody::Choice(cd));
+            i = end;
+        }
+
+        dialogs.push(choices);
+        dialogs
+    }
+
+    // "e happy "text" with vpunch" => Dialog, talker "e"
+    fn say_process(
+        line: &Line,
+        label: &str,
+        idx: &mut usize,
+        data: &mut LabelData,
+    ) -> Option<Dialog> {
+        let quote = line.text.find(['"', '\''])?;
+        let (who, s) = line.text.split_at(quote);
+        let mut who_words = who.split_whitespace();
+        let talker = who_words.next().unwrap_or(NARRATOR);
+        let is_identifiers = who
+            .split_whitespace()
+            .all(|w| w.chars().all(|c| c.is_alphanumeric() || c == '_'));
+        if !is_identifiers {
+            return None;
+        }
+        if who_words.next().is_some() {
+            data.diagnostics.push(Diagnostic::warning(
+                line.line_no,
+                format!("image attributes of \"{}\" are ignored", who.trim()),
+            ));
+        }
+
+        let (text, rest) = Self::read_string(s)?;
+        if !rest.trim().is_empty() {
+            data.diagnostics.push(Diagnostic::warning(
+                line.line_no,
+                format!("\"{}\" after say statement is ignored", rest.trim()),
+            ));
+        }
+
+        *idx += 1;
+        let rich = Self::text_process(text, line.line_no, data.diagnostics);
+        let body = vec![
+            DialogBody::from_rich_text(rich),
+            DialogBody::gen_text(format!("TALKER_{}", talker)),
+        ];
+        let id = format!("{}_{}_{}", label, idx, talker);
+        Some(
+            Dialog::from_dialog_data(DialogKind::Dialog, id, body)
+                .with_loc(SourceLoc::from_line(line.line_no)),
+        )
+    }
+
+    fn command_process(line: &Line, keyword: &str, data: &mut LabelData) -> Dialog {
+        let args_str = line.text[keyword.len()..].trim();
+        let mut args = command::parse_args(args_str, line.line_no, data.diagnostics);
+
+        // "jump .local" => "global.local"
+        if keyword == "jump" || keyword == "call" {
+            if let Some(CommandArg::Ident(s)) | Some(CommandArg::Str(s)) = args.first_mut() {
+                if let Some(local) = s.strip_prefix('.') {
+                    *s = namespace::qualify(data.global, local);
+                }
+            }
+        }
+
+        let args = args.into_iter().map(DialogBody::Arg).collect();
+        Dialog::from_dialog_data(DialogKind::Command, keyword, args)
+            .with_loc(SourceLoc::from_line(line.line_no))
+    }
+
+    // Ren'Py text tags into span tree
+    //
+    // {b}text{/b}, {color=#f00}text{/color} => styled span
+    // {{, [[                                => literal "{", "["
+    // [name]                                => kept as text
+    fn text_process(s: &str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> RichText {
+        let s = escape::unescape(s, line_no, diagnostics);
+        let mut builder = RichTextBuilder::new();
+        let mut rest = s.as_str();
+
+        while let Some(c) = rest.chars().next() {
+            let (tag_open, tag_close) = match c {
+                '{' | '[' if rest[1..].starts_with(c) => {
+                    builder.push_str(&rest[..1]);
+                    rest = &rest[2..];
+                    continue;
+                }
+                '{' => ('{', '}'),
+                '[' => ('[', ']'),
+                _ => {
+                    builder.push_str(c.encode_utf8(&mut [0; 4]));
+                    rest = &rest[c.len_utf8()..];
+                    continue;
+                }
+            };
+
+            let body = match rest[1..].find(tag_close) {
+                Some(i) => &rest[1..i + 1],
+                None => {
+                    builder.push_str(&rest[..1]);
+                    rest = &rest[1..];
+                    continue;
+                }
+            };
+            rest = &rest[body.len() + 2..];
+
+            if tag_open == '[' {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!(
+                        "interpolation \"[{}]\" is not supported, kept as text",
+                        body
+                    ),
+                ));
+                builder.push_str(&format!("[{}]", body));
+                continue;
+            }
+
+            if let Some(name) = body.strip_prefix('/') {
+                if let Err(e) = builder.close(name) {
+                    diagnostics.push(Diagnostic::warning(line_no, e));
+                }
+                continue;
+            }
+            match Style::parse(body) {
+                Some(style) => builder.open(style),
+                None => diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!("text tag \"{{{}}}\" is not supported, removed", body),
+                )),
+            }
+        }
+
+        let (rich, unclosed) = builder.finish();
+        for name in unclosed {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!("unclosed text tag \"{{{}}}\"", name),
+            ));
+        }
+        rich
+    }
+
+    // read a quoted string at the start of s.
+    // return: (raw string body, rest)
+    fn read_string(s: &str) -> Option<(&str, &str)> {
+        let s = s.trim_start();
+        let quote = s.chars().next().filter(|&c| c == '"' || c == '\'')?;
+        let mut is_escaped = false;
+        for (i, c) in s.char_indices().skip(1) {
+            match c {
+                _ if is_escaped => is_escaped = false,
+                '\\' => is_escaped = true,
+                _ if c == quote => return Some((&s[1..i], &s[i + 1..])),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // index after the block of lines[i]
+    fn block_end(lines: &[Line], i: usize) -> usize {
+        lines[i + 1..]
+            .iter()
+            .position(|l| l.indent <= lines[i].indent)
+            .map_or(lines.len(), |n| i + 1 + n)
+    }
+
+    fn lines(content: &str) -> Vec<Line> {
+        content
+            .lines()
+            .enumerate()
+            .filter_map(|(i, full_line)| {
+                let line = comment::trim_line(full_line, '#', &['"', '\'']);
+                let text = line.trim();
+                if text.is_empty() {
+                    return None;
+                }
+                Some(Line {
+                    line_no: i + 1,
+                    indent: line.len() - line.trim_start().len(),
+                    text: String::from(text),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RenPy;
+    use crate::core::dialog::{DialogBody, DialogKind};
+    use crate::core::kukuri_data::KukuriData;
+    use crate::core::markup::{RichText, Span, Style};
+    use crate::core::talker::Talker;
+    use crate::import::flatten;
+
+    #[test]
+    fn test_parse() {
+        let rpy_src = r##"
+define e = Character("Eileen", color="#c8ffc8")  # heroine
+define l = Character(_("Lucy"))
+
+label start:
+    scene bg room
+    show eileen happy
+    e "Hello, \"world\"!"
+    "It's # not a comment."
+    menu:
+        e "Where to?"
+        "Park":
+            $ park_visits += 1
+            jump .park
+        "Home" if tired:
+            l "Let's go home."
+    return
+
+label .park:
+    e happy "Nice park." with dissolve
+"##;
+        let (data, _) = RenPy::parse_with_namespace(rpy_src, "");
+        let (scenes, talkers) = match data.without_locs() {
+            KukuriData::Script(scenes, talkers) => (scenes, talkers),
+            _ => (Vec::new(), Vec::new()),
+        };
+
+        assert_eq!(
+            vec![
+                Talker::from_strs("TALKER_e", "Eileen"),
+                Talker::from_strs("TALKER_l", "Lucy"),
+            ],
+            talkers
+        );
+        let titles: Vec<&str> = scenes.iter().map(|sc| sc.title.as_str()).collect();
+        assert_eq!(vec!["start", "start.park"], titles);
+
+        let expected: Vec<(&str, &str)> = vec![
+            ("scene", "bg"),
+            ("show", "eileen"),
+            ("start_1_e", "Hello, \"world\"!"),
+            ("start_2_narrator", "It's # not a comment."),
+            ("start_3_e", "Where to?"),
+            ("start_4_C1", ""),
+            ("start_4_C1L1", "Park"),
+            ("python", "park_visits += 1"),
+            ("jump", "start.park"),
+            ("start_4_C1L2", "Home"),
+            ("start_4_C1L2_1_l", "Let's go home."),
+            ("return", ""),
+        ];
+        let actual = flatten(&scenes[0].dialogs);
+        let actual: Vec<(&str, &str)> = actual
+            .iter()
+            .map(|(id, s)| (id.as_str(), s.as_str()))
+            .collect();
+        assert_eq!(expected, actual);
+        assert_eq!(DialogKind::Choices, scenes[0].dialogs[5].kind);
+        assert_eq!(
+            vec![
+                DialogBody::Text(String::from("Nice park.")),
+                DialogBody::Text(String::from("TALKER_e")),
+            ],
+            scenes[1].dialogs[0].args
+        );
+    }
+
+    #[test]
+    fn test_text_tags() {
+        let rpy_src = "label a:\n    \"{b}Bold{/b} {{not tag} [[x]{w} {color=#f00}red{/color}\"\n";
+        let (data, diagnostics) = RenPy::parse_with_namespace(rpy_src, "");
+        let scenes = match data {
+            KukuriData::Script(scenes, _) => scenes,
+            _ => Vec::new(),
+        };
+
+        let expected = RichText {
+            spans: vec![
+                Span::Styled(Style::Bold, vec![Span::Text(String::from("Bold"))]),
+                Span::Text(String::from(" {not tag} [x] ")),
+                Span::Styled(
+                    Style::Color(String::from("#f00")),
+                    vec![Span::Text(String::from("red"))],
+                ),
+            ],
+        };
+        assert_eq!(
+            DialogBody::from_rich_text(expected),
+            scenes[0].dialogs[0].args[0]
+        );
+        assert_eq!(
+            vec!["2: warning: text tag \"{w}\" is not supported, removed"],
+            diagnostics
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let rpy_src = r#"
+default points = 0
+init python:
+    x = 1
+label start(who):
+    if points > 1:
+        e "High"
+    e "[who] wins"
+    window hide
+    nvl clear
+"#;
+        let (data, diagnostics) = RenPy::parse_with_namespace(rpy_src, "");
+        let scenes = match data {
+            KukuriData::Script(scenes, _) => scenes,
+            _ => Vec::new(),
+        };
+
+        let ids: Vec<&str> = scenes[0].dialogs.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(vec!["start_1_e", "start_2_e", "window"], ids);
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            vec![
+                "2: warning: \"default\" statement is not supported, ignored",
+                "3: warning: \"init\" statement is not supported, ignored",
+                "5: warning: parameters of label \"start(who)\" are not supported, ignored",
+                "6: warning: \"if\" is not supported, lines in it are imported unconditionally",
+                "8: warning: interpolation \"[who]\" is not supported, kept as text",
+                "10: warning: \"nvl\" statement is not supported, ignored",
+            ],
+            messages
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use crate::config::spreadsheet::SpreadsheetConfig;
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
+use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+use crate::core::kukuri_data::KukuriData;
+use crate::core::markup::RichText;
+use crate::core::namespace;
+use std::collections::HashMap;
+
+// scene of rows before any scene title
+const ROOT_SCENE: &str = "Root";
+
+// Import dialog lines from CSV/TSV spreadsheet.
+//
+// scene,id,type,talker,text,parent
+// Shop,,,Alice,Welcome!,          => DialogKind::Dialog
+// Shop,buy,option,,Buy,           => option of DialogKind::Choices
+// Shop,,,Alice,Thanks.,buy        => dialog in option "buy"
+//
+// columns are found by header names of SpreadsheetConfig
+pub struct Spreadsheet;
+
+const OPTION_TYPE: &str = "option";
+const DIALOG_TYPE: &str = "dialog";
+
+struct Row {
+    line_no: usize,
+    id: String,
+    is_option: bool,
+    talker: String,
+    text: String,
+    parent: String,
+}
+
+// rows of a scene, and the option each row belongs to
+struct SceneRows {
+    // qualified scene title
+    title: String,
+    rows: Vec<Row>,
+    // None => scene top level
+    containers: Vec<Option<usize>>,
+}
+
+impl Spreadsheet {
+    // scene titles are qualified with namespace
+    pub fn parse_with_namespace(
+        content: &str,
+        namespace: &str,
+        delimiter: char,
+        conf: &SpreadsheetConfig,
+    ) -> (KukuriData, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let mut records = Self::records(content, delimiter, &mut diagnostics).into_iter();
+
+        let header = match records.next() {
+            Some((_, header)) => header,
+            None => return (KukuriData::new(), diagnostics),
+        };
+        let column = |name: &str| header.iter().position(|h| h.trim() == name);
+        let text_column = match column(&conf.text) {
+            Some(c) => c,
+            None => {
+                diagnostics.push(Diagnostic::error(
+                    1,
+                    format!("text column \"{}\" is not found in header", conf.text),
+                ));
+                return (KukuriData::new(), diagnostics);
+            }
+        };
+        let columns = [
+            column(&conf.scene),
+            column(&conf.id),
+            column(&conf.kind),
+            column(&conf.talker),
+            Some(text_column),
+            column(&conf.parent),
+        ];
+
+        // scene title: rows, in order of first appearance
+        let mut titles: Vec<String> = Vec::new();
+        let mut scene_rows: HashMap<String, Vec<Row>> = HashMap::new();
+        let mut title = String::from(ROOT_SCENE);
+        for (line_no, record) in records {
+            let [scene, id, kind, talker, text, parent] =
+                columns.map(|c| c.and_then(|c| record.get(c)).map_or("", |s| s.trim()));
+            if !scene.is_empty() {
+                title = String::from(scene);
+            }
+            if text.is_empty() {
+                // e.g. a row of scene title only
+                if id.is_empty() && kind.is_empty() {
+                    continue;
+                }
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    "row has no text, imported as an empty text",
+                ));
+            }
+
+            let is_option = kind == OPTION_TYPE;
+            if !is_option && !kind.is_empty() && kind != DIALOG_TYPE {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!("unknown row type \"{}\", read as a dialog", kind),
+                ));
+            }
+
+            let row = Row {
+                line_no,
+                id: String::from(id),
+                is_option,
+                talker: String::from(talker),
+                text: String::from(text),
+                parent: String::from(parent),
+            };
+            if !scene_rows.contains_key(&title) {
+                titles.push(title.clone());
+            }
+            scene_rows.entry(title.clone()).or_default().push(row);
+        }
+
+        let scenes = titles
+            .into_iter()
+            .map(|title| {
+                let rows = scene_rows.remove(&title).unwrap_or_default();
+                let title = namespace::qualify(namespace, &title);
+                let scene_rows = SceneRows::new(title, rows, &mut diagnostics);
+
+                let mut sc = Scene::new();
+                sc.title = scene_rows.title.clone();
+                sc.loc = SourceLoc::from_line(scene_rows.rows[0].line_no);
+                sc.dialogs = scene_rows.build(None, &sc.title);
+                sc
+            })
+            .collect();
+
+        (KukuriData::from_scenes(scenes), diagnostics)
+    }
+
+    // RFC 4180 records, quoted fields may have delimiters, line breaks and "" as a quote.
+    // return: (line_no, fields)
+    fn records(
+        content: &str,
+        delimiter: char,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<(usize, Vec<String>)> {
+        let mut records = Vec::new();
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut is_quoted = false;
+        let mut line_no = 1;
+        let mut record_line_no = 1;
+        let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if is_quoted => {
+                    if chars.peek() == Some(&'"') {
+                        field.push('"');
+                        chars.next();
+                    } else {
+                        is_quoted = false;
+                    }
+                }
+                '"' if field.is_empty() => is_quoted = true,
+                '\n' if is_quoted => {
+                    field.push('\n');
+                    line_no += 1;
+                }
+                '\r' if chars.peek() == Some(&'\n') => {}
+                '\n' => {
+                    fields.push(std::mem::take(&mut field));
+                    // blank lines are skipped
+                    let record = std::mem::take(&mut fields);
+                    if record.iter().any(|f| !f.is_empty()) {
+                        records.push((record_line_no, record));
+                    }
+                    line_no += 1;
+                    record_line_no = line_no;
+                }
+                _ if c == delimiter && !is_quoted => fields.push(std::mem::take(&mut field)),
+                _ => field.push(c),
+            }
+        }
+
+        if is_quoted {
+            diagnostics.push(Diagnostic::warning(
+                record_line_no,
+                "quoted field is not closed",
+            ));
+        }
+        fields.push(field);
+        if fields.iter().any(|f| !f.is_empty()) {
+            records.push((record_line_no, fields));
+        }
+
+        records
+    }
+}
+
+impl SceneRows {
+    // parents are resolved to the rows defined before
+    fn new(title: String, rows: Vec<Row>, diagnostics: &mut Vec<Diagnostic>) -> Self {
+        let mut ids: HashMap<&str, usize> = HashMap::new();
+        let mut containers = Vec::with_capacity(rows.len());
+
+        for (i, row) in rows.iter().enumerate() {
+            let container = if row.parent.is_empty() {
+                None
+            } else {
+                match ids.get(row.parent.as_str()) {
+                    // options of a dialog follow it in the same container
+                    Some(&p) if rows[p].is_option => Some(p),
+                    Some(&p) => containers[p],
+                    None => {
+                        diagnostics.push(Diagnostic::warning(
+                            row.line_no,
+                            format!(
+                                "parent \"{}\" is not defined before this row, placed at scene top level",
+                                row.parent
+                            ),
+                        ));
+                        None
+                    }
+                }
+            };
+            containers.push(container);
+            if row.id.is_empty() {
+                continue;
+            }
+            if let Some(p) = ids.insert(&row.id, i) {
+                diagnostics.push(Diagnostic::warning(
+                    row.line_no,
+                    format!(
+                        "id \"{}\" is already defined at line {}, later rows refer to this row",
+                        row.id, rows[p].line_no
+                    ),
+                ));
+            }
+        }
+
+        Self {
+            title,
+            rows,
+            containers,
+        }
+    }
+
+    // given ids are qualified with scene title, e.g. "buy" => "Shop_buy"
+    fn given_id(&self, row: &Row) -> Option<String> {
+        if row.id.is_empty() {
+            None
+        } else {
+            Some(format!("{}_{}", self.title, row.id))
+        }
+    }
+
+    // dialogs in the container, consecutive options are a choices
+    fn build(&self, container: Option<usize>, label: &str) -> Vec<Dialog> {
+        let mut dialogs: Vec<Dialog> = Vec::new();
+        let mut idx = 0;
+        let mut is_in_choices = false;
+
+        for (i, row) in self.rows.iter().enumerate() {
+            if self.containers[i] != container {
+                continue;
+            }
+            let loc = SourceLoc::from_line(row.line_no);
+
+            if !row.is_option {
+                is_in_choices = false;
+                idx += 1;
+                let talker = if row.talker.is_empty() {
+                    "unknown"
+                } else {
+                    row.talker.as_str()
+                };
+                let id = self
+                    .given_id(row)
+                    .unwrap_or_else(|| format!("{}_{}_{}", label, idx, talker));
+                let body = vec![
+                    DialogBody::from_rich_text(RichText::from_plain(&row.text)),
+                    DialogBody::gen_text(format!("TALKER_{}", talker)),
+                ];
+                dialogs.push(Dialog::from_dialog_data(DialogKind::Dialog, id, body).with_loc(loc));
+                continue;
+            }
+
+            if !is_in_choices {
+                is_in_choices = true;
+                idx += 1;
+                let choices_id = format!("{}_{}_C1", label, idx);
+                dialogs.push(
+                    Dialog::from_dialog_data(DialogKind::Choices, choices_id, Vec::new())
+                        .with_loc(loc.clone()),
+                );
+            }
+            if let Some(choices) = dialogs.last_mut() {
+                let id = self
+                    .given_id(row)
+                    .unwrap_or_else(|| format!("{}L{}", choices.id, choices.args.len() + 1));
+                let mut cd = ChoiceData::from_texts(&id, &row.text).with_loc(loc);
+                cd.dialogs = self.build(Some(i), &id);
+                choices.args.push(DialogBody::Choice(cd));
+            }
+        }
+
+        dialogs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spreadsheet;
+    use crate::config::spreadsheet::SpreadsheetConfig;
+    use crate::core::kukuri_data::KukuriData;
+    use crate::import::flatten;
+
+    #[test]
+    fn test_parse() {
+        let csv_src = "\u{feff}scene,id,type,talker,text,parent\r\n\
+Shop,,,Alice,\"Welcome, \"\"friend\"\"!\",\r\n\
+,ask,,Alice,What do you need?,\r\n\
+,buy,option,,Buy,ask\r\n\
+,,,Alice,\"Thanks.\nCome again.\",buy\r\n\
+,,,,(She smiles.),buy\r\n\
+,wrap,option,,Gift wrap?,buy\r\n\
+,,,Alice,Wrapped.,wrap\r\n\
+,leave,option,,Leave,ask\r\n\
+\r\n\
+Town,,,Bob,Hi.,\r\n\
+,buy,option,,,\r\n\
+,,,Bob,Here.,buy\r\n\
+,buy,option,,Again,\r\n\
+,,dialogue,Bob,Lost.,nowhere\r\n";
+        let (data, diagnostics) =
+            Spreadsheet::parse_with_namespace(csv_src, "", ',', &SpreadsheetConfig::default());
+        let scenes = match data {
+            KukuriData::Scenes(scenes) => scenes,
+            _ => Vec::new(),
+        };
+
+        let titles: Vec<&str> = scenes.iter().map(|sc| sc.title.as_str()).collect();
+        assert_eq!(vec!["Shop", "Town"], titles);
+
+        let expected: Vec<(&str, &str)> = vec![
+            ("Shop_1_Alice", "Welcome, \"friend\"!"),
+            ("Shop_ask", "What do you need?"),
+            ("Shop_3_C1", ""),
+            ("Shop_buy", "Buy"),
+            ("Shop_buy_1_Alice", "Thanks.\nCome again."),
+            ("Shop_buy_2_unknown", "(She smiles.)"),
+            ("Shop_buy_3_C1", ""),
+            ("Shop_wrap", "Gift wrap?"),
+            ("Shop_wrap_1_Alice", "Wrapped."),
+            ("Shop_leave", "Leave"),
+        ];
+        let actual = flatten(&scenes[0].dialogs);
+        let actual: Vec<(&str, &str)> = actual
+            .iter()
+            .map(|(id, s)| (id.as_str(), s.as_str()))
+            .collect();
+        assert_eq!(expected, actual);
+        assert_eq!(4, scenes[0].dialogs[2].loc.line);
+
+        let expected: Vec<(&str, &str)> = vec![
+            ("Town_1_Bob", "Hi."),
+            ("Town_2_C1", ""),
+            ("Town_buy", ""),
+            ("Town_buy_1_Bob", "Here."),
+            ("Town_buy", "Again"),
+            ("Town_3_Bob", "Lost."),
+        ];
+        let actual = flatten(&scenes[1].dialogs);
+        let actual: Vec<(&str, &str)> = actual
+            .iter()
+            .map(|(id, s)| (id.as_str(), s.as_str()))
+            .collect();
+        assert_eq!(expected, actual);
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            vec![
+                "13: warning: row has no text, imported as an empty text",
+                "16: warning: unknown row type \"dialogue\", read as a dialog",
+                "15: warning: id \"buy\" is already defined at line 13, later rows refer to this row",
+                "16: warning: parent \"nowhere\" is not defined before this row, placed at scene top level",
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn test_parse_columns() {
+        let tsv_src = "Line\tWho\tScene\n\"Hi\tthere\"\tA\tIntro\n";
+        let conf = SpreadsheetConfig {
+            scene: String::from("Scene"),
+            talker: String::from("Who"),
+            text: String::from("Line"),
+            ..Default::default()
+        };
+        let (data, diagnostics) = Spreadsheet::parse_with_namespace(tsv_src, "ch1", '\t', &conf);
+        let scenes = match data {
+            KukuriData::Scenes(scenes) => scenes,
+            _ => Vec::new(),
+        };
+        assert!(diagnostics.is_empty());
+        assert_eq!("ch1.Intro", scenes[0].title);
+        assert_eq!(
+            vec![(String::from("ch1.Intro_1_A"), String::from("Hi\tthere"))],
+            flatten(&scenes[0].dialogs)
+        );
+
+        let (_, diagnostics) =
+            Spreadsheet::parse_with_namespace("a,b\n1,2\n", "", ',', &SpreadsheetConfig::default());
+        assert_eq!(
+            "1: error: text column \"text\" is not found in header",
+            diagnostics[0].to_string()
+        );
+    }
+}
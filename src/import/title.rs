@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+// "Dark Forest" => "Dark_Forest", "INT. HOUSE - DAY" => "INT_HOUSE_DAY"
+pub fn to_ident(s: &str) -> String {
+    s.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<&str>>()
+        .join("_")
+}
+
+// "Dark Forest" => "Dark_Forest", "???" => fallback
+pub fn to_title(name: &str, fallback: &str) -> String {
+    let title = to_ident(name);
+    if title.is_empty() {
+        String::from(fallback)
+    } else {
+        title
+    }
+}
+
+// Scene titles of names in a file.
+// a name without ident chars is titled fallback,
+// and a duplicated title gets a suffix "_2", "_3", ...
+pub struct Titles {
+    fallback: &'static str,
+    counts: BTreeMap<String, usize>,
+}
+
+impl Titles {
+    pub fn new(fallback: &'static str) -> Self {
+        Titles {
+            fallback,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn unique(&mut self, name: &str) -> String {
+        let title = to_title(name, self.fallback);
+        let count = self.counts.entry(title.clone()).or_insert(0);
+        *count += 1;
+        if *count > 1 {
+            format!("{}_{}", title, count)
+        } else {
+            title
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Titles;
+
+    #[test]
+    fn test_unique() {
+        let mut titles = Titles::new("Root");
+        let names = ["Dark Forest", "Dark-Forest", "???", "", "Dark Forest"];
+        let expected = [
+            "Dark_Forest",
+            "Dark_Forest_2",
+            "Root",
+            "Root_2",
+            "Dark_Forest_3",
+        ];
+        for (name, expected) in names.iter().zip(&expected) {
+            assert_eq!(*expected, titles.unique(name));
+        }
+    }
+}
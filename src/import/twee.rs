@@ -0,0 +1,573 @@
+use super::title::{self, Titles};
+use super::{comment, escape};
+use crate::core::command::{CommandArg, InlineCommand};
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
+use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+use crate::core::kukuri_data::KukuriData;
+use crate::core::markup::RichText;
+use crate::core::meta::MetaValue;
+use crate::core::namespace;
+
+const PASSAGE_HEADER: &str = "::";
+// title of passages without ident chars in the name
+const ROOT_SCENE: &str = "Root";
+// special passages of story format, not story content
+const SPECIAL_PASSAGES: [&str; 2] = ["StoryTitle", "StoryData"];
+const SPECIAL_TAGS: [&str; 3] = ["script", "stylesheet", "widget"];
+// macros without a kukuri counterpart, argument is the macro source
+const RAW_COMMAND: &str = "raw";
+const JUMP_COMMAND: &str = "jump";
+// Harlowe "(go-to: "X")", SugarCube "<<goto "X">>"
+const GOTO_MACROS: [&str; 2] = ["go-to", "goto"];
+// "/* */", "<!-- -->" and "/% %/" comments
+const COMMENTS: [(&str, &str); 3] = [("/*", "*/"), ("<!--", "-->"), ("/%", "%/")];
+
+// Import Twine 2 story in Twee 3 format.
+//
+// :: Name [tags] {"position":"0,0"}  => Scene "Name", tags and position are scene metadata
+// Name: text                         => DialogKind::Dialog
+// [[text->Target]]                   => option of DialogKind::Choices, jumps to "Target"
+// <<goto "X">>, (go-to: "X")         => Command "jump"
+// <<macro>>, (macro: args)[hook]     => Command "raw" with the macro source
+pub struct Twee;
+
+// (line_no, line)
+type Line<'a> = (usize, &'a str);
+
+enum Segment<'a> {
+    Text(&'a str),
+    // (text, target)
+    Link(&'a str, &'a str),
+    Macro(&'a str),
+}
+
+impl Twee {
+    // passage names are qualified with namespace
+    pub fn parse_with_namespace(content: &str, namespace: &str) -> (KukuriData, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let mut scenes = Vec::new();
+        let content = comment::strip(content, &COMMENTS);
+        // (header line_no, header, body lines)
+        let mut passages: Vec<(usize, &str, Vec<Line>)> = Vec::new();
+
+        // lines before the first passage are ignored
+        for (i, line) in content.lines().enumerate() {
+            match line.strip_prefix(PASSAGE_HEADER) {
+                Some(header) => passages.push((i + 1, header, Vec::new())),
+                None => {
+                    if let Some(p) = passages.last_mut() {
+                        p.2.push((i + 1, line));
+                    }
+                }
+            }
+        }
+
+        let mut titles = Titles::new(ROOT_SCENE);
+        for (line_no, header, body) in passages {
+            let sc = Self::passage_process(
+                line_no,
+                header,
+                &body,
+                namespace,
+                &mut titles,
+                &mut diagnostics,
+            );
+            if let Some(sc) = sc {
+                scenes.push(sc);
+            }
+        }
+
+        (KukuriData::from_scenes(scenes), diagnostics)
+    }
+
+    fn passage_process(
+        line_no: usize,
+        header: &str,
+        body: &[Line],
+        namespace: &str,
+        titles: &mut Titles,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<Scene> {
+        let (name, tags, metadata) = Self::split_header(header, line_no, diagnostics);
+        if SPECIAL_PASSAGES.contains(&name.as_str()) {
+            return None;
+        }
+        if let Some(t) = tags.iter().find(|t| SPECIAL_TAGS.contains(&t.as_str())) {
+            if t == "widget" {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!("widget passage \"{}\" is not supported, ignored", name),
+                ));
+            }
+            return None;
+        }
+
+        let title = titles.unique(&name);
+        let mut sc = Scene::new();
+        sc.title = namespace::qualify(namespace, &title);
+        sc.loc = SourceLoc::from_line(line_no);
+        if title != name {
+            sc.meta.insert(String::from("name"), MetaValue::Str(name));
+        }
+        if !tags.is_empty() {
+            let tags = tags.into_iter().map(MetaValue::Str).collect();
+            sc.meta.insert(String::from("tags"), MetaValue::Array(tags));
+        }
+        if let Some(json) = metadata {
+            Self::metadata_process(json, &mut sc, line_no, diagnostics);
+        }
+
+        let mut idx = 0;
+        let mut links = Vec::new();
+        for &(n, line) in body {
+            let dialogs = Self::line_process(line, n, &sc.title, &mut idx, &mut links, diagnostics);
+            sc.dialogs.extend(dialogs);
+        }
+
+        // links of the passage are options at the end
+        if !links.is_empty() {
+            idx += 1;
+            let choices_id = format!("{}_{}_C1", sc.title, idx);
+            let mut choices =
+                Dialog::from_dialog_data(DialogKind::Choices, &choices_id, Vec::new())
+                    .with_loc(SourceLoc::from_line(links[0].0));
+            for (i, (n, text, target)) in links.into_iter().enumerate() {
+                let id = format!("{}L{}", choices_id, i + 1);
+                let mut cd = ChoiceData::from_texts(&id, text).with_loc(SourceLoc::from_line(n));
+                cd.dialogs.push(Self::gen_jump(&target, n));
+                choices.args.push(DialogBody::Choice(cd));
+            }
+            sc.dialogs.push(choices);
+        }
+
+        Some(sc)
+    }
+
+    // "Name [tag1 tag2] {json}"
+    // return: (name, tags, metadata json)
+    fn split_header<'a>(
+        header: &'a str,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> (String, Vec<String>, Option<&'a str>) {
+        let header = header.trim();
+        let name_end = [
+            escape::find_unescaped(header, '['),
+            escape::find_unescaped(header, '{'),
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+        .unwrap_or(header.len());
+        let name = escape::unescape(header[..name_end].trim(), line_no, diagnostics);
+        let rest = &header[name_end..];
+
+        let (tags, rest) = match rest.strip_prefix('[') {
+            Some(r) => match escape::find_unescaped(r, ']') {
+                Some(i) => (
+                    r[..i]
+                        .split_whitespace()
+                        .map(|t| escape::unescape(t, line_no, diagnostics))
+                        .collect(),
+                    r[i + 1..].trim(),
+                ),
+                None => (Vec::new(), r),
+            },
+            None => (Vec::new(), rest),
+        };
+        let metadata = Some(rest).filter(|r| r.starts_with('{'));
+
+        (name, tags, metadata)
+    }
+
+    // {"position":"600,400","size":"100,200"}
+    fn metadata_process(
+        json: &str,
+        sc: &mut Scene,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let map = match serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(json) {
+            Ok(map) => map,
+            Err(e) => {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!("passage metadata is not valid json, ignored: {}", e),
+                ));
+                return;
+            }
+        };
+
+        for (key, value) in map {
+            if let serde_json::Value::String(s) = value {
+                sc.meta.insert(key, MetaValue::Str(s));
+            }
+        }
+    }
+
+    // links are pushed to links, and dialogs of the line are returned
+    fn line_process(
+        line: &str,
+        line_no: usize,
+        label: &str,
+        idx: &mut usize,
+        links: &mut Vec<(usize, String, String)>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<Dialog> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Vec::new();
+        }
+
+        // "Alice: text", a colon in links or macros is not a talker separator
+        let (talker, line) = match line.find(':') {
+            Some(i) if !line[..i].contains(['[', '<', '(']) && !line[i + 1..].trim().is_empty() => {
+                (line[..i].trim(), line[i + 1..].trim_start())
+            }
+            _ => ("unknown", line),
+        };
+
+        let segments = Self::split_segments(line, line_no, diagnostics);
+        let has_text = segments
+            .iter()
+            .any(|s| matches!(s, Segment::Text(t) if !t.trim().is_empty()));
+
+        let mut text = String::new();
+        let mut inline_commands = Vec::new();
+        let mut commands = Vec::new();
+        for seg in segments {
+            match seg {
+                Segment::Text(t) => text.push_str(t),
+                Segment::Link(t, target) => {
+                    if has_text {
+                        text.push_str(t);
+                    }
+                    links.push((line_no, String::from(t), String::from(target)));
+                }
+                Segment::Macro(m) => match Self::goto_target(m) {
+                    Some(target) => commands.push(Self::gen_jump(target, line_no)),
+                    None if has_text => {
+                        let offset = text.chars().count();
+                        let args = vec![CommandArg::gen_str(m)];
+                        inline_commands.push(InlineCommand::from_command_data(
+                            offset,
+                            RAW_COMMAND,
+                            args,
+                        ));
+                    }
+                    None => {
+                        let args = vec![DialogBody::Arg(CommandArg::gen_str(m))];
+                        commands.push(
+                            Dialog::from_dialog_data(DialogKind::Command, RAW_COMMAND, args)
+                                .with_loc(SourceLoc::from_line(line_no)),
+                        );
+                    }
+                },
+            }
+        }
+
+        let mut dialogs = Vec::new();
+        if has_text {
+            *idx += 1;
+            let body = vec![
+                DialogBody::from_rich_text(RichText::from_plain(text.trim_end())),
+                DialogBody::gen_text(format!("TALKER_{}", talker)),
+            ];
+            let id = format!("{}_{}_{}", label, idx, talker);
+            dialogs.push(
+                Dialog::from_dialog_data(DialogKind::Dialog, id, body)
+                    .with_inline_commands(inline_commands)
+                    .with_loc(SourceLoc::from_line(line_no)),
+            );
+        }
+        dialogs.extend(commands);
+        dialogs
+    }
+
+    fn split_segments<'a>(
+        line: &'a str,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<Segment<'a>> {
+        let mut segments = Vec::new();
+        let mut rest = line;
+        let mut text_start = 0;
+        let mut pos = 0;
+
+        while pos < rest.len() {
+            let s = &rest[pos..];
+            let found = if s.starts_with("[[") {
+                s.find("]]").map(|i| (i + 2, Self::link(&s[2..i])))
+            } else if s.starts_with("<<") {
+                s.find(">>").map(|i| (i + 2, Segment::Macro(&s[..i + 2])))
+            } else if s.starts_with('(') && Self::is_harlowe_macro(&s[1..]) {
+                let len = Self::harlowe_macro_len(s);
+                if len.is_none() {
+                    diagnostics.push(Diagnostic::warning(
+                        line_no,
+                        format!("unterminated macro \"{}\", kept as text", s),
+                    ));
+                }
+                len.map(|len| (len, Segment::Macro(&s[..len])))
+            } else {
+                None
+            };
+
+            match found {
+                Some((len, seg)) => {
+                    if text_start < pos {
+                        segments.push(Segment::Text(&rest[text_start..pos]));
+                    }
+                    segments.push(seg);
+                    rest = &rest[pos + len..];
+                    text_start = 0;
+                    pos = 0;
+                }
+                None => pos += s.chars().next().map_or(1, char::len_utf8),
+            }
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest));
+        }
+
+        segments
+    }
+
+    // "text", "text|Target", "text->Target", "Target<-text"
+    fn link(s: &str) -> Segment<'_> {
+        if let Some((text, target)) = s.split_once('|') {
+            Segment::Link(text.trim(), target.trim())
+        } else if let Some((text, target)) = s.rsplit_once("->") {
+            Segment::Link(text.trim(), target.trim())
+        } else if let Some((target, text)) = s.split_once("<-") {
+            Segment::Link(text.trim(), target.trim())
+        } else {
+            Segment::Link(s.trim(), s.trim())
+        }
+    }
+
+    // "(name:" e.g. "(set: $x to 1)"
+    fn is_harlowe_macro(s: &str) -> bool {
+        match s.find(':') {
+            Some(i) => {
+                i > 0
+                    && s[..i]
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+            }
+            None => false,
+        }
+    }
+
+    // length of "(macro: ...)" and the following "[hook]"
+    fn harlowe_macro_len(s: &str) -> Option<usize> {
+        let len = Self::balanced_len(s, '(', ')')?;
+        let hook = &s[len..];
+        if hook.starts_with('[') && !hook.starts_with("[[") {
+            Self::balanced_len(hook, '[', ']').map(|h| len + h)
+        } else {
+            Some(len)
+        }
+    }
+
+    // length of s until the bracket at s[0] is closed, brackets in quotes are skipped
+    fn balanced_len(s: &str, open: char, close: char) -> Option<usize> {
+        let mut depth = 0;
+        let mut quote = None;
+        for (i, c) in s.char_indices() {
+            match c {
+                '"' | '\'' if quote.is_none() => quote = Some(c),
+                _ if Some(c) == quote => quote = None,
+                _ if quote.is_some() => {}
+                _ if c == open => depth += 1,
+                _ if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i + c.len_utf8());
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // "<<goto "Shop">>", "<<goto [[Shop]]>>", "(go-to: "Shop")"
+    fn goto_target(m: &str) -> Option<&str> {
+        let body = if let Some(b) = m.strip_prefix("<<") {
+            b.strip_suffix(">>")?.trim()
+        } else {
+            m.strip_prefix('(')?.strip_suffix(')')?.trim()
+        };
+        let name_len = body
+            .find(|c: char| c.is_whitespace() || c == ':')
+            .unwrap_or(body.len());
+        if !GOTO_MACROS.contains(&&body[..name_len]) {
+            return None;
+        }
+
+        let arg = body[name_len..].trim_start_matches(':').trim();
+        let target = arg
+            .strip_prefix("[[")
+            .and_then(|a| a.strip_suffix("]]"))
+            .or_else(|| arg.strip_prefix('"').and_then(|a| a.strip_suffix('"')))
+            .or_else(|| arg.strip_prefix('\'').and_then(|a| a.strip_suffix('\'')))?;
+        Some(target)
+    }
+
+    fn gen_jump(target: &str, line_no: usize) -> Dialog {
+        let args = vec![DialogBody::Arg(CommandArg::gen_ident(title::to_title(
+            target, ROOT_SCENE,
+        )))];
+        Dialog::from_dialog_data(DialogKind::Command, JUMP_COMMAND, args)
+            .with_loc(SourceLoc::from_line(line_no))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Twee;
+    use crate::core::command::{CommandArg, InlineCommand};
+    use crate::core::dialog::{Dialog, DialogBody, DialogKind};
+    use crate::core::kukuri_data::KukuriData;
+    use crate::core::meta::MetaValue;
+
+    fn gen_jump(target: &str) -> Dialog {
+        Dialog::from_dialog_data(
+            DialogKind::Command,
+            "jump",
+            vec![DialogBody::Arg(CommandArg::gen_ident(target))],
+        )
+    }
+
+    fn gen_raw(source: &str) -> Dialog {
+        Dialog::from_dialog_data(
+            DialogKind::Command,
+            "raw",
+            vec![DialogBody::Arg(CommandArg::gen_str(source))],
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let twee_src = r#"
+:: StoryTitle
+Quest
+
+:: Start [intro town] {"position":"100,200","size":"100,100"}
+Alice: Welcome to the [[shop|Dark Shop]]. /* comment */
+<<set $gold to 10>>
+[[Leave->End]]
+
+:: Dark Shop
+(set: $visited to true)
+Bob: Take (if: $gold > 5)[a potion]<<sound "bell">> please.
+<<goto "End">>
+
+:: End
+Bye.
+"#;
+        let (data, diagnostics) = Twee::parse_with_namespace(twee_src, "");
+        let scenes = match data.without_locs() {
+            KukuriData::Scenes(scenes) => scenes,
+            _ => Vec::new(),
+        };
+        assert!(diagnostics.is_empty());
+
+        let titles: Vec<&str> = scenes.iter().map(|sc| sc.title.as_str()).collect();
+        assert_eq!(vec!["Start", "Dark_Shop", "End"], titles);
+        assert_eq!(
+            Some(&MetaValue::Array(vec![
+                MetaValue::Str(String::from("intro")),
+                MetaValue::Str(String::from("town")),
+            ])),
+            scenes[0].meta.get("tags")
+        );
+        assert_eq!(
+            Some(&MetaValue::Str(String::from("100,200"))),
+            scenes[0].meta.get("position")
+        );
+        assert_eq!(
+            Some(&MetaValue::Str(String::from("Dark Shop"))),
+            scenes[1].meta.get("name")
+        );
+
+        let start = &scenes[0].dialogs;
+        assert_eq!("Start_1_Alice", start[0].id);
+        assert_eq!(
+            DialogBody::Text(String::from("Welcome to the shop.")),
+            start[0].args[0]
+        );
+        assert_eq!(gen_raw("<<set $gold to 10>>"), start[1]);
+        assert_eq!("Start_2_C1", start[2].id);
+        let options: Vec<(&str, &str, &Vec<Dialog>)> = start[2]
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                DialogBody::Choice(cd) => Some((cd.id.as_str(), cd.label.as_str(), &cd.dialogs)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("Start_2_C1L1", "shop", &vec![gen_jump("Dark_Shop")]),
+                ("Start_2_C1L2", "Leave", &vec![gen_jump("End")]),
+            ],
+            options
+        );
+
+        let shop = &scenes[1].dialogs;
+        assert_eq!(gen_raw("(set: $visited to true)"), shop[0]);
+        assert_eq!(
+            DialogBody::Text(String::from("Take  please.")),
+            shop[1].args[0]
+        );
+        assert_eq!(
+            vec![
+                InlineCommand::from_command_data(
+                    5,
+                    "raw",
+                    vec![CommandArg::gen_str("(if: $gold > 5)[a potion]")]
+                ),
+                InlineCommand::from_command_data(
+                    5,
+                    "raw",
+                    vec![CommandArg::gen_str("<<sound \"bell\">>")]
+                ),
+            ],
+            shop[1].inline_commands
+        );
+        assert_eq!(gen_jump("End"), shop[2]);
+        assert_eq!("End_1_unknown", scenes[2].dialogs[0].id);
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let twee_src = ":: Odd \\[name\\] [a\\]b] {bad json}\n:: Lib [widget]\n<<widget \"x\">><</widget>>\n:: Style [stylesheet]\nbody {}\n";
+        let (data, diagnostics) = Twee::parse_with_namespace(twee_src, "quest");
+        let scenes = match data {
+            KukuriData::Scenes(scenes) => scenes,
+            _ => Vec::new(),
+        };
+
+        assert_eq!(1, scenes.len());
+        assert_eq!("quest.Odd_name", scenes[0].title);
+        assert_eq!(
+            Some(&MetaValue::Str(String::from("Odd [name]"))),
+            scenes[0].meta.get("name")
+        );
+        assert_eq!(
+            Some(&MetaValue::Array(vec![MetaValue::Str(String::from("a]b"))])),
+            scenes[0].meta.get("tags")
+        );
+
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(2, messages.len());
+        assert!(messages[0].starts_with("1: warning: passage metadata is not valid json"));
+        assert_eq!(
+            "2: warning: widget passage \"Lib\" is not supported, ignored",
+            messages[1]
+        );
+    }
+}
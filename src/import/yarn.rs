@@ -0,0 +1,675 @@
+use super::{command, comment, escape};
+use crate::core::diagnostic::{Diagnostic, SourceLoc};
+use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+use crate::core::kukuri_data::KukuriData;
+use crate::core::markup::{RichText, RichTextBuilder, Style};
+use crate::core::meta::MetaValue;
+use crate::core::namespace;
+
+const HEADER_END: &str = "---";
+const NODE_END: &str = "===";
+const OPTION_SYMBOL: &str = "->";
+const LINE_GROUP_SYMBOL: &str = "=>";
+const LINE_ID_TAG: &str = "#line:";
+
+// Yarn Spinner commands without a kukuri counterpart
+const CONDITION_COMMANDS: [&str; 6] = ["if", "elseif", "else", "endif", "once", "endonce"];
+const VARIABLE_COMMANDS: [&str; 3] = ["set", "declare", "local"];
+const FLOW_COMMANDS: [&str; 3] = ["detour", "return", "call"];
+
+// Import Yarn Spinner script.
+//
+// node           => Scene, "title" header is scene title
+// Name: text     => DialogKind::Dialog
+// -> option      => DialogKind::Choices, indented lines are option dialogs
+// <<cmd args>>   => DialogKind::Command
+// #line:abc123   => dialog (or choice) id
+// [b]text[/b]    => styled span
+pub struct Yarn;
+
+// a non-empty line of node body
+struct BodyLine {
+    line_no: usize,
+    indent: usize,
+    text: String,
+}
+
+impl Yarn {
+    // node titles are qualified with namespace
+    pub fn parse_with_namespace(content: &str, namespace: &str) -> (KukuriData, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let mut scenes = Vec::new();
+        let mut header: Vec<(usize, String)> = Vec::new();
+        let mut body: Vec<BodyLine> = Vec::new();
+        let mut is_body = false;
+        let mut node_line = 0;
+
+        let content = comment::strip(content, &[("//", "\n")]);
+
+        for (i, line) in content.lines().enumerate() {
+            let line_no = i + 1;
+            let trimmed = line.trim();
+
+            if !is_body {
+                if trimmed == HEADER_END {
+                    is_body = true;
+                    if header.is_empty() {
+                        node_line = line_no;
+                    }
+                } else if !trimmed.is_empty() {
+                    if header.is_empty() {
+                        node_line = line_no;
+                    }
+                    header.push((line_no, String::from(trimmed)));
+                }
+                continue;
+            }
+
+            if trimmed == NODE_END {
+                let sc = Self::node_process(namespace, node_line, &header, &body, &mut diagnostics);
+                scenes.push(sc);
+                header.clear();
+                body.clear();
+                is_body = false;
+                continue;
+            }
+
+            if !trimmed.is_empty() {
+                body.push(BodyLine {
+                    line_no,
+                    indent: line.len() - line.trim_start().len(),
+                    text: String::from(trimmed),
+                });
+            }
+        }
+
+        if is_body {
+            diagnostics.push(Diagnostic::warning(
+                node_line,
+                format!("node is not closed with \"{}\"", NODE_END),
+            ));
+            let sc = Self::node_process(namespace, node_line, &header, &body, &mut diagnostics);
+            scenes.push(sc);
+        } else if !header.is_empty() {
+            diagnostics.push(Diagnostic::error(
+                node_line,
+                format!("node header has no body, expected \"{}\"", HEADER_END),
+            ));
+        }
+
+        (KukuriData::from_scenes(scenes), diagnostics)
+    }
+
+    fn node_process(
+        namespace: &str,
+        node_line: usize,
+        header: &[(usize, String)],
+        body: &[BodyLine],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Scene {
+        let mut sc = Scene::new();
+        sc.loc = SourceLoc::from_line(node_line);
+        let mut title = String::new();
+
+        for (line_no, line) in header {
+            let (key, value) = match line.split_once(':') {
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => {
+                    diagnostics.push(Diagnostic::warning(
+                        *line_no,
+                        format!("invalid node header \"{}\", expected \"key: value\"", line),
+                    ));
+                    continue;
+                }
+            };
+
+            match key {
+                "title" => title = String::from(value),
+                "tags" => {
+                    let tags = value
+                        .split_whitespace()
+                        .map(|t| MetaValue::Str(String::from(t)))
+                        .collect();
+                    sc.meta.insert(String::from(key), MetaValue::Array(tags));
+                }
+                _ => {
+                    sc.meta
+                        .insert(String::from(key), MetaValue::Str(String::from(value)));
+                }
+            }
+        }
+
+        if title.is_empty() {
+            diagnostics.push(Diagnostic::error(node_line, "node has no title"));
+            title = String::from("UnknownScene");
+        }
+        sc.title = namespace::qualify(namespace, &title);
+        sc.dialogs = Self::block_process(body, &sc.title, diagnostics);
+        sc
+    }
+
+    // label: id prefix of dialogs in this block
+    fn block_process(
+        lines: &[BodyLine],
+        label: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<Dialog> {
+        let mut dialogs = Vec::new();
+        // count of dialogs and choices, for id generation
+        let mut idx = 0;
+        let mut i = 0;
+
+        while i < lines.len() {
+            let line = &lines[i];
+
+            if line.text.starts_with(OPTION_SYMBOL) {
+                idx += 1;
+                let choices_id = format!("{}_{}_C1", label, idx);
+                let mut choices =
+                    Dialog::from_dialog_data(DialogKind::Choices, &choices_id, Vec::new())
+                        .with_loc(SourceLoc::from_line(line.line_no));
+
+                // options at the same indent, each followed by deeper lines
+                while i < lines.len()
+                    && lines[i].indent == line.indent
+                    && lines[i].text.starts_with(OPTION_SYMBOL)
+                {
+                    let option = &lines[i];
+                    let end = lines[i + 1..]
+                        .iter()
+                        .position(|l| l.indent <= option.indent)
+                        .map_or(lines.len(), |n| i + 1 + n);
+
+                    let id = format!("{}L{}", choices_id, choices.args.len() + 1);
+                    let cd = Self::option_process(option, &id, &lines[i + 1..end], diagnostics);
+                    choices.args.push(DialogBody::Choice(cd));
+                    i = end;
+                }
+
+                dialogs.push(choices);
+                continue;
+            }
+
+            if line.text.starts_with("<<") {
+                if let Some(d) = Self::command_process(&line.text, line.line_no, diagnostics) {
+                    dialogs.push(d);
+                }
+                i += 1;
+                continue;
+            }
+
+            idx += 1;
+            let id_prefix = format!("{}_{}", label, idx);
+            dialogs.push(Self::dialog_process(line, &id_prefix, diagnostics));
+            i += 1;
+        }
+
+        dialogs
+    }
+
+    fn dialog_process(
+        line: &BodyLine,
+        id_prefix: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Dialog {
+        let mut text = line.text.as_str();
+        if let Some(s) = text.strip_prefix(LINE_GROUP_SYMBOL) {
+            diagnostics.push(Diagnostic::warning(
+                line.line_no,
+                "line groups are not supported, the line is imported as a normal line",
+            ));
+            text = s.trim();
+        }
+
+        let text = Self::strip_condition(text, line.line_no, diagnostics);
+        let (text, line_id) = Self::tags_process(text, line.line_no, diagnostics);
+
+        // "\:" is not a talker separator
+        let (talker, text) = match escape::find_unescaped(text, ':') {
+            Some(i) => (text[..i].trim(), text[i + 1..].trim()),
+            None => ("unknown", text),
+        };
+        let rich = Self::text_process(text, line.line_no, diagnostics);
+
+        let id = line_id.unwrap_or_else(|| format!("{}_{}", id_prefix, talker));
+        let body = vec![
+            DialogBody::from_rich_text(rich),
+            DialogBody::gen_text(format!("TALKER_{}", talker)),
+        ];
+        Dialog::from_dialog_data(DialogKind::Dialog, id, body)
+            .with_loc(SourceLoc::from_line(line.line_no))
+    }
+
+    fn option_process(
+        option: &BodyLine,
+        id: &str,
+        children: &[BodyLine],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> ChoiceData {
+        let text = option.text[OPTION_SYMBOL.len()..].trim();
+        let text = Self::strip_condition(text, option.line_no, diagnostics);
+        let (text, line_id) = Self::tags_process(text, option.line_no, diagnostics);
+        let label = Self::text_process(text, option.line_no, diagnostics).plain_text();
+
+        let mut cd = ChoiceData::from_texts(line_id.as_deref().unwrap_or(id), label)
+            .with_loc(SourceLoc::from_line(option.line_no));
+        // option dialogs are labeled by generated id, even if the option has a line id
+        cd.dialogs = Self::block_process(children, id, diagnostics);
+        cd
+    }
+
+    // "<<wait 0.5>>" => Command "wait"
+    fn command_process(
+        text: &str,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<Dialog> {
+        let (body, rest) = match text[2..].split_once(">>") {
+            Some(x) => x,
+            None => {
+                diagnostics.push(Diagnostic::error(
+                    line_no,
+                    format!("unterminated command \"{}\", expected \">>\"", text),
+                ));
+                return None;
+            }
+        };
+        if !rest.trim().is_empty() {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!("text after command is ignored: \"{}\"", rest.trim()),
+            ));
+        }
+
+        let body = body.trim();
+        let (id, args_str) = match body.find(char::is_whitespace) {
+            Some(i) => body.split_at(i),
+            None => (body, ""),
+        };
+
+        let reason = if CONDITION_COMMANDS.contains(&id) {
+            Some("lines in it are imported unconditionally")
+        } else if VARIABLE_COMMANDS.contains(&id) {
+            Some("variables are ignored")
+        } else if FLOW_COMMANDS.contains(&id) {
+            Some("use \"<<jump>>\" instead")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!("\"<<{}>>\" is not supported, {}", id, reason),
+            ));
+            return None;
+        }
+
+        if args_str.contains('{') || args_str.contains('$') {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!("expressions in \"<<{}>>\" are kept as text", body),
+            ));
+        }
+
+        let args = command::parse_args(args_str, line_no, diagnostics)
+            .into_iter()
+            .map(DialogBody::Arg)
+            .collect();
+        Some(
+            Dialog::from_dialog_data(DialogKind::Command, id, args)
+                .with_loc(SourceLoc::from_line(line_no)),
+        )
+    }
+
+    // drop a line condition e.g. "Hi <<if $met>>"
+    fn strip_condition<'a>(
+        text: &'a str,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> &'a str {
+        match text.find("<<") {
+            Some(i) => {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!(
+                        "condition \"{}\" is not supported, the line is always shown",
+                        text[i..].trim()
+                    ),
+                ));
+                text[..i].trim_end()
+            }
+            None => text,
+        }
+    }
+
+    // split trailing hashtags.
+    // return: (text, line id of "#line:" tag)
+    fn tags_process<'a>(
+        text: &'a str,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> (&'a str, Option<String>) {
+        let i = match escape::find_unescaped(text, '#') {
+            Some(i) => i,
+            None => return (text, None),
+        };
+
+        let mut line_id = None;
+        for tag in text[i..].split_whitespace() {
+            if tag.starts_with(LINE_ID_TAG) && tag.len() > LINE_ID_TAG.len() {
+                line_id = Some(String::from(&tag[1..]));
+            } else {
+                diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!("tag \"{}\" is ignored", tag),
+                ));
+            }
+        }
+        (text[..i].trim_end(), line_id)
+    }
+
+    // Yarn markup into span tree
+    //
+    // [b]text[/b], [color=red]text[/color] => styled span
+    // [wave]text[/wave]                    => custom style
+    // [/]                                  => close all
+    // {$name}                              => kept as text
+    fn text_process(text: &str, line_no: usize, diagnostics: &mut Vec<Diagnostic>) -> RichText {
+        let mut builder = RichTextBuilder::new();
+        // names of opened markups
+        let mut opened: Vec<String> = Vec::new();
+        let mut chars = text.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(c) => builder.push_str(c.encode_utf8(&mut [0; 4])),
+                    None => builder.push_str("\\"),
+                },
+                '[' | '{' => {
+                    let close = if c == '[' { ']' } else { '}' };
+                    let rest = chars.as_str();
+                    let body = match rest.find(close) {
+                        Some(i) => &rest[..i],
+                        None => {
+                            diagnostics.push(Diagnostic::warning(
+                                line_no,
+                                format!("unterminated \"{}{}\", kept as text", c, rest),
+                            ));
+                            builder.push_str(c.encode_utf8(&mut [0; 4]));
+                            continue;
+                        }
+                    };
+                    chars = rest[body.len() + 1..].chars();
+
+                    if c == '[' {
+                        Self::markup_process(body, &mut builder, &mut opened, line_no, diagnostics);
+                    } else {
+                        diagnostics.push(Diagnostic::warning(
+                            line_no,
+                            format!(
+                                "inline expression \"{{{}}}\" is not supported, kept as text",
+                                body
+                            ),
+                        ));
+                        builder.push_str(&format!("{{{}}}", body));
+                    }
+                }
+                _ => builder.push_str(c.encode_utf8(&mut [0; 4])),
+            }
+        }
+
+        let (rich, _) = builder.finish();
+        for name in opened {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!("unclosed markup \"[{}]\"", name),
+            ));
+        }
+        rich
+    }
+
+    fn markup_process(
+        body: &str,
+        builder: &mut RichTextBuilder,
+        opened: &mut Vec<String>,
+        line_no: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let body = body.trim();
+
+        if let Some(name) = body.strip_prefix('/') {
+            let name = name.trim();
+            let pos = if name.is_empty() {
+                Some(0)
+            } else {
+                opened.iter().rposition(|n| n == name)
+            };
+            match pos {
+                Some(pos) => {
+                    for _ in pos..opened.len() {
+                        // never fails, a style is opened for each name
+                        let _ = builder.close("");
+                    }
+                    opened.truncate(pos);
+                }
+                None => diagnostics.push(Diagnostic::warning(
+                    line_no,
+                    format!("closing markup \"[/{}]\" has no opening markup", name),
+                )),
+            }
+            return;
+        }
+
+        if body.ends_with('/') {
+            diagnostics.push(Diagnostic::warning(
+                line_no,
+                format!(
+                    "self-closing markup \"[{}]\" is not supported, ignored",
+                    body
+                ),
+            ));
+            return;
+        }
+
+        let name = body
+            .split(|c: char| c.is_whitespace() || c == '=')
+            .next()
+            .unwrap_or("");
+        let style = match Style::parse(body) {
+            Some(s) => s,
+            None => {
+                if name != body {
+                    diagnostics.push(Diagnostic::warning(
+                        line_no,
+                        format!("properties of markup \"[{}]\" are dropped", body),
+                    ));
+                }
+                Style::Custom(String::from(name))
+            }
+        };
+        builder.open(style);
+        opened.push(String::from(name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Yarn;
+    use crate::core::command::{CommandArg, Float};
+    use crate::core::diagnostic::Diagnostic;
+    use crate::core::dialog::{ChoiceData, Dialog, DialogBody, DialogKind, Scene};
+    use crate::core::kukuri_data::KukuriData;
+    use crate::core::markup::{RichText, Span, Style};
+    use crate::core::meta::MetaValue;
+
+    fn gen_dialog(id: &str, talker: &str, text: &str) -> Dialog {
+        Dialog::from_dialog_data(
+            DialogKind::Dialog,
+            id,
+            vec![
+                DialogBody::from_rich_text(RichText::from_plain(text)),
+                DialogBody::gen_text(format!("TALKER_{}", talker)),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let yarn_src = "\
+title: Start
+tags: intro  shop
+position: 0,0
+---
+// comment
+Alice: Hello. #line:a1b2c3
+Hmm...
+-> Buy #line:opt01
+    Bob: Thanks!
+    <<wait 0.5>>
+-> Leave <<if $can_leave>>
+<<jump Other>>
+===
+title: Other
+---
+Bob: Bye
+===
+";
+        let (data, diagnostics) = Yarn::parse_with_namespace(yarn_src, "");
+
+        let mut buy = ChoiceData::from_texts("line:opt01", "Buy");
+        buy.dialogs = vec![
+            gen_dialog("Start_3_C1L1_1_Bob", "Bob", "Thanks!"),
+            Dialog::from_dialog_data(
+                DialogKind::Command,
+                "wait",
+                vec![DialogBody::Arg(CommandArg::Float(Float(0.5)))],
+            ),
+        ];
+        let leave = ChoiceData::from_texts("Start_3_C1L2", "Leave");
+
+        let mut start = Scene::new();
+        start.title = String::from("Start");
+        start.meta.insert(
+            String::from("tags"),
+            MetaValue::Array(vec![
+                MetaValue::Str(String::from("intro")),
+                MetaValue::Str(String::from("shop")),
+            ]),
+        );
+        start.meta.insert(
+            String::from("position"),
+            MetaValue::Str(String::from("0,0")),
+        );
+        start.dialogs = vec![
+            gen_dialog("line:a1b2c3", "Alice", "Hello."),
+            gen_dialog("Start_2_unknown", "unknown", "Hmm..."),
+            Dialog::from_dialog_data(
+                DialogKind::Choices,
+                "Start_3_C1",
+                vec![DialogBody::Choice(buy), DialogBody::Choice(leave)],
+            ),
+            Dialog::from_dialog_data(
+                DialogKind::Command,
+                "jump",
+                vec![DialogBody::Arg(CommandArg::gen_ident("Other"))],
+            ),
+        ];
+
+        let mut other = Scene::new();
+        other.title = String::from("Other");
+        other.dialogs = vec![gen_dialog("Other_1_Bob", "Bob", "Bye")];
+
+        assert_eq!(
+            KukuriData::from_scenes(vec![start, other]),
+            data.without_locs()
+        );
+        assert_eq!(
+            vec![Diagnostic::warning(
+                11,
+                "condition \"<<if $can_leave>>\" is not supported, the line is always shown"
+            )],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_parse_markup() {
+        let mut diagnostics = Vec::new();
+        let rich = Yarn::text_process("[b]Hi[/b] [wave]all[/] \\[{$name}]", 1, &mut diagnostics);
+
+        assert_eq!(
+            RichText {
+                spans: vec![
+                    Span::Styled(Style::Bold, vec![Span::Text(String::from("Hi"))]),
+                    Span::Text(String::from(" ")),
+                    Span::Styled(
+                        Style::Custom(String::from("wave")),
+                        vec![Span::Text(String::from("all"))]
+                    ),
+                    Span::Text(String::from(" [{$name}]")),
+                ]
+            },
+            rich
+        );
+        assert_eq!(
+            vec![Diagnostic::warning(
+                1,
+                "inline expression \"{$name}\" is not supported, kept as text"
+            )],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_parse_unsupported() {
+        let yarn_src = "\
+title: Flow
+---
+<<if $met>>
+    => A: Again? #lastline
+<<endif>>
+<<set $met to true>>
+<<wait 1
+===
+position: 1,1
+---
+A: no title
+";
+        let (data, diagnostics) = Yarn::parse_with_namespace(yarn_src, "");
+
+        if let KukuriData::Scenes(scenes) = &data {
+            assert_eq!(
+                vec!["Flow_1_A"],
+                scenes[0]
+                    .dialogs
+                    .iter()
+                    .map(|d| d.id.as_str())
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!("UnknownScene", scenes[1].title);
+        }
+        assert_eq!(
+            vec![
+                Diagnostic::warning(
+                    3,
+                    "\"<<if>>\" is not supported, lines in it are imported unconditionally"
+                ),
+                Diagnostic::warning(
+                    4,
+                    "line groups are not supported, the line is imported as a normal line"
+                ),
+                Diagnostic::warning(4, "tag \"#lastline\" is ignored"),
+                Diagnostic::warning(
+                    5,
+                    "\"<<endif>>\" is not supported, lines in it are imported unconditionally"
+                ),
+                Diagnostic::warning(6, "\"<<set>>\" is not supported, variables are ignored"),
+                Diagnostic::error(7, "unterminated command \"<<wait 1\", expected \">>\""),
+                Diagnostic::warning(9, "node is not closed with \"===\""),
+                Diagnostic::error(9, "node has no title"),
+            ],
+            diagnostics
+        );
+    }
+}
@@ -6,7 +6,12 @@ mod import;
 mod utils;
 
 fn main() {
-    let matches = cli::build_cli().get_matches();
+    let app_matches = cli::build_cli().get_matches();
+    // global options are also given after subcommand
+    let matches = match app_matches.subcommand() {
+        (_, Some(sub_matches)) => sub_matches,
+        _ => &app_matches,
+    };
 
     let conf = match matches.value_of("config") {
         Some(path) => config::Config::from_file(path),
@@ -31,5 +36,14 @@ fn main() {
         // kukuri.import(path);
     }
 
-    kukuri.run();
+    match app_matches.subcommand_name() {
+        Some("glyphs") => kukuri.run_glyphs(),
+        Some("check-fonts") => {
+            if !kukuri.run_check_fonts() {
+                std::process::exit(1);
+            }
+        }
+        Some("decompile") => kukuri.run_decompile(),
+        _ => kukuri.run(),
+    }
 }
@@ -7,6 +7,10 @@ pub fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
     fs::read_to_string(path)
 }
 
+pub fn read_binary_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    fs::read(path)
+}
+
 pub fn write_file<P: AsRef<Path>>(path: P, s: &str) -> Result<()> {
     fs::write(path, s)
 }
@@ -15,9 +19,47 @@ pub fn mkdir_recursive<P: AsRef<Path>>(path: P) -> Result<()> {
     fs::create_dir_all(path)
 }
 
+// Levenshtein distance between two strings
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+
+    prev[b.len()]
+}
+
 // pub fn cargo_manifest_dir() -> PathBuf {
 //     match std::env::var("CARGO_MANIFEST_DIR") {
 //         Ok(s) => PathBuf::from(s),
 //         Err(e) => panic!("get_cargo_manifest_dir: Unable to get CARGO_MANIFEST_DIR: {:?}", e),
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::edit_distance;
+
+    #[test]
+    fn test_edit_distance() {
+        let tests = [
+            ("", "", 0),
+            ("jump", "jump", 0),
+            ("shwo", "show", 2),
+            ("jamp", "jump", 1),
+            ("bgm", "", 3),
+            ("かんじ", "かな", 2),
+        ];
+
+        for &(a, b, expected) in &tests {
+            assert_eq!(expected, edit_distance(a, b));
+        }
+    }
+}